@@ -0,0 +1,421 @@
+use std::io;
+use std::time::SystemTime;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::download::Downloader;
+
+pub const DEFAULT_API_BASE_URL: &str = "https://api.faforever.com";
+
+/// The related resources needed to know the players, their ratings and the map of a game
+const INCLUDE: &str = "include=playerStats,playerStats.player,mapVersion,mapVersion.map";
+
+/// A game as recorded by the FAF API
+#[derive(Serialize)]
+pub struct OnlineGame {
+    pub id: u32,
+    pub title: Option<String>,
+    pub map_name: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub players: Vec<OnlinePlayer>,
+}
+
+#[derive(Serialize)]
+pub struct OnlinePlayer {
+    pub login: String,
+    /// The displayed rating before the game, mean - 3 * deviation
+    pub rating: Option<i32>,
+}
+
+/// What is known locally about a game to look it up
+pub struct GameQuery<'a> {
+    /// Only legacy replays know the id of their game
+    pub game_id: Option<u32>,
+    pub map_name: Option<&'a str>,
+    pub player_names: Vec<&'a str>,
+    /// The game started before this, e.g. the modification time of the replay file
+    pub played_before: Option<SystemTime>,
+}
+
+/// Filters of the `search` command
+pub struct GameSearch<'a> {
+    pub player_name: &'a str,
+    pub map_name: Option<&'a str>,
+    /// Technical name of the featured mod, e.g. `faf`
+    pub featured_mod: Option<&'a str>,
+    /// `YYYY-MM-DD`, games started on or after this day
+    pub since: Option<&'a str>,
+    pub page_size: u32,
+    /// Starts at 1
+    pub page: u32,
+}
+
+pub struct FafApi<'a> {
+    downloader: &'a Downloader,
+    base_url: &'a str,
+    /// Sent as bearer token, some data is only visible to logged in users
+    token: Option<String>,
+}
+
+impl<'a> FafApi<'a> {
+    pub fn new(downloader: &'a Downloader, base_url: &'a str) -> FafApi<'a> {
+        FafApi {
+            downloader,
+            base_url: base_url.trim_end_matches('/'),
+            token: None,
+        }
+    }
+
+    /// Authenticates all requests with the access token, without one they are anonymous
+    pub fn with_token(mut self, token: Option<String>) -> FafApi<'a> {
+        self.token = token;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url
+    }
+
+    /// Looks the game up by id if known, otherwise by map, players and date
+    pub fn find_game(&self, query: &GameQuery) -> io::Result<Option<OnlineGame>> {
+        let url = match query.game_id {
+            Some(game_id) => format!("{}/data/game/{}?{}", self.base_url, game_id, INCLUDE),
+            None => self.get_games_url(&get_game_filter(query), 1, 1),
+        };
+
+        Ok(self.fetch_games(&url)?.into_iter().next())
+    }
+
+    /// The url `search_games` fetches, useful to debug the filter syntax
+    pub fn get_search_url(&self, search: &GameSearch) -> String {
+        self.get_games_url(&get_search_filter(search), search.page_size, search.page)
+    }
+
+    /// Latest games first
+    pub fn search_games(&self, search: &GameSearch) -> io::Result<Vec<OnlineGame>> {
+        self.fetch_games(&self.get_search_url(search))
+    }
+
+    /// Looks a player up by exact login, returns the player id
+    pub fn find_player(&self, login: &str) -> io::Result<Option<u32>> {
+        let url = format!(
+            "{}/data/player?filter={}",
+            self.base_url,
+            encode_query_component(&format!("login=={}", quote_rsql(login)))
+        );
+
+        let document = self.downloader.fetch_json(&url, self.token.as_deref())?;
+
+        Ok(document["data"]
+            .as_array()
+            .and_then(|players| players.first())
+            .and_then(|player| player["id"].as_str())
+            .and_then(|id| id.parse().ok()))
+    }
+
+    /// Logins containing `login`, to suggest when there is no exact match
+    pub fn find_similar_players(&self, login: &str) -> io::Result<Vec<String>> {
+        let url = format!(
+            "{}/data/player?filter={}&page[size]=10",
+            self.base_url,
+            encode_query_component(&format!("login=={}", quote_rsql(&format!("*{}*", login))))
+        );
+
+        let document = self.downloader.fetch_json(&url, self.token.as_deref())?;
+        let players = document["data"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        Ok(players
+            .iter()
+            .filter_map(|player| player["attributes"]["login"].as_str())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The most recent game of the player, optionally only considering finished ones
+    pub fn find_latest_game(
+        &self,
+        player_id: u32,
+        finished_only: bool,
+    ) -> io::Result<Option<OnlineGame>> {
+        let mut filter = format!(
+            "playerStats.player.id=={}",
+            quote_rsql(&player_id.to_string())
+        );
+        if finished_only {
+            filter.push_str(";endTime=isnull=false");
+        }
+
+        Ok(self
+            .fetch_games(&self.get_games_url(&filter, 1, 1))?
+            .into_iter()
+            .next())
+    }
+
+    fn get_games_url(&self, filter: &str, page_size: u32, page: u32) -> String {
+        format!(
+            "{}/data/game?filter={}&sort=-startTime&page[size]={}&page[number]={}&{}",
+            self.base_url,
+            encode_query_component(filter),
+            page_size,
+            page,
+            INCLUDE
+        )
+    }
+
+    fn fetch_games(&self, url: &str) -> io::Result<Vec<OnlineGame>> {
+        let document = self.downloader.fetch_json(url, self.token.as_deref())?;
+
+        let games = match &document["data"] {
+            Value::Array(games) => games.iter().collect(),
+            Value::Object(_) => vec![&document["data"]],
+            _ => Vec::new(),
+        };
+
+        games
+            .into_iter()
+            .map(|game| parse_game(game, &document["included"]))
+            .collect()
+    }
+}
+
+fn get_game_filter(query: &GameQuery) -> String {
+    let mut conditions = Vec::new();
+
+    if let Some(map_name) = query.map_name {
+        conditions.push(format!(
+            "mapVersion.map.displayName=={}",
+            quote_rsql(map_name)
+        ));
+    }
+    for player_name in &query.player_names {
+        conditions.push(format!(
+            "playerStats.player.login=={}",
+            quote_rsql(player_name)
+        ));
+    }
+    if let Some(played_before) = query.played_before {
+        conditions.push(format!(
+            "startTime=le={}",
+            quote_rsql(&humantime::format_rfc3339_seconds(played_before).to_string())
+        ));
+    }
+
+    conditions.join(";")
+}
+
+fn get_search_filter(search: &GameSearch) -> String {
+    let mut conditions = vec![format!(
+        "playerStats.player.login=={}",
+        quote_rsql(search.player_name)
+    )];
+
+    if let Some(map_name) = search.map_name {
+        conditions.push(format!(
+            "mapVersion.map.displayName=={}",
+            quote_rsql(map_name)
+        ));
+    }
+    if let Some(featured_mod) = search.featured_mod {
+        conditions.push(format!(
+            "featuredMod.technicalName=={}",
+            quote_rsql(featured_mod)
+        ));
+    }
+    if let Some(since) = search.since {
+        conditions.push(format!(
+            "startTime=ge={}",
+            quote_rsql(&format!("{}T00:00:00Z", since))
+        ));
+    }
+
+    conditions.join(";")
+}
+
+fn parse_game(game: &Value, included: &Value) -> io::Result<OnlineGame> {
+    let id = game["id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| invalid_response("game without id"))?;
+    let attributes = &game["attributes"];
+
+    let included = included.as_array().map(Vec::as_slice).unwrap_or_default();
+    let find_included = |resource: &Value| {
+        included.iter().find(|candidate| {
+            candidate["type"] == resource["type"] && candidate["id"] == resource["id"]
+        })
+    };
+
+    let player_stats = game["relationships"]["playerStats"]["data"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let players = player_stats
+        .iter()
+        .filter_map(find_included)
+        .map(|stats| {
+            let login = find_included(&stats["relationships"]["player"]["data"])
+                .and_then(|player| player["attributes"]["login"].as_str())
+                .unwrap_or("?")
+                .to_string();
+            let stats = &stats["attributes"];
+            let rating = match (
+                stats["beforeMean"].as_f64(),
+                stats["beforeDeviation"].as_f64(),
+            ) {
+                (Some(mean), Some(deviation)) => Some((mean - 3.0 * deviation).round() as i32),
+                _ => None,
+            };
+
+            OnlinePlayer { login, rating }
+        })
+        .collect();
+
+    let map_name = find_included(&game["relationships"]["mapVersion"]["data"])
+        .and_then(|map_version| find_included(&map_version["relationships"]["map"]["data"]))
+        .and_then(|map| map["attributes"]["displayName"].as_str())
+        .map(str::to_string);
+
+    let get_attribute = |name: &str| attributes[name].as_str().map(str::to_string);
+
+    Ok(OnlineGame {
+        id,
+        title: get_attribute("name"),
+        map_name,
+        start_time: get_attribute("startTime"),
+        end_time: get_attribute("endTime"),
+        players,
+    })
+}
+
+impl OnlineGame {
+    /// Games in progress have no end time yet
+    pub fn is_finished(&self) -> bool {
+        self.end_time.is_some()
+    }
+
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        let start_time = humantime::parse_rfc3339_weak(self.start_time.as_deref()?).ok()?;
+        let end_time = humantime::parse_rfc3339_weak(self.end_time.as_deref()?).ok()?;
+
+        end_time.duration_since(start_time).ok()
+    }
+
+    /// The average rating of all players
+    pub fn average_rating(&self) -> Option<i32> {
+        let ratings: Vec<i32> = self
+            .players
+            .iter()
+            .filter_map(|player| player.rating)
+            .collect();
+
+        match ratings.len() {
+            0 => None,
+            count => Some(ratings.iter().sum::<i32>() / count as i32),
+        }
+    }
+}
+
+fn invalid_response(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unexpected FAF API response: {}", reason),
+    )
+}
+
+fn quote_rsql(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn encode_query_component(component: &str) -> String {
+    component
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn filters_games_by_map_players_and_date() {
+        let query = GameQuery {
+            game_id: None,
+            map_name: Some("Seton's Clutch"),
+            player_names: vec!["Alice", "B\"ob"],
+            played_before: Some(UNIX_EPOCH + Duration::from_secs(1_790_000_000)),
+        };
+
+        assert_eq!(
+            get_game_filter(&query),
+            "mapVersion.map.displayName==\"Seton's Clutch\";\
+             playerStats.player.login==\"Alice\";\
+             playerStats.player.login==\"B\\\"ob\";\
+             startTime=le=\"2026-09-21T14:13:20Z\""
+        );
+        assert_eq!(
+            encode_query_component("a==\"b c\";"),
+            "a%3D%3D%22b%20c%22%3B"
+        );
+    }
+
+    #[test]
+    fn filters_search_by_player_mod_and_day() {
+        let search = GameSearch {
+            player_name: "Alice",
+            map_name: None,
+            featured_mod: Some("ladder1v1"),
+            since: Some("2024-01-01"),
+            page_size: 20,
+            page: 2,
+        };
+
+        assert_eq!(
+            get_search_filter(&search),
+            "playerStats.player.login==\"Alice\";\
+             featuredMod.technicalName==\"ladder1v1\";\
+             startTime=ge=\"2024-01-01T00:00:00Z\""
+        );
+    }
+
+    #[test]
+    fn parses_game_with_included_player_stats() {
+        let document: Value = serde_json::from_str(
+            r#"{
+                "data": {
+                    "type": "game", "id": "42",
+                    "attributes": {"name": "Alice vs Bob", "startTime": "2026-10-01T18:00:00Z"},
+                    "relationships": {"playerStats": {"data": [{"type": "gamePlayerStats", "id": "1"}]}}
+                },
+                "included": [
+                    {
+                        "type": "gamePlayerStats", "id": "1",
+                        "attributes": {"beforeMean": 1500.0, "beforeDeviation": 50.0},
+                        "relationships": {"player": {"data": {"type": "player", "id": "7"}}}
+                    },
+                    {"type": "player", "id": "7", "attributes": {"login": "Alice"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let game = parse_game(&document["data"], &document["included"]).unwrap();
+
+        assert_eq!(game.id, 42);
+        assert_eq!(game.title.as_deref(), Some("Alice vs Bob"));
+        assert_eq!(game.end_time, None);
+        assert_eq!(game.players[0].login, "Alice");
+        assert_eq!(game.players[0].rating, Some(1350));
+    }
+}
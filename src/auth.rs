@@ -0,0 +1,278 @@
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::download::Downloader;
+use crate::paths;
+
+pub const DEFAULT_HYDRA_BASE_URL: &str = "https://hydra.faforever.com";
+pub const DEFAULT_CLIENT_ID: &str = "faf-replay-cli";
+
+/// `offline` makes hydra hand out a refresh token, so the login outlives the access token
+const SCOPE: &str = "openid offline public_profile";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Access tokens are renewed that long before they expire, so they don't expire mid-command
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// What `login` stores, the refresh token gets new access tokens without logging in again
+#[derive(Serialize, Deserialize)]
+pub struct StoredLogin {
+    pub hydra_base_url: String,
+    pub client_id: String,
+    pub refresh_token: String,
+    pub access_token: String,
+    /// Seconds since the unix epoch
+    pub expires_at: u64,
+}
+
+impl StoredLogin {
+    /// `login.json` next to the config file
+    pub fn default_path() -> Option<PathBuf> {
+        paths::login_file()
+    }
+
+    /// The stored login, None if nobody logged in yet
+    pub fn load(path: &Path) -> io::Result<Option<StoredLogin>> {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_slice(&content).map(Some).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid login file {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Replaces the stored login, the file is only accessible by the user (0600 on unix)
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)?;
+
+        // temp files are created with 0600, a replaced file is never readable by others
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.persist(path).map_err(|e| e.error)?;
+
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        unix_seconds(now + EXPIRY_MARGIN) >= self.expires_at
+    }
+}
+
+/// A pending login, the user confirms `user_code` in the browser meanwhile
+pub struct DeviceAuthorization {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// `verification_uri` with the code already filled in
+    pub verification_uri_complete: Option<String>,
+    interval: Duration,
+    expires_in: Duration,
+}
+
+/// The FAF OAuth server
+pub struct Hydra<'a> {
+    downloader: &'a Downloader,
+    base_url: &'a str,
+    client_id: &'a str,
+}
+
+impl<'a> Hydra<'a> {
+    pub fn new(downloader: &'a Downloader, base_url: &'a str, client_id: &'a str) -> Hydra<'a> {
+        Hydra {
+            downloader,
+            base_url: base_url.trim_end_matches('/'),
+            client_id,
+        }
+    }
+
+    /// Starts the device flow, a login without typing the password into the terminal
+    pub fn start_device_login(&self) -> io::Result<DeviceAuthorization> {
+        let url = format!("{}/oauth2/device/auth", self.base_url);
+        let response = self
+            .downloader
+            .post_form(&url, &[("client_id", self.client_id), ("scope", SCOPE)])?;
+        check_oauth_error(&response)?;
+
+        Ok(DeviceAuthorization {
+            device_code: get_string(&response, "device_code")?,
+            user_code: get_string(&response, "user_code")?,
+            verification_uri: get_string(&response, "verification_uri")?,
+            verification_uri_complete: response["verification_uri_complete"]
+                .as_str()
+                .map(str::to_string),
+            interval: Duration::from_secs(response["interval"].as_u64().unwrap_or(5)),
+            expires_in: Duration::from_secs(response["expires_in"].as_u64().unwrap_or(600)),
+        })
+    }
+
+    /// Polls until the user confirmed or denied the login, or the code expired
+    pub fn wait_for_login(&self, authorization: &DeviceAuthorization) -> io::Result<StoredLogin> {
+        let started = Instant::now();
+        let mut interval = authorization.interval;
+
+        loop {
+            if started.elapsed() > authorization.expires_in {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "the code was not confirmed in time",
+                ));
+            }
+            thread::sleep(interval);
+
+            let response = self.request_token(&[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("device_code", &authorization.device_code),
+            ])?;
+
+            match response["error"].as_str() {
+                Some("authorization_pending") => {}
+                // asked by the server to poll less often
+                Some("slow_down") => interval += Duration::from_secs(5),
+                _ => return self.parse_login(&response, None),
+            }
+        }
+    }
+
+    /// Gets a new access token, hydra hands out a new refresh token along with it
+    pub fn refresh(&self, login: &StoredLogin) -> io::Result<StoredLogin> {
+        let response = self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &login.refresh_token),
+        ])?;
+
+        self.parse_login(&response, Some(&login.refresh_token))
+    }
+
+    fn request_token(&self, grant: &[(&str, &str)]) -> io::Result<Value> {
+        let mut form = vec![("client_id", self.client_id)];
+        form.extend_from_slice(grant);
+
+        self.downloader
+            .post_form(&format!("{}/oauth2/token", self.base_url), &form)
+    }
+
+    fn parse_login(
+        &self,
+        response: &Value,
+        previous_refresh_token: Option<&str>,
+    ) -> io::Result<StoredLogin> {
+        check_oauth_error(response)?;
+
+        let refresh_token = match (response["refresh_token"].as_str(), previous_refresh_token) {
+            (Some(refresh_token), _) | (None, Some(refresh_token)) => refresh_token.to_string(),
+            (None, None) => return Err(invalid_response("no refresh token")),
+        };
+        let expires_in = response["expires_in"].as_u64().unwrap_or(0);
+
+        Ok(StoredLogin {
+            hydra_base_url: self.base_url.to_string(),
+            client_id: self.client_id.to_string(),
+            refresh_token,
+            access_token: get_string(response, "access_token")?,
+            expires_at: unix_seconds(SystemTime::now()) + expires_in,
+        })
+    }
+}
+
+/// OAuth servers answer errors with `{"error": "...", "error_description": "..."}`
+fn check_oauth_error(response: &Value) -> io::Result<()> {
+    let error = match response["error"].as_str() {
+        Some(error) => error,
+        None => return Ok(()),
+    };
+
+    let kind = match error {
+        "access_denied" | "invalid_grant" => ErrorKind::PermissionDenied,
+        "expired_token" => ErrorKind::TimedOut,
+        _ => ErrorKind::Other,
+    };
+    let description = response["error_description"]
+        .as_str()
+        .map(|description| format!(": {}", description))
+        .unwrap_or_default();
+
+    Err(io::Error::new(
+        kind,
+        format!("login failed with {}{}", error, description),
+    ))
+}
+
+fn get_string(response: &Value, name: &str) -> io::Result<String> {
+    response[name]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| invalid_response(&format!("no {}", name)))
+}
+
+fn invalid_response(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Unexpected FAF OAuth response: {}", reason),
+    )
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_oauth_errors_with_description() {
+        let denied = serde_json::json!({
+            "error": "access_denied",
+            "error_description": "The resource owner denied the request"
+        });
+
+        let e = check_oauth_error(&denied).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(
+            e.to_string(),
+            "login failed with access_denied: The resource owner denied the request"
+        );
+        assert!(check_oauth_error(&serde_json::json!({"access_token": "a"})).is_ok());
+    }
+
+    #[test]
+    fn stores_login_only_readable_by_the_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("faf-replay-cli").join("login.json");
+        let login = StoredLogin {
+            hydra_base_url: DEFAULT_HYDRA_BASE_URL.to_string(),
+            client_id: DEFAULT_CLIENT_ID.to_string(),
+            refresh_token: "refresh".to_string(),
+            access_token: "access".to_string(),
+            expires_at: 1000,
+        };
+
+        login.save(&path).unwrap();
+        let loaded = StoredLogin::load(&path).unwrap().unwrap();
+
+        assert_eq!(loaded.refresh_token, "refresh");
+        assert!(loaded.is_expired(UNIX_EPOCH + Duration::from_secs(950)));
+        assert!(!loaded.is_expired(UNIX_EPOCH + Duration::from_secs(900)));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::convert::TryInto;
+use std::time::Duration;
+
+use crate::error::ReplayError;
+
+/// Advances the simulation by the number of ticks in its data
+pub const ADVANCE_COMMAND: u8 = 0;
+/// Ends the replay, missing if the recording stopped early
+pub const END_GAME_COMMAND: u8 = 23;
+/// A simulation tick lasts 100ms of game time
+pub const TICK_MILLIS: u64 = 100;
+
+/// A command of the replay body, following the header
+#[derive(Debug, Clone)]
+pub struct ReplayCommand {
+    pub command_type: u8,
+    /// Position of the command in the raw replay
+    pub offset: usize,
+    /// Length including the 3 bytes of type and length
+    pub length: usize,
+    /// Ticks the game advanced before this command
+    pub tick: u32,
+}
+
+/// How many commands and ticks the body of a replay has, see `count_replay_commands`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandCount {
+    pub commands: usize,
+    pub ticks: u32,
+    /// Where the body stopped being parseable, e.g. of a truncated replay. The counts only go up to there then.
+    pub invalid_at: Option<usize>,
+}
+
+impl CommandCount {
+    /// Whether the counts stop at an unparseable part of the body
+    pub fn is_approximate(&self) -> bool {
+        self.invalid_at.is_some()
+    }
+}
+
+/// Splits the body after the header of the raw `.scfareplay` bytes into its commands
+pub fn parse_replay_commands(
+    raw_replay: &[u8],
+    header_length: usize,
+) -> Result<Vec<ReplayCommand>, ReplayError> {
+    Commands::new(raw_replay, header_length).collect()
+}
+
+/// Counts the commands and ticks of the body after the header, without keeping the commands.
+/// Only the command headers and the ticks of advance commands are read.
+pub fn count_replay_commands(raw_replay: &[u8], header_length: usize) -> CommandCount {
+    let mut commands = Commands::new(raw_replay, header_length);
+    let mut count = CommandCount {
+        commands: 0,
+        ticks: 0,
+        invalid_at: None,
+    };
+
+    for command in &mut commands {
+        if command.is_err() {
+            count.invalid_at = Some(commands.offset);
+            break;
+        }
+        count.commands += 1;
+    }
+    count.ticks = commands.tick;
+    count
+}
+
+/// The commands of the body, one after another. Stops after the first error.
+struct Commands<'a> {
+    raw_replay: &'a [u8],
+    offset: usize,
+    tick: u32,
+    failed: bool,
+}
+
+impl<'a> Commands<'a> {
+    fn new(raw_replay: &'a [u8], header_length: usize) -> Commands<'a> {
+        Commands {
+            raw_replay,
+            offset: header_length,
+            tick: 0,
+            failed: false,
+        }
+    }
+
+    fn parse_command(&mut self) -> Result<ReplayCommand, ReplayError> {
+        let offset = self.offset;
+        let command_header = self
+            .raw_replay
+            .get(offset..offset + 3)
+            .ok_or_else(|| invalid_body(offset, "command header is truncated"))?;
+        let command_type = command_header[0];
+        let length = u16::from_le_bytes(command_header[1..3].try_into().unwrap()) as usize;
+
+        if length < 3 {
+            return Err(invalid_body(offset, "command is shorter than its header"));
+        }
+        let data = self
+            .raw_replay
+            .get(offset + 3..offset + length)
+            .ok_or_else(|| invalid_body(offset, "command is truncated"))?;
+
+        let command = ReplayCommand {
+            command_type,
+            offset,
+            length,
+            tick: self.tick,
+        };
+
+        if command_type == ADVANCE_COMMAND {
+            let ticks = data
+                .get(..4)
+                .ok_or_else(|| invalid_body(offset, "advance command without ticks"))?;
+            self.tick = self
+                .tick
+                .saturating_add(u32::from_le_bytes(ticks.try_into().unwrap()));
+        }
+        self.offset += length;
+
+        Ok(command)
+    }
+}
+
+impl Iterator for Commands<'_> {
+    type Item = Result<ReplayCommand, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.raw_replay.len() {
+            return None;
+        }
+        let command = self.parse_command();
+        self.failed = command.is_err();
+        Some(command)
+    }
+}
+
+/// Game time of the replay from the ticks of its commands,
+/// None if it doesn't end with `END_GAME_COMMAND`, e.g. an unfinished live replay
+pub fn replay_duration(commands: &[ReplayCommand]) -> Option<Duration> {
+    let end_game = commands
+        .last()
+        .filter(|command| command.command_type == END_GAME_COMMAND)?;
+
+    Some(Duration::from_millis(
+        u64::from(end_game.tick) * TICK_MILLIS,
+    ))
+}
+
+fn invalid_body(offset: usize, reason: &str) -> ReplayError {
+    ReplayError::InvalidBody(format!("{} at byte {}", reason, offset))
+}
@@ -0,0 +1,183 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::download::Downloader;
+
+pub struct BulkOptions {
+    /// How many replays are downloaded at the same time
+    pub concurrency: usize,
+    /// Minimum time between two requests to the vault, over all concurrent downloads
+    pub delay: Duration,
+}
+
+#[derive(Default)]
+pub struct BulkReport {
+    pub downloaded: usize,
+    /// Already in the output directory from a previous run
+    pub skipped: usize,
+    pub failed: Vec<(u32, io::Error)>,
+}
+
+/// Parses a list of replay ids, one per line. Empty lines and `#` comments are ignored.
+pub fn parse_replay_ids(content: &str) -> Result<Vec<u32>, String> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.split('#').next().unwrap().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            line.parse()
+                .map_err(|_| format!("line {}: {} is no replay id", index + 1, line))
+        })
+        .collect()
+}
+
+/// Downloads all replays into `out_dir` as `<id>.fafreplay` (or `<id>.scfareplay`),
+/// replays already there are skipped
+pub fn download_replays(
+    downloader: &Downloader,
+    vault_base_url: &str,
+    replay_ids: &[u32],
+    out_dir: &Path,
+    options: &BulkOptions,
+) -> BulkReport {
+    let next_index = AtomicUsize::new(0);
+    let next_request_at = Mutex::new(Instant::now());
+    let report = Mutex::new(BulkReport::default());
+
+    thread::scope(|scope| {
+        for _ in 0..options.concurrency.max(1) {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let replay_id = match replay_ids.get(index) {
+                    Some(replay_id) => *replay_id,
+                    None => return,
+                };
+
+                if let Some(existing_path) = find_downloaded(out_dir, replay_id) {
+                    println!("{}\tskipped, {} exists", replay_id, existing_path.display());
+                    report.lock().unwrap().skipped += 1;
+                    continue;
+                }
+
+                wait_for_turn(&next_request_at, options.delay);
+                match download_replay(downloader, vault_base_url, replay_id, out_dir) {
+                    Ok(path) => {
+                        println!("{}\tdownloaded to {}", replay_id, path.display());
+                        report.lock().unwrap().downloaded += 1;
+                    }
+                    Err(e) => {
+                        println!("{}\tfailed: {}", replay_id, e);
+                        report.lock().unwrap().failed.push((replay_id, e));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut report = report.into_inner().unwrap();
+    report.failed.sort_by_key(|(replay_id, _)| *replay_id);
+    report
+}
+
+pub fn find_downloaded(out_dir: &Path, replay_id: u32) -> Option<PathBuf> {
+    [".fafreplay", ".scfareplay"]
+        .iter()
+        .map(|suffix| out_dir.join(format!("{}{}", replay_id, suffix)))
+        .find(|path| path.is_file())
+}
+
+/// The id a replay file name starts with, like the vault names them: `1234567.fafreplay`,
+/// also `1234567 - Seton's Clutch.fafreplay` or `/replays/1234567_1.scfareplay`
+pub fn parse_file_name_replay_id(replay_path: &Path) -> Option<u32> {
+    let file_name = replay_path.file_name()?.to_str()?;
+    let id_length = file_name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(file_name.len());
+
+    file_name[..id_length].parse().ok()
+}
+
+/// Blocks until `delay` has passed since the previous request of any download
+fn wait_for_turn(next_request_at: &Mutex<Instant>, delay: Duration) {
+    let wait = {
+        let mut next_request_at = next_request_at.lock().unwrap();
+        let now = Instant::now();
+        let request_at = (*next_request_at).max(now);
+        *next_request_at = request_at + delay;
+        request_at - now
+    };
+
+    thread::sleep(wait);
+}
+
+fn download_replay(
+    downloader: &Downloader,
+    vault_base_url: &str,
+    replay_id: u32,
+    out_dir: &Path,
+) -> io::Result<PathBuf> {
+    let temp_replay_file = downloader.download_vault_replay(vault_base_url, replay_id)?;
+    let suffix = temp_replay_file
+        .path()
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = out_dir.join(format!("{}.{}", replay_id, suffix));
+
+    // written under another name first, an interrupted run must not leave a replay to skip
+    let part_path = out_dir.join(format!("{}.part", replay_id));
+    fs::write(&part_path, fs::read(temp_replay_file.path())?)?;
+    fs::rename(&part_path, &path)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ids_skipping_comments_and_empty_lines() {
+        assert_eq!(
+            parse_replay_ids("# ladder games\n21934412\n\n  42 # short one\r\n"),
+            Ok(vec![21934412, 42])
+        );
+        assert_eq!(
+            parse_replay_ids("42\nfoo\n"),
+            Err("line 2: foo is no replay id".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_leading_replay_ids_of_file_names() {
+        let id_of = |path: &str| parse_file_name_replay_id(Path::new(path));
+
+        assert_eq!(id_of("21934412.fafreplay"), Some(21934412));
+        assert_eq!(
+            id_of("/replays/21934412 - Seton's Clutch.scfareplay"),
+            Some(21934412)
+        );
+        assert_eq!(id_of("replays.2/42_1.fafreplay"), Some(42));
+        assert_eq!(id_of("Seton's Clutch 2.scfareplay"), None);
+        assert_eq!(id_of("99999999999.fafreplay"), None);
+        assert_eq!(id_of("/replays/"), None);
+    }
+
+    #[test]
+    fn spaces_requests_by_the_delay() {
+        let next_request_at = Mutex::new(Instant::now());
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            wait_for_turn(&next_request_at, Duration::from_millis(50));
+        }
+
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use faf_replay_cli::{
+    decode_legacy_replay, get_replay_type, get_replay_type_from_content, ReplayType,
+};
+use tempfile::NamedTempFile;
+
+use crate::checksum::sha256_file;
+use crate::download::{get_replay_suffix, SNIFF_LENGTH};
+use crate::paths;
+
+/// Downloaded vault replays, stored as `<id>.fafreplay` (or `<id>.scfareplay`)
+pub struct ReplayCache {
+    dir: PathBuf,
+}
+
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+pub enum EntryCheck {
+    Valid,
+    /// Doesn't match its stored checksum anymore
+    Corrupt,
+    /// Cached before checksums were stored
+    Unverified,
+}
+
+impl ReplayCache {
+    pub fn new(dir: PathBuf) -> ReplayCache {
+        ReplayCache { dir }
+    }
+
+    /// `replays` in the cache directory
+    pub fn default_dir() -> Option<PathBuf> {
+        paths::replay_cache_dir()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Looks up a cached replay. Cached files failing the sanity check are removed,
+    /// so the replay gets downloaded again.
+    pub fn find(&self, replay_id: u32) -> Option<PathBuf> {
+        let path = [".fafreplay", ".scfareplay"]
+            .iter()
+            .map(|suffix| self.dir.join(format!("{}{}", replay_id, suffix)))
+            .find(|path| path.is_file())?;
+
+        if is_sane_replay(&path) {
+            Some(path)
+        } else {
+            eprintln!(
+                "Cached replay {} is corrupt, downloading it again",
+                path.display()
+            );
+            remove_entry(&path);
+            None
+        }
+    }
+
+    /// Removes the cached replay, e.g. to download it again
+    pub fn remove(&self, replay_id: u32) {
+        for suffix in &[".fafreplay", ".scfareplay"] {
+            remove_entry(&self.dir.join(format!("{}{}", replay_id, suffix)));
+        }
+    }
+
+    /// Where an unfinished download of the replay is kept until it can be resumed
+    pub fn part_path(&self, replay_id: u32) -> PathBuf {
+        self.dir.join(format!("{}.part", replay_id))
+    }
+
+    /// Turns a completely downloaded part file into a cache entry named after its format,
+    /// its sha-256 is stored next to it for `verify`.
+    pub fn store_part(&self, replay_id: u32) -> io::Result<PathBuf> {
+        let part_path = self.part_path(replay_id);
+
+        let mut head = Vec::new();
+        fs::File::open(&part_path)?
+            .take(SNIFF_LENGTH)
+            .read_to_end(&mut head)?;
+
+        let suffix = get_replay_suffix(get_replay_type_from_content(&head))?;
+        let path = self.dir.join(format!("{}{}", replay_id, suffix));
+        fs::write(get_checksum_path(&path), sha256_file(&part_path)?)?;
+        fs::rename(&part_path, &path)?;
+
+        Ok(path)
+    }
+
+    /// Keeps a temp file a detached game still reads after we exited. It is stored as
+    /// `detached-<temp file name>`, so it is listed and cleaned up like the other entries.
+    pub fn store_detached(&self, temp_file: NamedTempFile) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        let temp_file_name = temp_file.path().file_name().unwrap().to_string_lossy();
+        let mut file_name = format!("detached-{}", temp_file_name.trim_start_matches('.'));
+        // extracted replays are temp files without suffix
+        if let ReplayType::Unknown = get_replay_type(&file_name) {
+            file_name.push_str(".scfareplay");
+        }
+        let path = self.dir.join(file_name);
+
+        // the temp dir is often another filesystem, renaming fails then
+        if let Err(e) = temp_file.persist(&path) {
+            fs::copy(e.file.path(), &path)?;
+        }
+        Ok(path)
+    }
+
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            let is_replay = match get_replay_type(&path.to_string_lossy()) {
+                ReplayType::Unknown => false,
+                _ => path.is_file(),
+            };
+
+            if is_replay {
+                let metadata = fs::metadata(&path)?;
+                entries.push(CacheEntry {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    /// Removes all cached replays older than `max_age`, returns how many were removed
+    pub fn clean(&self, max_age: Duration) -> io::Result<usize> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+
+        for entry in self.entries()? {
+            let age = now.duration_since(entry.modified).unwrap_or_default();
+            if age > max_age {
+                fs::remove_file(&entry.path)?;
+                let _ = fs::remove_file(get_checksum_path(&entry.path));
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> io::Result<usize> {
+        self.clean(Duration::from_secs(0))
+    }
+
+    /// Checks all entries against their stored checksums, corrupt ones are removed
+    pub fn verify(&self) -> io::Result<Vec<(CacheEntry, EntryCheck)>> {
+        let mut checked_entries = Vec::new();
+
+        for entry in self.entries()? {
+            let check = match fs::read_to_string(get_checksum_path(&entry.path)) {
+                Ok(stored) if stored.trim() == sha256_file(&entry.path)? => EntryCheck::Valid,
+                Ok(_) => {
+                    remove_entry(&entry.path);
+                    EntryCheck::Corrupt
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => EntryCheck::Unverified,
+                Err(e) => return Err(e),
+            };
+
+            checked_entries.push((entry, check));
+        }
+
+        Ok(checked_entries)
+    }
+}
+
+/// `<id>.fafreplay.sha256` next to the `<id>.fafreplay` entry
+fn get_checksum_path(entry_path: &Path) -> PathBuf {
+    let mut checksum_path = entry_path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    PathBuf::from(checksum_path)
+}
+
+fn remove_entry(entry_path: &Path) {
+    let _ = fs::remove_file(entry_path);
+    let _ = fs::remove_file(get_checksum_path(entry_path));
+}
+
+fn is_sane_replay(path: &Path) -> bool {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+
+    match get_replay_type(&path.to_string_lossy()) {
+        ReplayType::FafLegacy => decode_legacy_replay(&content).is_ok(),
+        ReplayType::ForgedAlliance => content.starts_with(b"Supreme Commander"),
+        ReplayType::Unknown => false,
+    }
+}
+
+/// Parses ages like `30d`, `12h`, `90m`, `2w` or plain seconds, None if it overflows
+pub fn parse_age(age: &str) -> Option<Duration> {
+    let unit_start = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (amount, unit) = age.split_at(unit_start);
+    let amount: u64 = amount.parse().ok()?;
+
+    let unit_seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    amount.checked_mul(unit_seconds).map(Duration::from_secs)
+}
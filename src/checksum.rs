@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+/// Checksums a server announced for a download, as `Digest: sha-256=<base64>`,
+/// `Content-MD5: <base64>` or `X-Checksum-Sha256: <hex>` headers
+#[derive(Default)]
+pub struct ExpectedChecksums {
+    md5: Option<Vec<u8>>,
+    sha256: Option<Vec<u8>>,
+}
+
+/// A download that doesn't match its checksum, worth downloading again
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    algorithm: &'static str,
+    expected: String,
+    actual: String,
+}
+
+impl ExpectedChecksums {
+    /// `whole_body` is false for 206 responses, a `Content-MD5` only covers the sent range there
+    pub fn from_headers<'a>(
+        header: impl Fn(&str) -> Option<&'a str>,
+        whole_body: bool,
+    ) -> ExpectedChecksums {
+        let mut checksums = ExpectedChecksums {
+            md5: header("X-Checksum-Md5").and_then(decode_hex),
+            sha256: header("X-Checksum-Sha256").and_then(decode_hex),
+        };
+
+        if whole_body {
+            if let Some(content_md5) = header("Content-MD5").and_then(decode_base64) {
+                checksums.md5 = Some(content_md5);
+            }
+        }
+
+        for digest in header("Digest").unwrap_or_default().split(',') {
+            let (algorithm, value) = match digest.trim().split_once('=') {
+                Some(digest) => digest,
+                None => continue,
+            };
+            match algorithm.to_ascii_lowercase().as_str() {
+                "sha-256" => checksums.sha256 = decode_base64(value),
+                "md5" => checksums.md5 = decode_base64(value),
+                _ => {}
+            }
+        }
+
+        checksums
+    }
+
+    /// Checks the downloaded file, fails with a `ChecksumMismatch` if it doesn't match
+    pub fn verify(&self, path: &Path) -> io::Result<()> {
+        if let Some(expected) = &self.sha256 {
+            check(expected, &hash_file::<Sha256>(path)?, "sha-256")?;
+        }
+        if let Some(expected) = &self.md5 {
+            check(expected, &hash_file::<Md5>(path)?, "md5")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Download corrupt - expected {} {}, got {}",
+            self.algorithm, self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+pub fn is_checksum_mismatch(e: &io::Error) -> bool {
+    e.get_ref()
+        .is_some_and(|inner| inner.is::<ChecksumMismatch>())
+}
+
+/// The hex sha-256 of the file, as stored next to cache entries
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    Ok(encode_hex(&hash_file::<Sha256>(path)?))
+}
+
+fn check(expected: &[u8], actual: &[u8], algorithm: &'static str) -> io::Result<()> {
+    if expected == actual {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        ChecksumMismatch {
+            algorithm,
+            expected: encode_hex(expected),
+            actual: encode_hex(actual),
+        },
+    ))
+}
+
+fn hash_file<D: Digest + io::Write>(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finalize().to_vec())
+}
+
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    base64::decode_config(value.trim(), base64::STANDARD).ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,370 @@
+use std::borrow::Cow;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The command as it would be typed into a shell, e.g. to run what `--dry-run` printed by hand
+pub fn format_command(command: &Command) -> String {
+    if cfg!(windows) {
+        join_command(command, quote_windows)
+    } else {
+        join_command(command, quote_posix)
+    }
+}
+
+fn join_command(command: &Command, quote: fn(&str) -> Cow<'_, str>) -> String {
+    let program = command.get_program().to_string_lossy();
+    let args = command.get_args().map(|arg| arg.to_string_lossy());
+
+    std::iter::once(program)
+        .chain(args)
+        .map(|arg| quote(&arg).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes the argument for POSIX shells if it contains anything they would interpret
+pub fn quote_posix(arg: &str) -> Cow<'_, str> {
+    let is_plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+=:,@%".contains(c));
+
+    if is_plain {
+        Cow::Borrowed(arg)
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', "'\\''")))
+    }
+}
+
+/// Quotes the argument for cmd.exe if it contains anything it would interpret, in the way the MSVC runtime
+/// (`CommandLineToArgvW`) splits the command line again: backslashes are only escaped before a quote
+pub fn quote_windows(arg: &str) -> Cow<'_, str> {
+    let is_plain = !arg.is_empty() && !arg.chars().any(|c| " \t\n\"&|<>^()".contains(c));
+    if is_plain {
+        return Cow::Borrowed(arg);
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // the closing quote must not be escaped
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Splits a command line into arguments like the MSVC runtime does, unlike a POSIX shell it keeps the backslashes
+/// of paths like `C:\Tools\wrapper.exe`
+pub fn split_windows(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut backslashes = 0;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            backslashes += 1;
+            in_arg = true;
+            continue;
+        }
+        if c == '"' {
+            arg.extend(std::iter::repeat_n('\\', backslashes / 2));
+            if backslashes % 2 == 1 {
+                arg.push('"');
+            } else if in_quotes && chars.peek() == Some(&'"') {
+                // `""` within quotes is a quote
+                chars.next();
+                arg.push('"');
+            } else {
+                in_quotes = !in_quotes;
+            }
+            backslashes = 0;
+            in_arg = true;
+            continue;
+        }
+
+        arg.extend(std::iter::repeat_n('\\', backslashes));
+        backslashes = 0;
+        if (c == ' ' || c == '\t') && !in_quotes {
+            if in_arg {
+                args.push(std::mem::take(&mut arg));
+                in_arg = false;
+            }
+        } else {
+            arg.push(c);
+            in_arg = true;
+        }
+    }
+    arg.extend(std::iter::repeat_n('\\', backslashes));
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+/// `.bat` and `.cmd` files, std starts them through cmd.exe and quotes the arguments for it
+pub fn is_batch_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("bat") || extension.eq_ignore_ascii_case("cmd")
+    })
+}
+
+/// Why the file can't be started as a program, the OS only says "permission denied" or "not a valid Win32
+/// application" when it is launched. None if it can be started.
+#[cfg(unix)]
+pub fn launch_problem(path: &Path) -> Option<String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path).ok()?.permissions().mode();
+    if mode & 0o111 == 0 {
+        return Some(format!(
+            "is not executable, run chmod +x {}",
+            quote_posix(&path.to_string_lossy())
+        ));
+    }
+    // also false for files of other users without the bit for us, and on filesystems mounted noexec
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    if unsafe { libc::access(c_path.as_ptr(), libc::X_OK) } != 0 {
+        return Some(
+            "is not executable for this user, check its owner and permissions, and that its filesystem isn't \
+             mounted noexec"
+                .to_string(),
+        );
+    }
+    None
+}
+
+/// Windows starts programs by their extension, a script needs its interpreter as wrapper
+#[cfg(windows)]
+pub fn launch_problem(path: &Path) -> Option<String> {
+    let startable = path.extension().is_some_and(|extension| {
+        ["exe", "com", "bat", "cmd"]
+            .iter()
+            .any(|startable| extension.eq_ignore_ascii_case(startable))
+    });
+    if startable {
+        return None;
+    }
+    Some(
+        "can't be started on Windows, only .exe, .com, .bat and .cmd files can. Start a script through its \
+         interpreter, e.g. --wrapper \"powershell -File script.ps1\""
+            .to_string(),
+    )
+}
+
+/// Where the program is found in PATH, like Command looks it up. On Windows batch files are found as well,
+/// Command only finds them by their path.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let mut file_names = vec![program.to_string()];
+    if cfg!(windows) {
+        file_names.push(format!("{}.exe", program));
+        file_names.push(format!("{}.bat", program));
+        file_names.push(format!("{}.cmd", program));
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| file_names.iter().map(move |file_name| dir.join(file_name)))
+        .find(|path| path.is_file())
+}
+
+/// The path as the game sees it in wine, where `Z:` is the root of the unix filesystem
+pub fn to_windows_path(path: &Path) -> String {
+    format!("Z:{}", path.display().to_string().replace('/', "\\"))
+}
+
+/// Whether the words of a wrapper start wine, e.g. `wine`, `/opt/wine-staging/bin/wine64` or `gamemoderun wine`
+pub fn is_wine_command<'a>(mut words: impl Iterator<Item = &'a str>) -> bool {
+    words.any(|word| {
+        let program = Path::new(word)
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .unwrap_or_default();
+        program == "wine" || program == "wine64" || program.starts_with("wine-")
+    })
+}
+
+/// A warning if the wrapper doesn't fit the platform: a wrapper is rarely needed on Windows, a leftover wine path
+/// there breaks the launch. Elsewhere a `.exe` needs wine, unless `exe_runs_natively` as .exe files are registered to
+/// run with it. `wine` is where it is found in PATH, for the hint.
+pub fn wrapper_warning(
+    windows: bool,
+    executable: &Path,
+    wrapper_words: Option<&[&str]>,
+    exe_runs_natively: bool,
+    wine: Option<&Path>,
+) -> Option<String> {
+    match wrapper_words {
+        Some(words) if windows && is_wine_command(words.iter().copied()) => Some(format!(
+            "The --wrapper {} starts wine, which doesn't run on Windows, the game starts without a wrapper there",
+            words.join(" ")
+        )),
+        Some(words) if windows => Some(format!(
+            "A --wrapper is unusual on Windows, the game starts without one. Check that {} is meant to start it",
+            words.join(" ")
+        )),
+        None if !windows && !exe_runs_natively && is_windows_executable(executable) => {
+            let hint = match wine {
+                Some(wine) => format!("--wrapper {}", wine.display()),
+                None => "installing wine and using --wrapper wine".to_string(),
+            };
+            Some(format!(
+                "{} is a Windows executable and .exe files aren't registered to run with wine (binfmt_misc), \
+                 the launch will most likely fail without a wrapper, try {}",
+                executable.display(),
+                hint
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn is_windows_executable(executable: &Path) -> bool {
+    executable
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+}
+
+/// Whether .exe files run with wine when started directly, through an enabled binfmt_misc entry
+#[cfg(target_os = "linux")]
+pub fn wine_binfmt_registered() -> bool {
+    let entries = match std::fs::read_dir("/proc/sys/fs/binfmt_misc") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.filter_map(|entry| entry.ok()).any(|entry| {
+        let content = std::fs::read_to_string(entry.path()).unwrap_or_default();
+        content.lines().next() == Some("enabled")
+            && content.lines().any(|line| {
+                line.strip_prefix("interpreter ")
+                    .is_some_and(|interpreter| is_wine_command(std::iter::once(interpreter)))
+            })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wine_binfmt_registered() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_arguments_needing_it() {
+        let mut command = Command::new("/games/FA/bin/ForgedAlliance.exe");
+        command.args(["/replay", "/tmp/Alice's replay.scfareplay", "/log", ""]);
+
+        assert_eq!(
+            join_command(&command, quote_posix),
+            "/games/FA/bin/ForgedAlliance.exe /replay '/tmp/Alice'\\''s replay.scfareplay' /log ''"
+        );
+    }
+
+    #[test]
+    fn quotes_and_splits_windows_command_lines_alike() {
+        let args = [
+            "C:\\Program Files (x86)\\THQ\\Gas Powered Games\\bin\\ForgedAlliance.exe",
+            "C:\\Users\\Zoë\\Documents\\My Games\\12345.scfareplay",
+            "plain",
+            "",
+            "say \"hi\"",
+            "C:\\trailing backslash\\",
+            "\\\\server\\share\\\"odd\"",
+        ];
+        let mut command = Command::new(args[0]);
+        command.args(&args[1..]);
+        let line = join_command(&command, quote_windows);
+
+        assert!(line.starts_with("\"C:\\Program Files (x86)\\THQ\\"));
+        assert!(line.contains(" plain \"\" "));
+        assert_eq!(split_windows(&line), args);
+        assert_eq!(
+            split_windows("C:\\Tools\\wrap.exe \"a \"\"b\"\"\"  --flag"),
+            ["C:\\Tools\\wrap.exe", "a \"b\"", "--flag"]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tells_how_to_make_scripts_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fa wrapper.sh");
+        std::fs::write(&script, "#!/bin/sh\nexec \"$@\"\n").unwrap();
+
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(
+            launch_problem(&script).unwrap(),
+            format!("is not executable, run chmod +x '{}'", script.display())
+        );
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(launch_problem(&script), None);
+    }
+
+    #[test]
+    fn translates_unix_paths_for_wine() {
+        assert_eq!(
+            to_windows_path(Path::new("/tmp/.tmpAb12/1234.scfareplay")),
+            "Z:\\tmp\\.tmpAb12\\1234.scfareplay"
+        );
+        assert!(is_wine_command(
+            ["gamemoderun", "/opt/wine-staging/bin/wine64"]
+                .iter()
+                .copied()
+        ));
+        assert!(!is_wine_command(
+            ["gamemoderun", "mangohud"].iter().copied()
+        ));
+    }
+
+    #[test]
+    fn warns_about_wrappers_not_fitting_the_platform() {
+        let executable = Path::new("/games/FA/bin/ForgedAlliance.exe");
+        let wine = Some(Path::new("/usr/bin/wine"));
+
+        let warning = wrapper_warning(true, executable, Some(&["/usr/bin/wine"]), false, None);
+        assert!(warning.unwrap().contains("doesn't run on Windows"));
+        let warning = wrapper_warning(true, executable, Some(&["gamemoderun"]), false, None);
+        assert!(warning.unwrap().contains("unusual on Windows"));
+        let warning = wrapper_warning(false, executable, None, false, wine);
+        assert!(warning.unwrap().ends_with("try --wrapper /usr/bin/wine"));
+
+        assert_eq!(wrapper_warning(true, executable, None, false, None), None);
+        assert_eq!(wrapper_warning(false, executable, None, true, wine), None);
+        assert_eq!(
+            wrapper_warning(false, executable, Some(&["wine"]), false, wine),
+            None
+        );
+        assert_eq!(
+            wrapper_warning(
+                false,
+                Path::new("/usr/local/bin/fa-launcher"),
+                None,
+                false,
+                wine
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::paths;
+
+/// Settings from the config file, options given on the command line take precedence
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Like `--executable`, stored when the game was found without it
+    pub executable: Option<PathBuf>,
+    /// Forbids all network access, like `--offline`
+    #[serde(default)]
+    pub offline: bool,
+    /// Like `--proxy`
+    pub proxy: Option<String>,
+    /// Init files of featured mods, e.g. `nomads = "init_nomads.lua"`, over the built-in ones
+    #[serde(default)]
+    pub init_files: BTreeMap<String, String>,
+    /// Environment variables of the game, e.g. `DRI_PRIME = "1"`, `--env` takes precedence
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// How many game logs are kept in the log directory
+    pub keep_logs: Option<usize>,
+    /// `[[executables]]`, the first one matching a replay launches it
+    #[serde(default)]
+    pub executables: Vec<ExecutableProfile>,
+    /// `[launch]`
+    #[serde(default)]
+    pub launch: LaunchConfig,
+}
+
+/// How the game is launched
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchConfig {
+    /// Like `--windowed`, the window size, e.g. `1280x720`
+    pub windowed: Option<String>,
+    /// Log lines of transient startup failures `--launch-retries` retries, in addition to the built-in ones
+    #[serde(default)]
+    pub retry_patterns: Vec<String>,
+}
+
+/// A game installation used for the replays matching its rules, e.g. an old one for replays of old game versions
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutableProfile {
+    /// For `--profile`
+    pub name: String,
+    pub path: PathBuf,
+    /// Like `--init`
+    pub init: Option<String>,
+    /// Matches replays of this featured mod
+    pub featured_mod: Option<String>,
+    /// Matches replays whose game version contains this, e.g. `3599` for `Supreme Commander v1.50.3599`
+    pub game_version: Option<String>,
+}
+
+impl ExecutableProfile {
+    /// Without rules it is the default for replays no other profile matches
+    pub fn is_default(&self) -> bool {
+        self.featured_mod.is_none() && self.game_version.is_none()
+    }
+
+    /// All rules match, unknown values of raw or live replays never do
+    pub fn matches(&self, featured_mod: Option<&str>, game_version: Option<&str>) -> bool {
+        let mod_matches = self
+            .featured_mod
+            .as_deref()
+            .is_none_or(|rule| Some(rule) == featured_mod);
+        let version_matches = self.game_version.as_deref().is_none_or(|rule| {
+            game_version.is_some_and(|game_version| game_version.contains(rule))
+        });
+
+        !self.is_default() && mod_matches && version_matches
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        paths::config_file()
+    }
+
+    /// Reads the config file at `path`, a missing file only counts as error if `required`
+    pub fn load(path: &Path, required: bool) -> io::Result<Config> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("Could not read config file {}: {}", path.display(), e),
+                ))
+            }
+        };
+
+        toml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid config file {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Sets `executable` in the config file at `path`, keeping everything else of it
+    pub fn store_executable(path: &Path, executable: &Path) -> io::Result<()> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, with_executable(&content, executable))
+    }
+}
+
+/// `1280x720` as width and height, both above 0
+pub fn parse_resolution(resolution: &str) -> Option<(u32, u32)> {
+    let (width, height) = resolution.split_once(['x', 'X'])?;
+    let width = width.parse().ok().filter(|&width| width > 0)?;
+    let height = height.parse().ok().filter(|&height| height > 0)?;
+
+    Some((width, height))
+}
+
+/// `content` with its `executable` replaced, top level keys have to come before all tables
+fn with_executable(content: &str, executable: &Path) -> String {
+    let executable = toml::Value::String(executable.display().to_string());
+    let mut result = format!("executable = {}\n", executable);
+    let mut in_table = false;
+
+    for line in content.lines() {
+        in_table |= line.trim_start().starts_with('[');
+        let is_executable = line
+            .split('=')
+            .next()
+            .is_some_and(|key| key.trim() == "executable");
+        if in_table || !is_executable {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_options_keep_their_defaults() {
+        let config: Config = toml::from_str("offline = true").unwrap();
+
+        assert!(config.offline);
+        assert_eq!(config.proxy, None);
+        assert!(config.init_files.is_empty());
+        assert!(config.env.is_empty());
+        assert!(config.executables.is_empty());
+    }
+
+    #[test]
+    fn profiles_match_by_all_their_rules() {
+        let config: Config = toml::from_str(
+            r#"
+            [[executables]]
+            name = "develop"
+            path = "/games/develop/bin/ForgedAlliance.exe"
+            featured_mod = "fafdevelop"
+            game_version = "3701"
+
+            [[executables]]
+            name = "stable"
+            path = "/games/faf/bin/ForgedAlliance.exe"
+            "#,
+        )
+        .unwrap();
+        let develop = &config.executables[0];
+
+        assert!(develop.matches(Some("fafdevelop"), Some("Supreme Commander v1.50.3701")));
+        assert!(!develop.matches(Some("fafdevelop"), Some("Supreme Commander v1.50.3599")));
+        assert!(!develop.matches(None, Some("Supreme Commander v1.50.3701")));
+        assert!(config.executables[1].is_default());
+        assert!(!config.executables[1].matches(Some("faf"), None));
+    }
+
+    #[test]
+    fn parses_resolutions() {
+        let config: Config = toml::from_str("[launch]\nwindowed = \"1280x720\"").unwrap();
+
+        assert_eq!(
+            config.launch.windowed.as_deref().and_then(parse_resolution),
+            Some((1280, 720))
+        );
+        assert_eq!(parse_resolution("1920X1080"), Some((1920, 1080)));
+        assert_eq!(parse_resolution("1280"), None);
+        assert_eq!(parse_resolution("0x720"), None);
+        assert_eq!(parse_resolution("1280x-720"), None);
+    }
+
+    #[test]
+    fn replaces_the_stored_executable() {
+        let content = "executable = '/old/ForgedAlliance.exe'\noffline = true\n\n[env]\nexecutable = 'kept'\n";
+        let content = with_executable(content, Path::new("C:\\FAF\\bin\\ForgedAlliance.exe"));
+        let config: Config = toml::from_str(&content).unwrap();
+
+        assert_eq!(
+            config.executable,
+            Some(PathBuf::from("C:\\FAF\\bin\\ForgedAlliance.exe"))
+        );
+        assert!(config.offline);
+        assert_eq!(config.env["executable"], "kept");
+    }
+}
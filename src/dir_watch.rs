@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use faf_replay_cli::{get_replay_type, ReplayType};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// A file has to be left alone that long before it is considered completely written
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches a directory for new replay files
+pub struct ReplayDirWatcher {
+    // dropping the watcher stops the events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Replay files that changed recently and when they last did
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl ReplayDirWatcher {
+    pub fn new(dir: &Path) -> notify::Result<ReplayDirWatcher> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(ReplayDirWatcher {
+            _watcher: watcher,
+            events,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Blocks until new replay files stopped changing and returns the most recent one,
+    /// `None` once watching isn't possible anymore
+    pub fn next_replay(&mut self) -> Option<PathBuf> {
+        loop {
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) => self.add_event(event),
+                Ok(Err(e)) => eprintln!("Watching replays failed: {}", e),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+
+            let now = Instant::now();
+            let settled = self
+                .pending
+                .values()
+                .all(|changed| now.duration_since(*changed) >= SETTLE_TIME);
+
+            if settled && !self.pending.is_empty() {
+                // only the latest is worth watching if several replays came in at once
+                let latest = self
+                    .pending
+                    .drain()
+                    .filter(|(path, _)| path.is_file())
+                    .max_by_key(|(_, changed)| *changed);
+
+                if let Some((latest, _)) = latest {
+                    return Some(latest);
+                }
+            }
+        }
+    }
+
+    fn add_event(&mut self, event: Event) {
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+
+        for path in event.paths {
+            if let ReplayType::Unknown = get_replay_type(&path.to_string_lossy()) {
+                continue;
+            }
+
+            self.pending.insert(path, Instant::now());
+        }
+    }
+}
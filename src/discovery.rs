@@ -0,0 +1,127 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::steam;
+
+/// A `bin` folder the game may be installed in, and where it is known from
+pub struct Candidate {
+    pub source: String,
+    pub bin_dir: PathBuf,
+}
+
+/// The `bin` folders the game is looked for in without `--executable`, in this order:
+/// the FAF client's copy in its data directory, the game path set in the FAF client,
+/// the installations in the Windows registry, then the Steam libraries
+pub fn candidates() -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut add = |source: &str, bin_dir: PathBuf| {
+        if !candidates
+            .iter()
+            .any(|candidate: &Candidate| candidate.bin_dir == bin_dir)
+        {
+            candidates.push(Candidate {
+                source: source.to_string(),
+                bin_dir,
+            })
+        }
+    };
+
+    if let Some(program_data) = env::var_os("PROGRAMDATA") {
+        add(
+            "FAF client data",
+            Path::new(&program_data).join("FAForever").join("bin"),
+        );
+    }
+    if let Some(home) = env::var_os("HOME") {
+        add(
+            "FAF client data",
+            Path::new(&home).join(".faforever").join("bin"),
+        );
+    }
+
+    for prefs_path in client_prefs_paths() {
+        let game_path = fs::read_to_string(&prefs_path)
+            .ok()
+            .and_then(|prefs| parse_game_path(&prefs));
+        if let Some(game_path) = game_path {
+            add(
+                &format!("FAF client settings ({})", prefs_path.display()),
+                game_path.join("bin"),
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    for (key, game_path) in crate::registry::find_game_paths() {
+        add(&format!("Registry ({})", key), game_path.join("bin"));
+    }
+
+    let libraries = steam::find_library_folders(&steam::default_steam_dirs());
+    for bin_dir in steam::bin_dirs(&libraries) {
+        add("Steam library", bin_dir);
+    }
+
+    candidates
+}
+
+/// The executable in the first of the candidates having one
+pub fn find_executable(candidates: &[Candidate]) -> Option<PathBuf> {
+    candidates
+        .iter()
+        .find_map(|candidate| steam::find_executable_in(&candidate.bin_dir))
+}
+
+/// `client.prefs` of the FAF client, in `%APPDATA%` on Windows and `~/.faforever` elsewhere
+fn client_prefs_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Some(app_data) = env::var_os("APPDATA") {
+        paths.push(
+            Path::new(&app_data)
+                .join("Forged Alliance Forever")
+                .join("client.prefs"),
+        );
+    }
+    if let Some(home) = env::var_os("HOME") {
+        paths.push(Path::new(&home).join(".faforever").join("client.prefs"));
+    }
+    paths
+}
+
+/// The installation directory of the game in the JSON of `client.prefs`,
+/// `forgedAlliance.installationPath` or `forgedAlliance.path` of older clients
+fn parse_game_path(prefs: &str) -> Option<PathBuf> {
+    let prefs: serde_json::Value = serde_json::from_str(prefs).ok()?;
+    let forged_alliance = prefs.get("forgedAlliance")?;
+
+    ["installationPath", "path"]
+        .iter()
+        .find_map(|key| forged_alliance.get(key)?.as_str())
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_game_path_of_client_prefs() {
+        let prefs = r#"{
+            "forgedAlliance": {
+                "installationPath": "C:\\Games\\Supreme Commander Forged Alliance",
+                "preferencesFile": "C:\\Users\\alice\\AppData\\Local\\Gas Powered Games\\Supreme Commander Forged Alliance\\Game.prefs"
+            }
+        }"#;
+
+        assert_eq!(
+            parse_game_path(prefs),
+            Some(PathBuf::from(
+                "C:\\Games\\Supreme Commander Forged Alliance"
+            ))
+        );
+        assert_eq!(parse_game_path(r#"{"forgedAlliance": {"path": ""}}"#), None);
+        assert_eq!(parse_game_path("not json"), None);
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::pe::{self, ExeKind};
+
+/// One line of the `doctor` checklist
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+pub enum Outcome {
+    Pass(String),
+    /// Launching may still work, e.g. local replays without a reachable server
+    Warn(String),
+    /// Launching won't work
+    Fail(String),
+}
+
+impl Check {
+    pub fn new(name: &'static str, outcome: Outcome) -> Check {
+        Check { name, outcome }
+    }
+}
+
+/// Prints the checklist, returns how many checks failed
+pub fn print_checklist(checks: &[Check]) -> usize {
+    let mut failed = 0;
+
+    for check in checks {
+        let (label, detail) = match &check.outcome {
+            Outcome::Pass(detail) => ("[ok]  ", detail),
+            Outcome::Warn(detail) => ("[warn]", detail),
+            Outcome::Fail(detail) => {
+                failed += 1;
+                ("[FAIL]", detail)
+            }
+        };
+        println!("{} {}: {}", label, check.name, detail);
+    }
+    failed
+}
+
+/// The executable has to exist, be the game, and on unix be executable unless a wrapper starts it
+pub fn check_executable(executable: &Path, has_wrapper: bool) -> Outcome {
+    let metadata = match fs::metadata(executable) {
+        Ok(metadata) => metadata,
+        Err(e) => return Outcome::Fail(format!("{} ({})", executable.display(), e)),
+    };
+    if !metadata.is_file() {
+        return Outcome::Fail(format!("{} is not a file", executable.display()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if !has_wrapper && metadata.permissions().mode() & 0o111 == 0 {
+            return Outcome::Fail(format!(
+                "{} is not executable, start it with --wrapper wine or chmod +x it",
+                executable.display()
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = has_wrapper;
+
+    match pe::inspect(executable) {
+        Ok(ExeKind::ForgedAlliance) => Outcome::Pass(executable.display().to_string()),
+        Ok(ExeKind::OtherExecutable) => Outcome::Warn(format!(
+            "{} doesn't look like ForgedAlliance.exe",
+            executable.display()
+        )),
+        Ok(ExeKind::Shortcut) => {
+            Outcome::Fail(format!("{} is a Windows shortcut", executable.display()))
+        }
+        Ok(ExeKind::NotExecutable) => {
+            Outcome::Fail(format!("{} is no Windows executable", executable.display()))
+        }
+        Err(e) => Outcome::Warn(format!(
+            "{} could not be checked ({})",
+            executable.display(),
+            e
+        )),
+    }
+}
+
+/// Forged Alliance is a Windows game, elsewhere it needs wine or another wrapper.
+/// `wrapper` is the resolved program of `--wrapper`, `wine` where wine is found in PATH.
+pub fn check_wrapper(
+    given_wrapper: Option<&str>,
+    wrapper: Option<&Path>,
+    wine: Option<&Path>,
+) -> Outcome {
+    match (given_wrapper, wrapper, wine) {
+        (Some(_), Some(wrapper), _) => Outcome::Pass(wrapper.display().to_string()),
+        (Some(given_wrapper), None, _) => {
+            Outcome::Fail(format!("{} not found", given_wrapper))
+        }
+        (None, _, _) if cfg!(windows) => Outcome::Pass("not needed on Windows".to_string()),
+        (None, _, Some(wine)) => Outcome::Warn(format!(
+            "none given, the game only starts if .exe files are registered to run with wine (binfmt_misc), \
+             otherwise use --wrapper {}",
+            wine.display()
+        )),
+        (None, _, None) => Outcome::Fail(
+            "none given and wine isn't found in PATH, install wine or use --wrapper".to_string(),
+        ),
+    }
+}
+
+/// Extracted replays are written to the temp dir
+pub fn check_temp_dir(temp_dir: Option<&Path>) -> Outcome {
+    let temp_file = match temp_dir {
+        Some(temp_dir) => tempfile::NamedTempFile::new_in(temp_dir),
+        None => tempfile::NamedTempFile::new(),
+    };
+    let temp_dir_name = temp_dir.map_or_else(
+        || env::temp_dir().display().to_string(),
+        |temp_dir| temp_dir.display().to_string(),
+    );
+
+    match temp_file {
+        Ok(_) => Outcome::Pass(format!("{} is writable", temp_dir_name)),
+        Err(e) => Outcome::Fail(format!("{} is not writable ({})", temp_dir_name, e)),
+    }
+}
@@ -0,0 +1,933 @@
+use std::cell::Cell;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use faf_replay_cli::{get_replay_type, get_replay_type_from_content, ReplayType};
+use tempfile::NamedTempFile;
+
+use crate::checksum::{is_checksum_mismatch, ExpectedChecksums};
+use crate::progress::{ByteProgress, DownloadEvents, Progress, ProgressRead};
+use crate::proxy::ProxyConfig;
+
+pub const DEFAULT_VAULT_BASE_URL: &str = "https://replay.faforever.com";
+
+const MAX_REDIRECTS: u32 = 5;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Wait before the first retry, doubled for every further one
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// The doubling stops here, so many `--retries` don't wait for hours, the jitter comes on top
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Team game replays rarely exceed 60 MB, anything beyond this is not a replay
+const MAX_DOWNLOAD_SIZE: u64 = 256 * 1024 * 1024;
+/// Number of leading bytes used to sniff the replay format
+pub const SNIFF_LENGTH: u64 = 64;
+
+pub const OFFLINE_MESSAGE: &str = "network access disabled by --offline";
+
+pub fn is_url(replay_source: &str) -> bool {
+    replay_source.starts_with("http://") || replay_source.starts_with("https://")
+}
+
+pub struct DownloadOptions {
+    pub show_progress: bool,
+    /// Reports the progress as `downloading` events of `--progress-format json` instead
+    pub progress_events: bool,
+    /// Logs the cause of every retry
+    pub verbose: bool,
+    /// How often a request failing with a connection error, timeout or 5xx is retried
+    pub retries: u32,
+    pub proxy: ProxyConfig,
+    /// Fails every request instead of sending it
+    pub offline: bool,
+    /// Where downloads are kept until used, the system temp dir if None
+    pub temp_dir: Option<PathBuf>,
+}
+
+pub struct Downloader {
+    direct_agent: ureq::Agent,
+    /// One agent per proxy, `options.proxy` decides which one a url goes through
+    proxied_agents: Vec<(String, ureq::Agent)>,
+    options: DownloadOptions,
+}
+
+/// What is known about the download a `.part` file belongs to, stored next to it
+struct PartInfo {
+    total_size: Option<u64>,
+    etag: Option<String>,
+}
+
+impl Downloader {
+    pub fn new(options: DownloadOptions) -> io::Result<Downloader> {
+        let mut proxied_agents = Vec::new();
+        for proxy in options.proxy.proxies() {
+            let ureq_proxy = ureq::Proxy::new(proxy).map_err(|e| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid proxy url {}: {}", proxy, e),
+                )
+            })?;
+            proxied_agents.push((proxy.to_string(), agent_builder().proxy(ureq_proxy).build()));
+        }
+
+        Ok(Downloader {
+            direct_agent: agent_builder().build(),
+            proxied_agents,
+            options,
+        })
+    }
+
+    fn agent_for(&self, url: &str) -> &ureq::Agent {
+        self.options
+            .proxy
+            .for_url(url)
+            .and_then(|proxy| {
+                self.proxied_agents
+                    .iter()
+                    .find(|(proxied, _)| proxied == proxy)
+            })
+            .map_or(&self.direct_agent, |(_, agent)| agent)
+    }
+
+    /// Fetches the replay with the given id from the FAF replay vault.
+    ///
+    /// The vault resolves `<base url>/<id>` the same way the FAF client does, so the body is
+    /// whatever format the replay was stored in (usually the legacy `.fafreplay` format).
+    pub fn download_vault_replay(
+        &self,
+        vault_base_url: &str,
+        replay_id: u32,
+    ) -> io::Result<NamedTempFile> {
+        let url = vault_replay_url(vault_base_url, replay_id);
+
+        self.retry_interrupted(&url, |attempt| {
+            let response = self.get(&url, &[], attempt, |e| vault_error(e, &url, replay_id))?;
+            self.store_response(response)
+        })
+    }
+
+    /// Like `download_vault_replay`, but downloads into `part_path` and continues a previous
+    /// interrupted download there unless `resume` is false. The part file is left behind
+    /// on failure, so the next attempt only needs to fetch the missing bytes.
+    pub fn download_vault_replay_resumable(
+        &self,
+        vault_base_url: &str,
+        replay_id: u32,
+        part_path: &Path,
+        resume: bool,
+    ) -> io::Result<()> {
+        let url = vault_replay_url(vault_base_url, replay_id);
+
+        let mut resume = resume;
+
+        self.retry_interrupted(&url, |attempt| {
+            let result = self.download_resumable(&url, part_path, resume, attempt, |e| {
+                vault_error(e, &url, replay_id)
+            });
+            // a retry continues with what this attempt got
+            resume = true;
+            result
+        })
+    }
+
+    /// Fetches a replay from an arbitrary http(s) url, e.g. a link to a Discord attachment.
+    pub fn download_replay_url(&self, url: &str) -> io::Result<NamedTempFile> {
+        self.retry_interrupted(url, |attempt| {
+            let response = self.get(url, &[], attempt, |e| download_error(e, url))?;
+            self.store_response(response)
+        })
+    }
+
+    /// Fetches any file, e.g. a map from the content server, into a temp file ending in `suffix`.
+    pub fn download_file(&self, url: &str, suffix: &str) -> io::Result<NamedTempFile> {
+        self.retry_interrupted(url, |attempt| {
+            let response = self.get(url, &[], attempt, |e| download_error(e, url))?;
+            let content_length = get_content_length(&response);
+
+            if content_length.is_some_and(|length| length > MAX_DOWNLOAD_SIZE) {
+                return Err(download_too_large());
+            }
+            let checksums = ExpectedChecksums::from_headers(|name| response.header(name), true);
+
+            let progress = self.progress(content_length);
+            let mut reader = ProgressRead::new(response.into_reader(), &*progress);
+            let mut temp_file = self.temp_file(suffix)?;
+            copy_body(&mut reader, temp_file.as_file_mut(), 0)?;
+            progress.finish();
+
+            checksums.verify(temp_file.path())?;
+            Ok(temp_file)
+        })
+    }
+
+    /// Fetches and parses a json document, e.g. from the FAF API.
+    /// `bearer_token` is sent as `Authorization` if given.
+    pub fn fetch_json(
+        &self,
+        url: &str,
+        bearer_token: Option<&str>,
+    ) -> io::Result<serde_json::Value> {
+        let authorization = bearer_token.map(|token| format!("Bearer {}", token));
+        let mut headers = vec![("Accept", "application/json")];
+        if let Some(authorization) = &authorization {
+            headers.push(("Authorization", authorization));
+        }
+
+        self.retry_interrupted(url, |attempt| {
+            let response = self.get(url, &headers, attempt, |e| match e {
+                ureq::Error::Status(401, _) if bearer_token.is_some() => io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!(
+                        "Request to {} was rejected with HTTP status 401, the API token is invalid or expired",
+                        url
+                    ),
+                ),
+                ureq::Error::Status(code, _) => io::Error::other(format!(
+                    "Request to {} failed with HTTP status {}",
+                    url, code
+                )),
+                ureq::Error::Transport(transport) => {
+                    io::Error::other(format!("Could not reach {}: {}", url, transport))
+                }
+            })?;
+            serde_json::from_reader(response.into_reader()).map_err(|e| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid json from {}: {}", url, e),
+                )
+            })
+        })
+    }
+
+    /// Posts a form and parses the json answer. Error statuses are returned as json as well,
+    /// OAuth servers explain them in the body. Not retried, e.g. a refresh token is only valid once.
+    pub fn post_form(&self, url: &str, form: &[(&str, &str)]) -> io::Result<serde_json::Value> {
+        if self.options.offline {
+            return Err(offline_error());
+        }
+
+        let response = match self
+            .agent_for(url)
+            .post(url)
+            .set("Accept", "application/json")
+            .send_form(form)
+        {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(transport)) => {
+                return Err(io::Error::other(format!(
+                    "Could not reach {}: {}",
+                    url, transport
+                )))
+            }
+        };
+
+        serde_json::from_reader(response.into_reader()).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid json from {}: {}", url, e),
+            )
+        })
+    }
+
+    /// Whether the server answers at all, any HTTP status counts. Returns the status.
+    pub fn check_reachable(&self, url: &str) -> io::Result<u16> {
+        if self.options.offline {
+            return Err(offline_error());
+        }
+
+        match self.agent_for(url).head(url).call() {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(response.status()),
+            Err(ureq::Error::Transport(transport)) => Err(io::Error::other(format!(
+                "Could not reach {}: {}",
+                url, transport
+            ))),
+        }
+    }
+
+    /// Retries a whole download whose body transfer broke off, e.g. by a connection reset,
+    /// or that didn't match its checksum. Failing requests are already retried by `get`, both count
+    /// the same `attempt` against `options.retries`.
+    fn retry_interrupted<T>(
+        &self,
+        url: &str,
+        mut download: impl FnMut(&Cell<u32>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let attempt = Cell::new(1);
+
+        loop {
+            match download(&attempt) {
+                Err(e)
+                    if attempt.get() <= self.options.retries
+                        && (is_interrupted(&e) || is_checksum_mismatch(&e)) =>
+                {
+                    self.wait_for_retry(attempt.get(), url, &e);
+                    attempt.set(attempt.get() + 1);
+                }
+                Err(e) if attempt.get() > 1 => return Err(gave_up(e, attempt.get())),
+                result => return result,
+            }
+        }
+    }
+
+    fn wait_for_retry(&self, attempt: u32, url: &str, cause: &dyn std::fmt::Display) {
+        let backoff = get_backoff(attempt);
+
+        eprintln!(
+            "Attempt {} to fetch {} failed, retrying in {:.1}s",
+            attempt,
+            url,
+            backoff.as_secs_f32()
+        );
+        if self.options.verbose {
+            eprintln!("debug: attempt {} failed with: {}", attempt, cause);
+        }
+
+        thread::sleep(backoff);
+    }
+
+    /// Retries transient failures, `attempt` is that of `retry_interrupted` wrapping the download
+    fn get(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        attempt: &Cell<u32>,
+        map_error: impl FnOnce(ureq::Error) -> io::Error,
+    ) -> io::Result<ureq::Response> {
+        if self.options.offline {
+            return Err(offline_error());
+        }
+
+        let response = loop {
+            let request = headers
+                .iter()
+                .fold(self.agent_for(url).get(url), |request, (name, value)| {
+                    request.set(name, value)
+                });
+
+            match request.call() {
+                Ok(response) => break response,
+                Err(e) if attempt.get() <= self.options.retries && is_transient(&e) => {
+                    self.wait_for_retry(attempt.get(), url, &e);
+                    attempt.set(attempt.get() + 1);
+                }
+                Err(e) => return Err(map_error(e)),
+            }
+        };
+
+        // ureq only reports 4xx and 5xx as errors, unfollowed redirects end up here
+        match response.status() {
+            200..=299 => Ok(response),
+            code => Err(io::Error::other(format!(
+                "Download of {} failed with HTTP status {}",
+                url, code
+            ))),
+        }
+    }
+
+    /// Streams the response body into a temp file whose extension matches the replay format,
+    /// so `prepare_replay_file` can handle it like any local file.
+    fn store_response(&self, response: ureq::Response) -> io::Result<NamedTempFile> {
+        let content_length = get_content_length(&response);
+        let file_name = response
+            .header("Content-Disposition")
+            .and_then(get_content_disposition_file_name);
+
+        if content_length.is_some_and(|length| length > MAX_DOWNLOAD_SIZE) {
+            return Err(download_too_large());
+        }
+        let checksums = ExpectedChecksums::from_headers(|name| response.header(name), true);
+
+        let progress = self.progress(content_length);
+        let mut reader = ProgressRead::new(response.into_reader(), &*progress);
+
+        let mut head = Vec::new();
+        (&mut reader).take(SNIFF_LENGTH).read_to_end(&mut head)?;
+
+        let replay_type = match file_name.as_deref().map(get_replay_type) {
+            None | Some(ReplayType::Unknown) => get_replay_type_from_content(&head),
+            Some(replay_type) => replay_type,
+        };
+
+        let mut temp_replay_file = self.temp_file(get_replay_suffix(replay_type)?)?;
+        let file = temp_replay_file.as_file_mut();
+        file.write_all(&head)?;
+
+        copy_body(&mut reader, file, head.len() as u64)?;
+        progress.finish();
+
+        // a mismatching temp file is deleted when dropped
+        checksums.verify(temp_replay_file.path())?;
+
+        Ok(temp_replay_file)
+    }
+
+    fn download_resumable(
+        &self,
+        url: &str,
+        part_path: &Path,
+        resume: bool,
+        attempt: &Cell<u32>,
+        map_error: impl Fn(ureq::Error) -> io::Error,
+    ) -> io::Result<()> {
+        let info_path = get_part_info_path(part_path);
+        let downloaded = match fs::metadata(part_path) {
+            Ok(metadata) if resume => metadata.len(),
+            _ => 0,
+        };
+        let part_info = match PartInfo::load(&info_path) {
+            Some(part_info) if downloaded > 0 => part_info,
+            // without knowing what the part belongs to it can't be continued
+            _ => return self.download_from_scratch(url, part_path, attempt, &map_error),
+        };
+
+        if part_info.total_size == Some(downloaded) {
+            // interrupted right after the last byte was written
+            return fs::remove_file(&info_path);
+        }
+
+        let range = format!("bytes={}-", downloaded);
+        let mut headers = vec![("Range", range.as_str())];
+        if let Some(etag) = &part_info.etag {
+            // the server ignores the range and sends everything if the replay changed
+            headers.push(("If-Range", etag));
+        }
+
+        let response = self.get(url, &headers, attempt, &map_error)?;
+        if response.status() != 206 {
+            // the server sends the whole replay instead
+            return self.store_part(response, part_path, &info_path, 0);
+        }
+
+        let continues_part = get_content_range_start_and_total(&response)
+            == Some((downloaded, part_info.total_size))
+            && response.header("ETag") == part_info.etag.as_deref();
+
+        if continues_part {
+            self.store_part(response, part_path, &info_path, downloaded)
+        } else {
+            self.download_from_scratch(url, part_path, attempt, &map_error)
+        }
+    }
+
+    fn download_from_scratch(
+        &self,
+        url: &str,
+        part_path: &Path,
+        attempt: &Cell<u32>,
+        map_error: impl Fn(ureq::Error) -> io::Error,
+    ) -> io::Result<()> {
+        let response = self.get(url, &[], attempt, map_error)?;
+
+        self.store_part(response, part_path, &get_part_info_path(part_path), 0)
+    }
+
+    /// Writes the response body into the part file starting at byte `offset`
+    fn store_part(
+        &self,
+        response: ureq::Response,
+        part_path: &Path,
+        info_path: &Path,
+        offset: u64,
+    ) -> io::Result<()> {
+        let total_size = if offset == 0 {
+            let total_size = get_content_length(&response);
+            let part_info = PartInfo {
+                total_size,
+                etag: response.header("ETag").map(str::to_string),
+            };
+            part_info.save(info_path)?;
+            total_size
+        } else {
+            PartInfo::load(info_path).and_then(|part_info| part_info.total_size)
+        };
+
+        if total_size.is_some_and(|length| length > MAX_DOWNLOAD_SIZE) {
+            return Err(download_too_large());
+        }
+        let checksums = ExpectedChecksums::from_headers(|name| response.header(name), offset == 0);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(part_path)?;
+        file.set_len(offset)?;
+
+        let progress = self.progress(total_size);
+        progress.set_position(offset);
+        let mut reader = ProgressRead::new(response.into_reader(), &*progress);
+
+        copy_body(&mut reader, &mut file, offset)?;
+        progress.finish();
+
+        let _ = fs::remove_file(info_path);
+        if let Err(e) = checksums.verify(part_path) {
+            // nothing of it can be trusted, the retry starts from scratch
+            let _ = fs::remove_file(part_path);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn temp_file(&self, suffix: &str) -> io::Result<NamedTempFile> {
+        let mut builder = tempfile::Builder::new();
+        builder.suffix(suffix);
+
+        match &self.options.temp_dir {
+            Some(temp_dir) => builder.tempfile_in(temp_dir),
+            None => builder.tempfile(),
+        }
+    }
+
+    fn progress(&self, content_length: Option<u64>) -> Box<dyn Progress> {
+        if self.options.progress_events {
+            return Box::new(DownloadEvents::new(content_length));
+        }
+        Box::new(ByteProgress::new(
+            self.options.show_progress,
+            content_length,
+            "Downloading",
+            "Downloaded",
+        ))
+    }
+}
+
+impl PartInfo {
+    fn load(info_path: &Path) -> Option<PartInfo> {
+        let content = fs::read_to_string(info_path).ok()?;
+        let mut lines = content.lines();
+
+        Some(PartInfo {
+            total_size: lines.next()?.parse().ok(),
+            etag: lines
+                .next()
+                .filter(|etag| !etag.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    fn save(&self, info_path: &Path) -> io::Result<()> {
+        let total_size = self.total_size.map(|size| size.to_string());
+
+        fs::write(
+            info_path,
+            format!(
+                "{}\n{}\n",
+                total_size.unwrap_or_default(),
+                self.etag.as_deref().unwrap_or_default()
+            ),
+        )
+    }
+}
+
+/// Connection problems and server side errors may go away, client errors like a 404 won't
+fn is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::Status(code, _) => *code >= 500,
+        ureq::Error::Transport(transport) => matches!(
+            transport.kind(),
+            ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+        ),
+    }
+}
+
+/// Body transfers breaking off midway, the next attempt might get through
+fn is_interrupted(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::TimedOut
+            | ErrorKind::BrokenPipe
+    )
+}
+
+fn offline_error() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, OFFLINE_MESSAGE)
+}
+
+fn gave_up(e: io::Error, attempts: u32) -> io::Error {
+    io::Error::new(
+        e.kind(),
+        format!("{} (gave up after {} attempts)", e, attempts),
+    )
+}
+
+/// Exponential backoff with up to 50% jitter, so parallel clients don't retry in lockstep
+fn get_backoff(attempt: u32) -> Duration {
+    let backoff = 2u32
+        .checked_pow(attempt.saturating_sub(1))
+        .and_then(|factor| INITIAL_BACKOFF.checked_mul(factor))
+        .map_or(MAX_BACKOFF, |backoff| backoff.min(MAX_BACKOFF));
+    let jitter_percent = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|now| now.subsec_nanos() % 50)
+        .unwrap_or_default();
+
+    backoff + backoff * jitter_percent / 100
+}
+
+fn agent_builder() -> ureq::AgentBuilder {
+    ureq::AgentBuilder::new()
+        .redirects(MAX_REDIRECTS)
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+}
+
+/// Returns the file extension a downloaded replay of the given type needs
+pub fn get_replay_suffix(replay_type: ReplayType) -> io::Result<&'static str> {
+    match replay_type {
+        ReplayType::Unknown => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Unknown replay format of downloaded replay!",
+        )),
+        ReplayType::ForgedAlliance => Ok(".scfareplay"),
+        ReplayType::FafLegacy => Ok(".fafreplay"),
+    }
+}
+
+fn get_part_info_path(part_path: &Path) -> PathBuf {
+    let mut info_path = part_path.as_os_str().to_owned();
+    info_path.push(".info");
+    PathBuf::from(info_path)
+}
+
+fn vault_replay_url(vault_base_url: &str, replay_id: u32) -> String {
+    format!("{}/{}", vault_base_url.trim_end_matches('/'), replay_id)
+}
+
+fn download_error(e: ureq::Error, url: &str) -> io::Error {
+    match e {
+        ureq::Error::Status(code, _) => io::Error::other(format!(
+            "Download of {} failed with HTTP status {}",
+            url, code
+        )),
+        ureq::Error::Transport(transport) => {
+            io::Error::other(format!("Could not download {}: {}", url, transport))
+        }
+    }
+}
+
+fn vault_error(e: ureq::Error, url: &str, replay_id: u32) -> io::Error {
+    match e {
+        ureq::Error::Status(404, _) => io::Error::new(
+            ErrorKind::NotFound,
+            format!("Replay {} does not exist in the replay vault", replay_id),
+        ),
+        ureq::Error::Status(code, _) => io::Error::other(format!(
+            "Replay vault answered with HTTP status {} for {}",
+            code, url
+        )),
+        ureq::Error::Transport(transport) => {
+            io::Error::other(format!("Could not reach the replay vault: {}", transport))
+        }
+    }
+}
+
+/// Copies the body into the file, `downloaded` being the number of bytes already written
+fn copy_body(reader: &mut impl Read, file: &mut File, mut downloaded: u64) -> io::Result<()> {
+    let mut buffer = [0; 16 * 1024];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        downloaded += read as u64;
+        if downloaded > MAX_DOWNLOAD_SIZE {
+            return Err(download_too_large());
+        }
+        file.write_all(&buffer[..read])?;
+    }
+}
+
+fn download_too_large() -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "Download exceeds the maximum size of {} bytes",
+            MAX_DOWNLOAD_SIZE
+        ),
+    )
+}
+
+fn get_content_length(response: &ureq::Response) -> Option<u64> {
+    response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok())
+}
+
+/// Parses a header like `bytes 400-999/1000` into the start and the total size
+fn get_content_range_start_and_total(response: &ureq::Response) -> Option<(u64, Option<u64>)> {
+    let range = response.header("Content-Range")?.strip_prefix("bytes ")?;
+    let (start_end, total) = range.split_once('/')?;
+    let (start, _) = start_end.split_once('-')?;
+
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+/// Extracts the file name of a header like `attachment; filename="1234.fafreplay"`
+fn get_content_disposition_file_name(content_disposition: &str) -> Option<String> {
+    content_disposition.split(';').find_map(|parameter| {
+        let (key, value) = parameter.trim().split_once('=')?;
+
+        match key.trim().to_ascii_lowercase().as_str() {
+            // RFC 5987 encoding: <charset>'<language>'<value>
+            "filename*" => value.rsplit('\'').next().map(str::to_string),
+            "filename" => Some(value.trim_matches('"').to_string()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    fn replay_body() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Serves one canned response per connection and returns the received request headers.
+    /// A response body shorter than its Content-Length simulates a dropped connection.
+    fn serve(responses: Vec<Vec<u8>>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/42", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while reader.read_line(&mut request).unwrap() > 2 {}
+                requests.push(request.to_lowercase());
+
+                stream.write_all(&response).unwrap();
+            }
+
+            requests
+        });
+
+        (url, server)
+    }
+
+    fn response(status: &str, headers: &[&str], content_length: usize, body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status, content_length
+        );
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+        response.push_str("\r\n");
+
+        let mut response = response.into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn download(url: &str, part_path: &Path, resume: bool) -> io::Result<()> {
+        let options = DownloadOptions {
+            show_progress: false,
+            progress_events: false,
+            verbose: false,
+            retries: 0,
+            proxy: ProxyConfig::default(),
+            offline: false,
+            temp_dir: None,
+        };
+
+        Downloader::new(options).unwrap().download_resumable(
+            url,
+            part_path,
+            resume,
+            &Cell::new(1),
+            |e| io::Error::other(e.to_string()),
+        )
+    }
+
+    #[test]
+    fn caps_the_backoff_of_late_retries() {
+        let first_backoff = get_backoff(1);
+        assert!(first_backoff >= INITIAL_BACKOFF && first_backoff < INITIAL_BACKOFF * 3 / 2);
+
+        for attempt in [10, 33, u32::MAX] {
+            let backoff = get_backoff(attempt);
+            assert!(
+                backoff >= MAX_BACKOFF && backoff < MAX_BACKOFF * 3 / 2,
+                "{:?} before attempt {}",
+                backoff,
+                attempt
+            );
+        }
+    }
+
+    #[test]
+    fn resumes_interrupted_download_with_range_request() {
+        let body = replay_body();
+        let (url, server) = serve(vec![
+            response("200 OK", &["ETag: \"v1\""], 1000, &body[..400]),
+            response(
+                "206 Partial Content",
+                &["ETag: \"v1\"", "Content-Range: bytes 400-999/1000"],
+                600,
+                &body[400..],
+            ),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+
+        assert!(download(&url, &part_path, true).is_err());
+        assert_eq!(fs::metadata(&part_path).unwrap().len(), 400);
+
+        download(&url, &part_path, true).unwrap();
+
+        assert_eq!(fs::read(&part_path).unwrap(), body);
+        assert!(!get_part_info_path(&part_path).exists());
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("range: bytes=400-"));
+        assert!(requests[1].contains("if-range: \"v1\""));
+    }
+
+    #[test]
+    fn retries_interrupted_download_by_resuming_it() {
+        let body = replay_body();
+        let (url, server) = serve(vec![
+            response("200 OK", &["ETag: \"v1\""], 1000, &body[..400]),
+            response(
+                "206 Partial Content",
+                &["ETag: \"v1\"", "Content-Range: bytes 400-999/1000"],
+                600,
+                &body[400..],
+            ),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+        let downloader = Downloader::new(DownloadOptions {
+            show_progress: false,
+            progress_events: false,
+            verbose: false,
+            retries: 1,
+            proxy: ProxyConfig::default(),
+            offline: false,
+            temp_dir: None,
+        })
+        .unwrap();
+
+        downloader
+            .download_vault_replay_resumable(url.trim_end_matches("/42"), 42, &part_path, false)
+            .unwrap();
+
+        assert_eq!(fs::read(&part_path).unwrap(), body);
+        assert!(server.join().unwrap()[1].contains("range: bytes=400-"));
+    }
+
+    #[test]
+    fn shares_the_retries_between_interrupted_and_failing_requests() {
+        let body = replay_body();
+        let (url, server) = serve(vec![
+            response("200 OK", &["ETag: \"v1\""], 1000, &body[..400]),
+            response("503 Service Unavailable", &[], 0, &[]),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+        let downloader = Downloader::new(DownloadOptions {
+            show_progress: false,
+            progress_events: false,
+            verbose: false,
+            retries: 1,
+            proxy: ProxyConfig::default(),
+            offline: false,
+            temp_dir: None,
+        })
+        .unwrap();
+
+        let e = downloader
+            .download_vault_replay_resumable(url.trim_end_matches("/42"), 42, &part_path, false)
+            .unwrap_err();
+
+        assert_eq!(e.to_string().matches("gave up after 2 attempts").count(), 1);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn restarts_download_when_server_sends_the_whole_replay() {
+        let body = replay_body();
+        let (url, server) = serve(vec![
+            response("200 OK", &["ETag: \"v1\""], 1000, &body[..400]),
+            response("200 OK", &["ETag: \"v2\""], 1000, &body),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+
+        assert!(download(&url, &part_path, true).is_err());
+        download(&url, &part_path, true).unwrap();
+
+        assert_eq!(fs::read(&part_path).unwrap(), body);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn no_resume_ignores_existing_part() {
+        let body = replay_body();
+        let (url, server) = serve(vec![response("200 OK", &[], 1000, &body)]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+        fs::write(&part_path, [0xff; 400]).unwrap();
+        fs::write(get_part_info_path(&part_path), "1000\n\"v1\"\n").unwrap();
+
+        download(&url, &part_path, false).unwrap();
+
+        assert_eq!(fs::read(&part_path).unwrap(), body);
+        assert!(!server.join().unwrap()[0].contains("range:"));
+    }
+
+    #[test]
+    fn downloads_again_when_checksum_does_not_match() {
+        use sha2::{Digest, Sha256};
+
+        let body = replay_body();
+        let digest = format!("Digest: sha-256={}", base64::encode(Sha256::digest(&body)));
+        let mut corrupt_body = body.clone();
+        corrupt_body[500] ^= 0xff;
+        let (url, server) = serve(vec![
+            response("200 OK", &[&digest], 1000, &corrupt_body),
+            response("200 OK", &[&digest], 1000, &body),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let part_path = dir.path().join("42.part");
+        let downloader = Downloader::new(DownloadOptions {
+            show_progress: false,
+            progress_events: false,
+            verbose: false,
+            retries: 1,
+            proxy: ProxyConfig::default(),
+            offline: false,
+            temp_dir: None,
+        })
+        .unwrap();
+
+        downloader
+            .download_vault_replay_resumable(url.trim_end_matches("/42"), 42, &part_path, false)
+            .unwrap();
+
+        assert_eq!(fs::read(&part_path).unwrap(), body);
+        // the corrupt part was thrown away instead of being resumed
+        assert!(!server.join().unwrap()[1].contains("range:"));
+    }
+}
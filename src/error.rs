@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    UnknownFormat,
+    MissingMetadata,
+    InvalidMetadata(serde_json::Error),
+    MissingStream,
+    Base64(base64::DecodeError),
+    Decompress(io::Error),
+    InvalidHeader(String),
+    InvalidBody(String),
+    /// With `PrepareOptions::strict`, the number of bytes after the compressed stream
+    TrailingData(usize),
+    /// Decompresses to more than the limit in bytes, see `PrepareOptions::max_size`
+    TooLarge(u64),
+}
+
+impl ReplayError {
+    /// Stable name of the error for machine-readable output, e.g. `MissingStream`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReplayError::Io(_) => "Io",
+            ReplayError::UnknownFormat => "UnknownFormat",
+            ReplayError::MissingMetadata => "MissingMetadata",
+            ReplayError::InvalidMetadata(_) => "InvalidMetadata",
+            ReplayError::MissingStream => "MissingStream",
+            ReplayError::Base64(_) => "Base64",
+            ReplayError::Decompress(_) => "Decompress",
+            ReplayError::InvalidHeader(_) => "InvalidHeader",
+            ReplayError::InvalidBody(_) => "InvalidBody",
+            ReplayError::TrailingData(_) => "TrailingData",
+            ReplayError::TooLarge(_) => "TooLarge",
+        }
+    }
+
+    /// Process exit code for the error: 202 if the file couldn't be read,
+    /// 203 if the replay is corrupt and 204 if its format is unknown.
+    /// Exit codes from 200 on can't be confused with the ones of the game, which are passed on.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ReplayError::Io(_) => 202,
+            ReplayError::UnknownFormat => 204,
+            _ => 203,
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "Replay file issues - {}", e),
+            ReplayError::UnknownFormat => write!(f, "Unknown replay format!"),
+            ReplayError::MissingMetadata => {
+                write!(f, "Replay corrupt - replay metadata json is missing")
+            }
+            ReplayError::InvalidMetadata(e) => {
+                write!(
+                    f,
+                    "Replay corrupt - replay metadata json is invalid ({})",
+                    e
+                )
+            }
+            ReplayError::MissingStream => {
+                write!(f, "Replay corrupt - binary replay stream is missing")
+            }
+            ReplayError::Base64(e) => write!(f, "Replay corrupt - couldn't decode base64 ({})", e),
+            ReplayError::Decompress(e) => {
+                write!(
+                    f,
+                    "Replay corrupt - couldn't decompress replay stream ({})",
+                    e
+                )
+            }
+            ReplayError::InvalidHeader(reason) => {
+                write!(
+                    f,
+                    "Replay corrupt - couldn't parse replay header ({})",
+                    reason
+                )
+            }
+            ReplayError::InvalidBody(reason) => {
+                write!(
+                    f,
+                    "Replay corrupt - couldn't parse replay commands ({})",
+                    reason
+                )
+            }
+            ReplayError::TrailingData(trailing_bytes) => {
+                write!(
+                    f,
+                    "Replay corrupt - {} bytes of trailing data after the compressed replay stream",
+                    trailing_bytes
+                )
+            }
+            ReplayError::TooLarge(max_size) => {
+                write!(
+                    f,
+                    "Replay too large - decompresses to more than {} bytes",
+                    max_size
+                )
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io(e) | ReplayError::Decompress(e) => Some(e),
+            ReplayError::InvalidMetadata(e) => Some(e),
+            ReplayError::Base64(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
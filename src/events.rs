@@ -0,0 +1,102 @@
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+/// `--progress-format json`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// What `--progress-format json` reports on stdout, one JSON object per line.
+/// Frontends rely on the names and fields, so they are only ever added to.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The replay is looked at to tell its format
+    Detecting,
+    /// `total` is null if the server didn't send the size
+    Downloading {
+        bytes: u64,
+        total: Option<u64>,
+    },
+    /// A compressed replay is extracted for the game
+    Extracting,
+    Launching {
+        pid: u32,
+    },
+    /// `code` is null if the game was ended by a signal
+    GameExited {
+        code: Option<i32>,
+    },
+    /// `kind` is the error code in snake case, e.g. `missing_stream`
+    Error {
+        kind: String,
+        message: &'a str,
+    },
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Prints the event if enabled, flushed right away as the frontend waits for it
+pub fn emit(event: &Event) {
+    if !enabled() {
+        return;
+    }
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", serde_json::to_string(event).unwrap());
+    let _ = stdout.flush();
+}
+
+/// `MissingStream` as `missing_stream`
+pub fn to_kind(code: &str) -> String {
+    let mut kind = String::new();
+    for (i, c) in code.char_indices() {
+        if c.is_ascii_uppercase() && i > 0 {
+            kind.push('_');
+        }
+        kind.push(c.to_ascii_lowercase());
+    }
+    kind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_keep_their_names_and_fields() {
+        let json = |event: Event| serde_json::to_string(&event).unwrap();
+
+        assert_eq!(json(Event::Detecting), r#"{"event":"detecting"}"#);
+        assert_eq!(
+            json(Event::Downloading {
+                bytes: 10,
+                total: None
+            }),
+            r#"{"event":"downloading","bytes":10,"total":null}"#
+        );
+        assert_eq!(json(Event::Extracting), r#"{"event":"extracting"}"#);
+        assert_eq!(
+            json(Event::Launching { pid: 1234 }),
+            r#"{"event":"launching","pid":1234}"#
+        );
+        assert_eq!(
+            json(Event::GameExited { code: Some(0) }),
+            r#"{"event":"game_exited","code":0}"#
+        );
+        assert_eq!(
+            json(Event::Error {
+                kind: to_kind("MissingStream"),
+                message: "No replay stream"
+            }),
+            r#"{"event":"error","kind":"missing_stream","message":"No replay stream"}"#
+        );
+        assert_eq!(to_kind("Base64"), "base64");
+    }
+}
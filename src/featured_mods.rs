@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+/// Passed to the game for replays not telling their featured mod
+pub const DEFAULT_FEATURED_MOD: &str = "faf";
+
+/// The init file of the retail game, used for replays without featured mod
+pub const DEFAULT_INIT_FILE: &str = "init.lua";
+
+/// Init files of the featured mods, others are assumed to follow the `init_<featured mod>.lua` scheme
+const INIT_FILES: [(&str, &str); 10] = [
+    ("faf", "init_faf.lua"),
+    ("fafbeta", "init_fafbeta.lua"),
+    ("fafdevelop", "init_fafdevelop.lua"),
+    ("coop", "init_coop.lua"),
+    // matchmaker games are played with the regular FAF balance
+    ("ladder1v1", "init_faf.lua"),
+    ("tmm2v2", "init_faf.lua"),
+    ("nomads", "init_nomads.lua"),
+    ("murderparty", "init_murderparty.lua"),
+    ("labwars", "init_labwars.lua"),
+    ("phantomx", "init_phantomx.lua"),
+];
+
+/// The init file a replay of the featured mod has to be started with, otherwise the game
+/// runs another sim than the recorded one and desyncs. `overrides` are taken from the config file.
+pub fn get_init_file(featured_mod: &str, overrides: &BTreeMap<String, String>) -> String {
+    if let Some(init_file) = overrides.get(featured_mod) {
+        return init_file.clone();
+    }
+
+    INIT_FILES
+        .iter()
+        .find(|(known_mod, _)| *known_mod == featured_mod)
+        .map_or_else(
+            || format!("init_{}.lua", featured_mod),
+            |(_, init_file)| init_file.to_string(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_featured_mods_to_init_files() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("nomads".to_string(), "init_nomads_v2.lua".to_string());
+
+        assert_eq!(get_init_file("faf", &overrides), "init_faf.lua");
+        assert_eq!(get_init_file("coop", &overrides), "init_coop.lua");
+        assert_eq!(get_init_file("ladder1v1", &overrides), "init_faf.lua");
+        assert_eq!(get_init_file("nomads", &overrides), "init_nomads_v2.lua");
+    }
+}
@@ -0,0 +1,241 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::maps;
+
+/// How many logs are kept in the log directory without `keep_logs` in the config file
+pub const DEFAULT_KEEP_LOGS: usize = 20;
+/// How much of the log is shown when the game failed
+pub const TAIL_LINES: usize = 30;
+
+const LOG_PREFIX: &str = "game-";
+const LOG_EXTENSION: &str = "log";
+
+/// Startup failures `--launch-retries` retries, they usually succeed on the next try, e.g. under wine right after
+/// boot when the graphics or sound device isn't ready yet. Case insensitive.
+pub const TRANSIENT_FAILURES: &[&str] = &[
+    "Failed to create Direct3D device",
+    "D3DERR_NOTAVAILABLE",
+    "D3DERR_DEVICELOST",
+    "DirectSoundCreate failed",
+    "Failed to initialize sound",
+    "XAudio2Create failed",
+];
+
+/// A known problem showing up in the game log
+struct Signature {
+    /// Searched in every line, case insensitive
+    patterns: &'static [&'static str],
+    /// Picks what the hint is about from the rest of the line after the pattern, e.g. the map
+    detail: Option<fn(&str) -> Option<&str>>,
+    /// `{}` is replaced by the detail
+    hint: &'static str,
+}
+
+/// Checked in this order, each one is reported once
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        patterns: &["Unable to open map"],
+        detail: Some(map_folder),
+        hint: "The map {} was not found, install it from the map vault or launch without --no-map-download \
+               to download it",
+    },
+    Signature {
+        patterns: &["Unable to find mod"],
+        detail: Some(first_word),
+        hint: "The mod {} of the replay is not installed, download it in the mod vault of the FAF client",
+    },
+    Signature {
+        patterns: &["Failed to create Direct3D device", "D3DERR_"],
+        detail: None,
+        hint: "The game could not set up its graphics device, check the graphics driver \
+               (with wine or Proton: DXVK) and try a windowed mode",
+    },
+    Signature {
+        patterns: &["access violation", "EXCEPTION_ACCESS_VIOLATION"],
+        detail: None,
+        hint: "The game crashed with an access violation, usually a broken mod or map, or the game ran out of memory",
+    },
+    Signature {
+        patterns: &["desync"],
+        detail: None,
+        hint: "The replay desynced, it was probably recorded with another game version or featured mod \
+               than it was launched with (--featured-mod, --profile)",
+    },
+];
+
+/// A new `game-<time>-<replay id>.log` in `log_dir`, after removing the oldest logs so that `keep` remain
+/// including the new one
+pub fn new_log_path(log_dir: &Path, replay_id: u32, keep: usize) -> io::Result<PathBuf> {
+    fs::create_dir_all(log_dir)?;
+    prune(log_dir, keep.saturating_sub(1))?;
+
+    Ok(log_path(log_dir, replay_id))
+}
+
+/// `game-<time>-<replay id>.log` in `log_dir`, without touching the directory
+pub fn log_path(log_dir: &Path, replay_id: u32) -> PathBuf {
+    // colons aren't allowed in Windows file names
+    let time = humantime::format_rfc3339_seconds(SystemTime::now())
+        .to_string()
+        .replace(':', "-");
+    log_dir.join(format!(
+        "{}{}-{}.{}",
+        LOG_PREFIX, time, replay_id, LOG_EXTENSION
+    ))
+}
+
+/// Removes all but the newest `keep` logs, other files in the directory are left alone
+fn prune(log_dir: &Path, keep: usize) -> io::Result<()> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_game_log(path))
+        .collect();
+    // the time in the name sorts them from old to new
+    logs.sort();
+
+    let remove_count = logs.len().saturating_sub(keep);
+    for log in &logs[..remove_count] {
+        fs::remove_file(log)?;
+    }
+    Ok(())
+}
+
+fn is_game_log(path: &Path) -> bool {
+    let file_name = path.file_name().and_then(|name| name.to_str());
+    file_name.is_some_and(|name| name.starts_with(LOG_PREFIX))
+        && path
+            .extension()
+            .is_some_and(|extension| extension == LOG_EXTENSION)
+}
+
+/// The log as text, it isn't necessarily UTF-8
+pub fn read(path: &Path) -> io::Result<String> {
+    Ok(String::from_utf8_lossy(&fs::read(path)?).into_owned())
+}
+
+/// The last `count` lines of the log
+pub fn tail(log: &str, count: usize) -> String {
+    let lines: Vec<&str> = log.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+/// Hints for the known problems found in the log, in the order of the signatures
+pub fn diagnose(log: &str) -> Vec<String> {
+    SIGNATURES
+        .iter()
+        .filter_map(|signature| {
+            let rest = log.lines().find_map(|line| {
+                // ASCII lowercase keeps the byte offsets
+                let lowercase_line = line.to_ascii_lowercase();
+                signature.patterns.iter().find_map(|pattern| {
+                    let start = lowercase_line.find(&pattern.to_ascii_lowercase())?;
+                    Some(&line[start + pattern.len()..])
+                })
+            })?;
+
+            Some(match signature.detail {
+                Some(detail) => signature
+                    .hint
+                    .replace("{}", detail(rest).unwrap_or("of the replay")),
+                None => signature.hint.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The first line of the log with one of the `patterns`, case insensitive
+pub fn find_transient_failure<'a, P: AsRef<str>>(log: &'a str, patterns: &[P]) -> Option<&'a str> {
+    log.lines().map(str::trim).find(|line| {
+        let lowercase_line = line.to_lowercase();
+        patterns
+            .iter()
+            .any(|pattern| lowercase_line.contains(&pattern.as_ref().to_lowercase()))
+    })
+}
+
+/// `setons_clutch.v0005` of ` /maps/setons_clutch.v0005/setons_clutch_scenario.lua`
+fn map_folder(rest: &str) -> Option<&str> {
+    first_word(rest).and_then(maps::get_map_folder)
+}
+
+/// Without surrounding quotes or colons
+fn first_word(rest: &str) -> Option<&str> {
+    let word = rest
+        .split_whitespace()
+        .next()?
+        .trim_matches(|c| c == '"' || c == '\'' || c == ':');
+    Some(word).filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "game-2024-01-01T10-00-00Z-1.log",
+            "game-2024-01-02T10-00-00Z-2.log",
+            "game-2024-01-03T10-00-00Z-3.log",
+            "notes.txt",
+        ]
+        .iter()
+        {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let log_path = new_log_path(dir.path(), 4, 2).unwrap();
+
+        let mut remaining: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, ["game-2024-01-03T10-00-00Z-3.log", "notes.txt"]);
+        assert!(is_game_log(&log_path));
+        assert!(log_path.to_str().unwrap().ends_with("-4.log"));
+    }
+
+    #[test]
+    fn tails_the_last_lines() {
+        assert_eq!(tail("one\ntwo\nthree\n", 2), "two\nthree");
+        assert_eq!(tail("one", 30), "one");
+        assert_eq!(tail("", 30), "");
+    }
+
+    #[test]
+    fn diagnoses_known_problems() {
+        let log = "info: Loading map\n\
+                   warning: Unable to open map /maps/setons_clutch.v0005/setons_clutch_scenario.lua\n\
+                   warning: Unable to find mod \"e7846e9b-23a4-4b95-ae3a-fb69b289a585\"\n\
+                   error: EXCEPTION_ACCESS_VIOLATION (0xc0000005) at address 0x008b5a5e\n";
+
+        let hints = diagnose(log);
+
+        assert_eq!(hints.len(), 3);
+        assert!(hints[0].starts_with("The map setons_clutch.v0005 was not found"));
+        assert!(hints[1].starts_with("The mod e7846e9b-23a4-4b95-ae3a-fb69b289a585 of the replay"));
+        assert!(hints[2].contains("access violation"));
+        assert!(diagnose("info: Loading map\n").is_empty());
+    }
+
+    #[test]
+    fn finds_transient_startup_failures() {
+        let log = "info: Initializing sound\n  warning: DirectSoundCreate FAILED: 0x88780078\ninfo: exiting\n";
+
+        assert_eq!(
+            find_transient_failure(log, TRANSIENT_FAILURES),
+            Some("warning: DirectSoundCreate FAILED: 0x88780078")
+        );
+        assert_eq!(find_transient_failure(log, &["device lost"]), None);
+        assert_eq!(
+            find_transient_failure(log, &["exiting".to_string()]),
+            Some("info: exiting")
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+/// Start of `VS_FIXEDFILEINFO` in the version resource of a PE executable
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = 0xfeef04bd_u32.to_le_bytes();
+
+/// The build of a game version like `Supreme Commander v1.50.3701`, the number replays are compatible by
+pub fn parse_build(game_version: &str) -> Option<u32> {
+    game_version
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .find(|part| !part.is_empty())?
+        .parse()
+        .ok()
+}
+
+/// The build of the game the executable belongs to, best effort: a `version` file next to it
+/// (as some Linux installs have) or the file version in the version resource of the executable
+pub fn read_executable_build(executable: &Path) -> Option<u32> {
+    let version_file = executable.parent()?.join("version");
+    if let Ok(version) = fs::read_to_string(version_file) {
+        return parse_build(version.trim());
+    }
+
+    parse_file_version_build(&fs::read(executable).ok()?)
+}
+
+/// Warns like `The replay is of game version 3811, the executable of 3779 (older)`, None if they match
+pub fn describe_mismatch(replay_build: u32, executable_build: u32) -> Option<String> {
+    let executable_age = match executable_build.cmp(&replay_build) {
+        Ordering::Equal => return None,
+        Ordering::Less => "older",
+        Ordering::Greater => "newer",
+    };
+
+    Some(format!(
+        "The replay is of game version {}, the executable of {} ({}), the replay will most likely desync",
+        replay_build, executable_build, executable_age
+    ))
+}
+
+/// The last non-zero part of the file version, e.g. `3701` of `1.5.3701.0`
+fn parse_file_version_build(executable: &[u8]) -> Option<u32> {
+    let start = executable
+        .windows(FIXED_FILE_INFO_SIGNATURE.len())
+        .position(|window| window == FIXED_FILE_INFO_SIGNATURE)?;
+    // signature, struct version, then the most and least significant half of the file version
+    let read_u32 = |offset: usize| {
+        let bytes = executable.get(start + offset..start + offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let (most_significant, least_significant) = (read_u32(8)?, read_u32(12)?);
+
+    [
+        most_significant >> 16,
+        most_significant & 0xffff,
+        least_significant >> 16,
+        least_significant & 0xffff,
+    ]
+    .iter()
+    .rev()
+    .copied()
+    .find(|&part| part != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_builds_of_replays_and_executables() {
+        assert_eq!(parse_build("Supreme Commander v1.50.3701"), Some(3701));
+        assert_eq!(parse_build("3599\n".trim()), Some(3599));
+        assert_eq!(parse_build("Supreme Commander"), None);
+
+        let mut executable = b"MZ version resource ".to_vec();
+        executable.extend(FIXED_FILE_INFO_SIGNATURE);
+        executable.extend(0x0001_0000_u32.to_le_bytes());
+        executable.extend(0x0001_0005_u32.to_le_bytes());
+        executable.extend(0x0e75_0000_u32.to_le_bytes());
+        assert_eq!(parse_file_version_build(&executable), Some(3701));
+    }
+}
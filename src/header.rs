@@ -0,0 +1,232 @@
+use std::convert::TryInto;
+
+use crate::error::ReplayError;
+
+/// The header of a raw `.scfareplay`, everything in front of the command stream.
+#[derive(Debug, Clone)]
+pub struct ReplayHeader {
+    /// e.g. `Supreme Commander v1.50.3701`
+    pub game_version: String,
+    /// e.g. `Replay v1.9`
+    pub replay_version: String,
+    /// e.g. `/maps/setons_clutch.v0004/setons_clutch.scmap`
+    pub map_path: String,
+    pub mods: LuaValue,
+    /// The scenario table, containing the map name and the game options
+    pub scenario: LuaValue,
+    /// The command sources, i.e. the players and observers connected to the game
+    pub players: Vec<ReplayPlayer>,
+    pub cheats_enabled: bool,
+    pub armies: Vec<ReplayArmy>,
+    pub random_seed: u32,
+    /// Length of the header in bytes, the command stream starts right after it
+    pub length: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayPlayer {
+    pub name: String,
+    pub id: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayArmy {
+    /// The army table with `PlayerName`, `Faction`, `Team` and more
+    pub data: LuaValue,
+    /// Index into `ReplayHeader::players`, 255 for armies without a player (e.g. AIs)
+    pub source: u8,
+}
+
+/// A value of the lua tables serialized into the header
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaValue {
+    Number(f32),
+    String(String),
+    Nil,
+    Bool(bool),
+    /// Key value pairs in the order they are stored in
+    Table(Vec<(LuaValue, LuaValue)>),
+}
+
+impl LuaValue {
+    /// Looks up a string key of a table
+    pub fn get(&self, key: &str) -> Option<&LuaValue> {
+        match self {
+            LuaValue::Table(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key.as_str() == Some(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LuaValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            LuaValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+impl ReplayHeader {
+    /// The map name from the scenario, e.g. `Seton's Clutch`
+    pub fn map_name(&self) -> Option<&str> {
+        self.scenario.get("name").and_then(LuaValue::as_str)
+    }
+}
+
+/// Parses the header of the raw `.scfareplay` bytes
+pub fn parse_replay_header(raw_replay: &[u8]) -> Result<ReplayHeader, ReplayError> {
+    let mut reader = HeaderReader {
+        bytes: raw_replay,
+        position: 0,
+    };
+
+    let game_version = reader.read_string()?;
+    reader.skip(3)?;
+    let replay_version_and_map = reader.read_string()?;
+    let (replay_version, map_path) = replay_version_and_map
+        .split_once("\r\n")
+        .ok_or_else(|| invalid_header("map path is missing"))?;
+    reader.skip(4)?;
+
+    let mods = reader.read_sized_lua()?;
+    let scenario = reader.read_sized_lua()?;
+
+    let player_count = reader.read_u8()?;
+    let mut players = Vec::with_capacity(player_count as usize);
+    for _ in 0..player_count {
+        let name = reader.read_string()?;
+        let id = reader.read_u32()? as i32;
+        players.push(ReplayPlayer { name, id });
+    }
+
+    let cheats_enabled = reader.read_u8()? != 0;
+
+    let army_count = reader.read_u8()?;
+    let mut armies = Vec::with_capacity(army_count as usize);
+    for _ in 0..army_count {
+        let data = reader.read_sized_lua()?;
+        let source = reader.read_u8()?;
+        if source != 255 {
+            reader.skip(1)?;
+        }
+        armies.push(ReplayArmy { data, source });
+    }
+
+    let random_seed = reader.read_u32()?;
+
+    Ok(ReplayHeader {
+        game_version,
+        replay_version: replay_version.to_string(),
+        map_path: map_path.to_string(),
+        mods,
+        scenario,
+        players,
+        cheats_enabled,
+        armies,
+        random_seed,
+        length: reader.position,
+    })
+}
+
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ReplayError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid_header("header is truncated"))?;
+
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), ReplayError> {
+        self.take(length).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, ReplayError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a null terminated string
+    fn read_string(&mut self) -> Result<String, ReplayError> {
+        let rest = &self.bytes[self.position..];
+        let length = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| invalid_header("string is not terminated"))?;
+
+        let string = String::from_utf8_lossy(&rest[..length]).into_owned();
+        self.position += length + 1;
+        Ok(string)
+    }
+
+    /// Reads a lua value prefixed with its size in bytes
+    fn read_sized_lua(&mut self) -> Result<LuaValue, ReplayError> {
+        let size = self.read_u32()? as usize;
+        let end = self.position + size;
+        let value = self.read_lua()?;
+
+        if self.position != end {
+            return Err(invalid_header("lua table size mismatch"));
+        }
+        Ok(value)
+    }
+
+    fn read_lua(&mut self) -> Result<LuaValue, ReplayError> {
+        match self.read_u8()? {
+            0 => Ok(LuaValue::Number(self.read_f32()?)),
+            1 => Ok(LuaValue::String(self.read_string()?)),
+            2 => {
+                self.skip(1)?;
+                Ok(LuaValue::Nil)
+            }
+            3 => Ok(LuaValue::Bool(self.read_u8()? != 0)),
+            4 => {
+                let mut entries = Vec::new();
+                while self.peek_u8()? != 5 {
+                    let key = self.read_lua()?;
+                    let value = self.read_lua()?;
+                    entries.push((key, value));
+                }
+                self.skip(1)?;
+                Ok(LuaValue::Table(entries))
+            }
+            other => Err(invalid_header(&format!("unknown lua type {}", other))),
+        }
+    }
+
+    fn peek_u8(&self) -> Result<u8, ReplayError> {
+        self.bytes
+            .get(self.position)
+            .copied()
+            .ok_or_else(|| invalid_header("header is truncated"))
+    }
+}
+
+fn invalid_header(reason: &str) -> ReplayError {
+    ReplayError::InvalidHeader(reason.to_string())
+}
@@ -0,0 +1,400 @@
+use std::cmp::Ordering;
+use std::time::{Duration, SystemTime};
+
+use faf_replay_cli::{
+    CommandCount, LuaValue, ReplayArmy, ReplayHeader, ReplayMetadata, ReplayPeek, ReplayType,
+};
+use serde_json::json;
+
+use crate::api::OnlineGame;
+use crate::game_version;
+
+/// Everything known locally about a replay file
+pub struct LocalInfo<'a> {
+    pub replay_type: ReplayType,
+    pub header: &'a ReplayHeader,
+    pub metadata: Option<&'a ReplayMetadata>,
+    pub modified: Option<SystemTime>,
+    /// Game time, None if the replay is incomplete
+    pub duration: Option<Duration>,
+    /// Game version of `--executable`, to tell if the replay plays with it
+    pub executable_build: Option<u32>,
+    /// Only counted with `--verbose`
+    pub command_count: Option<CommandCount>,
+}
+
+/// `info --format`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InfoFormat {
+    Text,
+    Json,
+    /// `key=value` lines
+    KeyValue,
+    /// A header line and a line of values
+    Csv,
+}
+
+impl InfoFormat {
+    pub fn parse(name: &str) -> Option<InfoFormat> {
+        match name {
+            "text" => Some(InfoFormat::Text),
+            "json" => Some(InfoFormat::Json),
+            "kv" => Some(InfoFormat::KeyValue),
+            "csv" => Some(InfoFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+pub fn print_local_info(info: &LocalInfo, format: InfoFormat) {
+    match format {
+        InfoFormat::Text => print_local_info_text(info),
+        InfoFormat::Json => println!("{:#}", local_info_json(info)),
+        InfoFormat::KeyValue => {
+            for (key, value) in local_info_fields(info) {
+                // one line per field, whatever the title contains
+                println!("{}={}", key, value.replace(['\r', '\n'], " "));
+            }
+        }
+        InfoFormat::Csv => {
+            let fields = local_info_fields(info);
+            let keys: Vec<&str> = fields.iter().map(|(key, _)| *key).collect();
+            let values: Vec<String> = fields.iter().map(|(_, value)| quote_csv(value)).collect();
+            println!("{}", keys.join(","));
+            println!("{}", values.join(","));
+        }
+    }
+}
+
+/// The fields of `kv` and `csv` in a fixed order scripts can rely on, unknown values are empty
+fn local_info_fields(info: &LocalInfo) -> Vec<(&'static str, String)> {
+    let header = info.header;
+    let metadata = info.metadata;
+    let players: Vec<String> = header
+        .armies
+        .iter()
+        .map(|army| {
+            let (name, details) = describe_army(army);
+            if details.is_empty() {
+                name.to_string()
+            } else {
+                format!("{} ({})", name, details.join(", "))
+            }
+        })
+        .collect();
+
+    let mut fields = vec![
+        ("format", get_format_name(&info.replay_type).to_string()),
+        ("type", info.replay_type.to_string()),
+        ("game_version", header.game_version.clone()),
+        ("executable_build", to_string(info.executable_build)),
+        ("replay_version", header.replay_version.clone()),
+        (
+            "map_name",
+            header.map_name().unwrap_or_default().to_string(),
+        ),
+        ("map_path", header.map_path.clone()),
+        (
+            "date",
+            to_string(info.modified.map(humantime::format_rfc3339_seconds)),
+        ),
+        (
+            "duration_seconds",
+            to_string(info.duration.map(|duration| duration.as_secs())),
+        ),
+        (
+            "replay_id",
+            to_string(metadata.and_then(|metadata| metadata.uid)),
+        ),
+        (
+            "title",
+            to_string(metadata.and_then(|metadata| metadata.title.as_deref())),
+        ),
+        (
+            "featured_mod",
+            to_string(metadata.and_then(|metadata| metadata.featured_mod.as_deref())),
+        ),
+        ("players", players.join("; ")),
+    ];
+    // after the others, their positions stay the same with --verbose
+    if let Some(count) = &info.command_count {
+        fields.push(("commands", count.commands.to_string()));
+        fields.push(("ticks", count.ticks.to_string()));
+        fields.push(("counts_approximate", count.is_approximate().to_string()));
+    }
+    fields
+}
+
+fn local_info_json(info: &LocalInfo) -> serde_json::Value {
+    let header = info.header;
+    let metadata = info.metadata;
+    let players: Vec<serde_json::Value> = header
+        .armies
+        .iter()
+        .map(|army| {
+            json!({
+                "name": army.data.get("PlayerName").and_then(LuaValue::as_str),
+                "team": army.data.get("Team").and_then(LuaValue::as_f32).map(|team| team as i32),
+                "faction": army.data.get("Faction").and_then(LuaValue::as_f32).map(get_faction_name),
+            })
+        })
+        .collect();
+
+    let mut info_json = json!({
+        "format": get_format_name(&info.replay_type),
+        "type": info.replay_type.as_str(),
+        "game_version": header.game_version,
+        "executable_build": info.executable_build,
+        "replay_version": header.replay_version,
+        "map_name": header.map_name(),
+        "map_path": header.map_path,
+        "date": info.modified.map(|modified| humantime::format_rfc3339_seconds(modified).to_string()),
+        "duration_seconds": info.duration.map(|duration| duration.as_secs()),
+        "replay_id": metadata.and_then(|metadata| metadata.uid),
+        "title": metadata.and_then(|metadata| metadata.title.as_deref()),
+        "featured_mod": metadata.and_then(|metadata| metadata.featured_mod.as_deref()),
+        "players": players,
+    });
+    if let Some(count) = &info.command_count {
+        info_json["commands"] = json!(count.commands);
+        info_json["ticks"] = json!(count.ticks);
+        info_json["counts_approximate"] = json!(count.is_approximate());
+    }
+    info_json
+}
+
+fn to_string(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Quoted like RFC 4180 if needed
+fn quote_csv(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The player name and its team and faction, as far as known
+fn describe_army(army: &ReplayArmy) -> (&str, Vec<String>) {
+    let name = army
+        .data
+        .get("PlayerName")
+        .and_then(LuaValue::as_str)
+        .unwrap_or("?");
+    let team = army.data.get("Team").and_then(LuaValue::as_f32);
+    let faction = army.data.get("Faction").and_then(LuaValue::as_f32);
+
+    let mut details = Vec::new();
+    if let Some(team) = team {
+        details.push(format!("team {}", team));
+    }
+    if let Some(faction) = faction {
+        details.push(get_faction_name(faction));
+    }
+    (name, details)
+}
+
+fn print_local_info_text(info: &LocalInfo) {
+    let header = info.header;
+
+    print_field("Format", get_format_name(&info.replay_type));
+    print_field("Game version", &header.game_version);
+    let replay_build = game_version::parse_build(&header.game_version);
+    if let (Some(replay_build), Some(executable_build)) = (replay_build, info.executable_build) {
+        let compatibility = match executable_build.cmp(&replay_build) {
+            Ordering::Equal => "same as the replay",
+            Ordering::Less => "older, the replay will most likely desync",
+            Ordering::Greater => "newer, the replay will most likely desync",
+        };
+        print_field(
+            "Executable",
+            &format!("game version {}, {}", executable_build, compatibility),
+        );
+    }
+    print_field("Replay version", &header.replay_version);
+    match header.map_name() {
+        Some(map_name) => print_field("Map", &format!("{} ({})", map_name, header.map_path)),
+        None => print_field("Map", &header.map_path),
+    }
+    if let Some(modified) = info.modified {
+        print_field(
+            "Date",
+            &format!(
+                "~{} (file modification time)",
+                humantime::format_rfc3339_seconds(modified)
+            ),
+        );
+    }
+    match info.duration {
+        Some(duration) => print_field(
+            "Duration",
+            &format!("~{} (game time)", format_duration(duration)),
+        ),
+        None => print_field("Duration", "unknown, the replay is incomplete"),
+    }
+    if let Some(count) = &info.command_count {
+        let counts = format!("{} over {} ticks", count.commands, count.ticks);
+        match count.invalid_at {
+            Some(offset) => print_field(
+                "Commands",
+                &format!(
+                    "at least {} (approximate, the body can't be parsed after byte {})",
+                    counts, offset
+                ),
+            ),
+            None => print_field("Commands", &counts),
+        }
+    }
+
+    if let Some(metadata) = info.metadata {
+        if let Some(uid) = metadata.uid {
+            print_field("Replay id", &uid.to_string());
+        }
+        if let Some(title) = &metadata.title {
+            print_field("Title", title);
+        }
+        if let Some(featured_mod) = &metadata.featured_mod {
+            print_field("Featured mod", featured_mod);
+        }
+    }
+
+    println!("Players:");
+    for army in &header.armies {
+        let (name, details) = describe_army(army);
+        println!("  {:<20} {}", name, details.join(", "));
+    }
+}
+
+/// Tool and replay version in one line for bug reports, e.g.
+/// `faf-replay-cli 0.1, FAF legacy replay, Supreme Commander v1.50.3701, Replay v1.9`
+pub fn format_version_string(
+    tool_version: &str,
+    replay_type: &ReplayType,
+    header: &ReplayHeader,
+) -> String {
+    format!(
+        "faf-replay-cli {}, {}, {}, {}",
+        tool_version,
+        get_format_name(replay_type),
+        header.game_version,
+        header.replay_version
+    )
+}
+
+/// Prints the fields fetched from the FAF API in their own section,
+/// so they can't be mistaken for data from the replay file
+pub fn print_online_info(game: &OnlineGame, api_base_url: &str) {
+    println!();
+    println!("From the FAF API ({}):", api_base_url);
+    print_field("Game id", &game.id.to_string());
+    if let Some(title) = &game.title {
+        print_field("Title", title);
+    }
+    if let Some(start_time) = &game.start_time {
+        print_field("Started", start_time);
+    }
+    if let Some(end_time) = &game.end_time {
+        print_field("Ended", end_time);
+    }
+
+    println!("Players:");
+    for player in &game.players {
+        match player.rating {
+            Some(rating) => println!("  {:<20} rating {}", player.login, rating),
+            None => println!("  {}", player.login),
+        }
+    }
+}
+
+pub fn print_peek(peek: &ReplayPeek) {
+    print_field("Decompressed", &format!("{} bytes", peek.decompressed_size));
+    print_field("Header", &peek.first_header_line);
+}
+
+/// Prints search results numbered from 1, the numbers `search --watch` takes
+pub fn print_game_table(games: &[OnlineGame]) {
+    println!(
+        "{:>3}  {:<10} {:<16}  {:<24} {:<32} {:>8} {:>6}",
+        "#", "ID", "DATE", "MAP", "PLAYERS", "DURATION", "RATING"
+    );
+
+    for (index, game) in games.iter().enumerate() {
+        let date: String = game
+            .start_time
+            .as_deref()
+            .map(|start_time| start_time.replacen('T', " ", 1).chars().take(16).collect())
+            .unwrap_or_default();
+        let players: Vec<&str> = game
+            .players
+            .iter()
+            .map(|player| player.login.as_str())
+            .collect();
+
+        println!(
+            "{:>3}  {:<10} {:<16}  {:<24} {:<32} {:>8} {:>6}",
+            index + 1,
+            game.id,
+            date,
+            truncate(game.map_name.as_deref().unwrap_or("?"), 24),
+            truncate(&players.join(", "), 32),
+            game.duration().map(format_duration).unwrap_or_default(),
+            game.average_rating()
+                .map(|rating| rating.to_string())
+                .unwrap_or_default()
+        );
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+fn get_format_name(replay_type: &ReplayType) -> &'static str {
+    match replay_type {
+        ReplayType::FafLegacy => "FAF legacy replay",
+        _ => "Forged Alliance replay",
+    }
+}
+
+fn print_field(name: &str, value: &str) {
+    println!("{:<16}{}", format!("{}:", name), value);
+}
+
+fn get_faction_name(faction: f32) -> String {
+    match faction as i32 {
+        1 => "UEF".to_string(),
+        2 => "Aeon".to_string(),
+        3 => "Cybran".to_string(),
+        4 => "Seraphim".to_string(),
+        _ => format!("faction {}", faction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_csv_values_only_if_needed() {
+        assert_eq!(quote_csv("Seton's Clutch"), "Seton's Clutch");
+        assert_eq!(quote_csv("Alice (team 2, UEF)"), "\"Alice (team 2, UEF)\"");
+        assert_eq!(quote_csv("the \"best\" game"), "\"the \"\"best\"\" game\"");
+    }
+}
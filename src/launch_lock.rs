@@ -0,0 +1,239 @@
+use std::fs;
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long a lock file without an owner may be in the middle of being written by the process
+/// that just created it, it is stale after that
+const HALF_WRITTEN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Between reading a lock file without an owner again
+const HALF_WRITTEN_RECHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held while a game is launched through us, so a second launch doesn't start another game
+/// fighting over the screen and `Game.prefs`. Removed when dropped.
+pub struct LaunchLock {
+    path: PathBuf,
+    pid: u32,
+}
+
+/// The owner of a lock, read from the lock file
+#[derive(Debug, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// As written, RFC 3339
+    pub started_at: String,
+}
+
+pub enum LockError {
+    /// A process still running holds the lock
+    Held(LockOwner),
+    Io(io::Error),
+}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> LockError {
+        LockError::Io(e)
+    }
+}
+
+impl LaunchLock {
+    /// Takes the lock at `path` for this process. A lock of a process that doesn't run anymore,
+    /// e.g. after a crash, is removed first.
+    pub fn acquire(path: &Path) -> Result<LaunchLock, LockError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // once more if a stale lock was removed in between
+        let mut removed_stale = false;
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(mut file) => {
+                    let pid = process::id();
+                    file.write_all(format_owner(pid, SystemTime::now()).as_bytes())?;
+                    return Ok(LaunchLock {
+                        path: path.to_path_buf(),
+                        pid,
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let owner = fs::read_to_string(path)
+                        .ok()
+                        .and_then(|content| parse_owner(&content));
+                    match owner {
+                        Some(owner) if is_running(owner.pid) => return Err(LockError::Held(owner)),
+                        // another launch may have just created it and not written its owner yet
+                        None if is_recently_modified(path) => {
+                            thread::sleep(HALF_WRITTEN_RECHECK_INTERVAL);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    if removed_stale {
+                        return Err(io::Error::new(
+                            ErrorKind::AlreadyExists,
+                            "the lock file keeps being recreated",
+                        )
+                        .into());
+                    }
+                    // stale, or half written and the writer is gone
+                    remove_if_exists(path)?;
+                    removed_stale = true;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Lets a detached game hold the lock after we exited, it is stale once the game exited
+    pub fn hand_over(mut self, pid: u32) -> io::Result<()> {
+        fs::write(&self.path, format_owner(pid, SystemTime::now()))?;
+        self.pid = pid;
+        std::mem::forget(self);
+        Ok(())
+    }
+}
+
+impl Drop for LaunchLock {
+    fn drop(&mut self) {
+        // only our own lock, a stale one may have been taken over by another launch
+        let still_ours = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| parse_owner(&content))
+            .is_some_and(|owner| owner.pid == self.pid);
+        if still_ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Within `HALF_WRITTEN_GRACE_PERIOD`, false if it is gone or modified in the future
+fn is_recently_modified(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < HALF_WRITTEN_GRACE_PERIOD)
+}
+
+fn format_owner(pid: u32, started_at: SystemTime) -> String {
+    format!(
+        "{} {}\n",
+        pid,
+        humantime::format_rfc3339_seconds(started_at)
+    )
+}
+
+/// `<pid> <started at>`
+fn parse_owner(content: &str) -> Option<LockOwner> {
+    let mut parts = content.split_whitespace();
+    let pid = parts.next()?.parse().ok()?;
+    let started_at = parts.next()?.to_string();
+
+    Some(LockOwner { pid, started_at })
+}
+
+/// Whether a process with `pid` exists
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    // 0 and negative pids would address process groups
+    if pid == 0 || pid > i32::MAX as u32 {
+        return false;
+    }
+    // signal 0 only checks, a process of another user can't be signalled but is running
+    let signalled = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn is_running(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return false;
+        }
+        let mut exit_code = 0;
+        let running =
+            GetExitCodeProcess(process, &mut exit_code) != 0 && exit_code == STILL_ACTIVE as u32;
+        CloseHandle(process);
+        running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_stale_locks_but_not_held_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("launch.lock");
+
+        // above the highest pid of all systems
+        fs::write(&path, format!("{} 2024-01-01T10:00:00Z\n", i32::MAX)).unwrap();
+        let lock = LaunchLock::acquire(&path).ok().unwrap();
+        assert_eq!(
+            parse_owner(&fs::read_to_string(&path).unwrap())
+                .unwrap()
+                .pid,
+            process::id()
+        );
+
+        match LaunchLock::acquire(&path) {
+            Err(LockError::Held(owner)) => assert_eq!(owner.pid, process::id()),
+            _ => panic!("the lock of a running process was taken"),
+        }
+
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn waits_for_the_owner_of_a_new_lock_file_to_be_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("launch.lock");
+
+        fs::write(&path, "").unwrap();
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                fs::write(&path, format_owner(process::id(), SystemTime::now())).unwrap();
+            })
+        };
+        match LaunchLock::acquire(&path) {
+            Err(LockError::Held(owner)) => assert_eq!(owner.pid, process::id()),
+            _ => panic!("a lock file still being written was taken"),
+        }
+        writer.join().unwrap();
+
+        // left without an owner by a crashed launch
+        let file = fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - 2 * HALF_WRITTEN_GRACE_PERIOD)
+            .unwrap();
+        drop(file);
+        let lock = LaunchLock::acquire(&path).ok().unwrap();
+        drop(lock);
+        assert!(!path.exists());
+    }
+}
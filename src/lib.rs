@@ -0,0 +1,33 @@
+//! Detection and decoding of the replay formats used by Forged Alliance and FAForever.
+//!
+//! The `faf-replay-cli` binary is a thin launcher around this library, other tools can use it
+//! to get at the raw replay stream without going through the filesystem.
+
+extern crate base64;
+extern crate flate2;
+extern crate serde;
+extern crate serde_json;
+extern crate tempfile;
+extern crate zstd;
+
+mod body;
+mod error;
+mod header;
+mod metadata;
+mod replay;
+
+pub use body::{
+    count_replay_commands, parse_replay_commands, replay_duration, CommandCount, ReplayCommand,
+    ADVANCE_COMMAND, END_GAME_COMMAND, TICK_MILLIS,
+};
+pub use error::ReplayError;
+pub use header::{parse_replay_header, LuaValue, ReplayArmy, ReplayHeader, ReplayPlayer};
+pub use metadata::ReplayMetadata;
+pub use replay::{
+    convert_legacy_replay_stream_to_raw, convert_legacy_replay_stream_to_raw_in,
+    convert_legacy_replay_stream_to_raw_limited, convert_raw_replay_to_legacy_stream,
+    decode_legacy_replay, encode_legacy_replay, extract_faf_legacy_replay, get_replay_type,
+    get_replay_type_from_content, peek_replay_file, prepare_replay_file,
+    prepare_replay_file_with_options, qt_zlib_decode, PrepareOptions, PreparedReplay,
+    ReplayLocation, ReplayPeek, ReplayType, DEFAULT_MAX_SIZE,
+};
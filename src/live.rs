@@ -0,0 +1,211 @@
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::proxy::{connect_through, ProxyConfig};
+
+const LIVE_REPLAY_SCHEME: &str = "faflive://";
+pub const DEFAULT_LIVE_REPLAY_PORT: u16 = 15000;
+/// The live replay server of FAForever, the lobby server relays the streams of running games
+pub const DEFAULT_LIVE_REPLAY_HOST: &str = "lobby.faforever.com";
+
+/// A game in progress on a live replay server, as referenced by
+/// `faflive://lobby.faforever.com/<game id>/<player>.SCFAreplay`
+pub struct LiveReplaySource {
+    pub host: String,
+    pub port: u16,
+    pub game_id: u32,
+    /// `<game id>/<player>.SCFAreplay`, requested verbatim from the live replay server
+    pub path: String,
+}
+
+pub struct LiveReplayRelay {
+    pub game_id: u32,
+    /// The gpgnet url the game has to be launched with to connect to the relay
+    pub game_url: String,
+}
+
+/// The stream received from the live replay server so far
+#[derive(Default)]
+struct LiveBuffer {
+    state: Mutex<LiveBufferState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct LiveBufferState {
+    data: Vec<u8>,
+    remote_closed: bool,
+}
+
+pub fn is_live_replay_uri(replay_source: &str) -> bool {
+    replay_source.starts_with(LIVE_REPLAY_SCHEME)
+}
+
+/// The uri the FAF client uses to spectate the game of the player
+pub fn get_live_replay_uri(host: &str, game_id: u32, player: &str) -> String {
+    format!(
+        "{}{}/{}/{}.SCFAreplay",
+        LIVE_REPLAY_SCHEME, host, game_id, player
+    )
+}
+
+pub fn parse_live_replay_uri(uri: &str) -> Option<LiveReplaySource> {
+    let without_scheme = uri.strip_prefix(LIVE_REPLAY_SCHEME)?;
+    // the FAF client appends map and mod info as query, the server doesn't need it
+    let without_query = without_scheme.split('?').next()?;
+    let (authority, path) = without_query.split_once('/')?;
+
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, DEFAULT_LIVE_REPLAY_PORT),
+    };
+
+    let (game_id, file_name) = path.split_once('/')?;
+    if host.is_empty() || file_name.is_empty() {
+        return None;
+    }
+
+    Some(LiveReplaySource {
+        host: host.to_string(),
+        port,
+        game_id: game_id.parse().ok()?,
+        path: path.to_string(),
+    })
+}
+
+/// Opens a local listener on `port` (any free one if 0) the game can connect to and relays
+/// the stream of the live replay server through it, optionally writing a copy of the stream
+/// to `save_live`. The server is reached through a CONNECT tunnel if `proxy` applies to it.
+pub fn start_relay(
+    source: LiveReplaySource,
+    save_live: Option<&Path>,
+    port: u16,
+    proxy: &ProxyConfig,
+) -> io::Result<LiveReplayRelay> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let port = listener.local_addr()?.port();
+    let save_file = save_live.map(File::create).transpose()?;
+    let tunnel_proxy = proxy.for_tunnel(&source.host).map(str::to_string);
+
+    let relay = LiveReplayRelay {
+        game_id: source.game_id,
+        game_url: format!("gpgnet://127.0.0.1:{}/{}", port, source.path),
+    };
+
+    thread::spawn(move || {
+        if let Err(e) = relay_to_game(listener, source, tunnel_proxy, save_file) {
+            eprintln!("Live replay relay failed: {}", e);
+        }
+    });
+
+    Ok(relay)
+}
+
+fn relay_to_game(
+    listener: TcpListener,
+    source: LiveReplaySource,
+    tunnel_proxy: Option<String>,
+    save_file: Option<File>,
+) -> io::Result<()> {
+    let (mut game, _) = listener.accept()?;
+
+    // the game announces which replay it wants to read: G<path>\0
+    let request = read_request(&mut game)?;
+
+    let remote = match &tunnel_proxy {
+        Some(proxy) => connect_through(proxy, &source.host, source.port),
+        None => TcpStream::connect((source.host.as_str(), source.port)),
+    };
+    let mut remote = remote.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "Could not connect to live replay server {}:{}: {}",
+                source.host, source.port, e
+            ),
+        )
+    })?;
+    remote.write_all(&request)?;
+
+    let buffer = Arc::new(LiveBuffer::default());
+    let receiving_buffer = Arc::clone(&buffer);
+    thread::spawn(move || receive_from_remote(remote, &receiving_buffer, save_file));
+
+    serve_to_game(&mut game, &buffer)
+}
+
+/// Reads the request a game sends when connecting to a replay stream: G<path>\0
+pub fn read_request(game: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut byte = [0];
+
+    loop {
+        game.read_exact(&mut byte)?;
+        request.push(byte[0]);
+
+        if byte[0] == 0 {
+            return Ok(request);
+        }
+    }
+}
+
+fn receive_from_remote(mut remote: TcpStream, buffer: &LiveBuffer, mut save_file: Option<File>) {
+    let mut chunk = [0; 16 * 1024];
+
+    loop {
+        let read = match remote.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!(
+                    "Lost connection to the live replay server ({}), serving what was buffered",
+                    e
+                );
+                break;
+            }
+        };
+
+        if let Some(file) = &mut save_file {
+            if let Err(e) = file.write_all(&chunk[..read]) {
+                eprintln!("Could not save live replay, stop saving: {}", e);
+                save_file = None;
+            }
+        }
+
+        let mut state = buffer.state.lock().unwrap();
+        state.data.extend_from_slice(&chunk[..read]);
+        buffer.changed.notify_all();
+    }
+
+    buffer.state.lock().unwrap().remote_closed = true;
+    buffer.changed.notify_all();
+}
+
+fn serve_to_game(game: &mut TcpStream, buffer: &LiveBuffer) -> io::Result<()> {
+    let mut sent = 0;
+
+    loop {
+        let pending = {
+            let mut state = buffer.state.lock().unwrap();
+            while sent == state.data.len() && !state.remote_closed {
+                state = buffer.changed.wait(state).unwrap();
+            }
+
+            if sent == state.data.len() {
+                // the remote is gone and everything it sent has been delivered
+                return game.shutdown(std::net::Shutdown::Write);
+            }
+
+            state.data[sent..].to_vec()
+        };
+
+        game.write_all(&pending)?;
+        sent += pending.len();
+    }
+}
@@ -1,223 +1,4412 @@
-extern crate base64;
 extern crate clap;
-extern crate flate2;
+extern crate faf_replay_cli;
+#[cfg(unix)]
+extern crate libc;
+extern crate shell_words;
 extern crate tempfile;
+extern crate ureq;
+#[cfg(windows)]
+extern crate winreg;
 
-use std::fs::File;
+mod api;
+mod auth;
+mod bulk;
+mod cache;
+mod checksum;
+mod command_line;
+mod config;
+mod dir_watch;
+mod discovery;
+mod doctor;
+mod download;
+mod events;
+mod featured_mods;
+mod game_log;
+mod game_version;
+mod info;
+mod launch_lock;
+mod live;
+mod maps;
+mod paths;
+mod pe;
+mod progress;
+mod proton;
+mod proxy;
+#[cfg(windows)]
+mod registry;
+mod serve;
+mod sessions;
+mod steam;
+mod tee;
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt::Display;
+use std::fs;
 use std::io;
-use std::io::{BufRead, ErrorKind, Read, Write};
-use std::path::Path;
-use std::process::{exit, Command};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use clap::{App, Arg, ArgMatches};
-use flate2::read::ZlibDecoder;
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use faf_replay_cli::{
+    convert_legacy_replay_stream_to_raw_limited, count_replay_commands, encode_legacy_replay,
+    parse_replay_commands, parse_replay_header, peek_replay_file, prepare_replay_file_with_options,
+    replay_duration, PrepareOptions, PreparedReplay, ReplayError, ReplayLocation, ReplayMetadata,
+    ReplayType, DEFAULT_MAX_SIZE,
+};
 use tempfile::NamedTempFile;
 
-enum ReplayType {
-    Unknown,
-    /// The raw replay format created by the Forged Alliance binary
-    ForgedAlliance,
-    /// The legacy replay format from FAForever
-    /// (A json followed by a linebreak and then including the Qt-zipped base64-ed replay stream)
-    FafLegacy,
+use api::{FafApi, GameQuery, GameSearch};
+use auth::{Hydra, StoredLogin};
+use cache::{EntryCheck, ReplayCache};
+use config::{Config, ExecutableProfile};
+use download::{DownloadOptions, Downloader};
+use events::Event;
+use launch_lock::{LaunchLock, LockError};
+use maps::{MapCheck, MapsDir};
+use progress::{ByteProgress, Progress};
+use proxy::ProxyConfig;
+use sessions::{Session, SessionRecord};
+use tee::{Tee, TeeStream};
+
+/// Exit code of all failures that aren't replay errors, see `ReplayError::exit_code`.
+/// Like those it is above 200 to not be confused with the exit codes of the game.
+const GENERAL_EXIT_CODE: i32 = 201;
+/// Exit code if the game was stopped by `--timeout`
+const TIMEOUT_EXIT_CODE: i32 = 205;
+/// Exit code if another replay is watched already, see `LaunchLock`
+const ALREADY_RUNNING_EXIT_CODE: i32 = 206;
+/// How long a game stopped by `--timeout` or `stop` gets to exit before it is killed
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// `--launch-retries` only retries games failing this quickly, later failures aren't about the startup
+const LAUNCH_RETRY_WINDOW: Duration = Duration::from_secs(10);
+/// Gives the device that failed a moment before the game is launched again
+const LAUNCH_RETRY_DELAY: Duration = Duration::from_secs(3);
+/// How often `--timeout` and `stop` check whether the game exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Passed as `/replayid` if the id of the replay isn't known. The game only uses the id
+/// to name the replay, it has nothing to do with the port of a live replay (`--replay-port`).
+const UNKNOWN_REPLAY_ID: u32 = 0;
+
+/// Set by `--error-format json`
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// A game is launched and waited for, Ctrl+C stops it instead of exiting
+static GAME_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Ctrl+C presses while a game ran
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// From the first launch of a game until its extracted replay is removed, Ctrl+C while no game runs
+/// sets `INTERRUPTED` instead of exiting right away
+static LAUNCH_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Ctrl+C between the games of `--launch-retries` or after the game exited, checked by `exit_if_interrupted`
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Progress is drawn on stderr if it is a terminal, unless `--quiet` or json output is asked for
+static SHOW_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--offline` or `offline = true` in the config file
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--strict`, suspicious replays fail instead of being warned about
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// `--max-size`, how large a replay may decompress
+static MAX_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_MAX_SIZE);
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Set by `--temp-dir` or `$TMPDIR`
+static TEMP_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Legacy replays decompressing to less than this are done before a progress bar would show
+const LARGE_REPLAY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// A replay fetched from the network, either freshly downloaded or served from the cache
+enum FetchedReplay {
+    Cached(PathBuf),
+    Downloaded(NamedTempFile),
+}
+
+impl FetchedReplay {
+    fn path(&self) -> &Path {
+        match self {
+            FetchedReplay::Cached(path) => path,
+            FetchedReplay::Downloaded(f) => f.path(),
+        }
+    }
+}
+
+/// `--wrapper`, the game is started through it, e.g. `gamemoderun` or `wine`
+struct Wrapper {
+    /// A path or a command found in PATH
+    program: String,
+    /// Passed to the wrapper before the path of the executable
+    args: Vec<String>,
+}
+
+/// How the game ended
+#[derive(Clone, Copy)]
+struct GameExit {
+    status: ExitStatus,
+    /// Stopped by `--timeout`
+    timed_out: bool,
+}
+
+impl From<ExitStatus> for GameExit {
+    fn from(status: ExitStatus) -> GameExit {
+        GameExit {
+            status,
+            timed_out: false,
+        }
+    }
+}
+
+/// How the game is started, checked before anything is downloaded
+struct GameLaunch<'a> {
+    executable: PathBuf,
+    wrapper: Option<Wrapper>,
+    /// `--init`, relative to the directory of the executable. Chosen by the featured mod of the
+    /// replay if None, see `choose_init_file`.
+    init_file: Option<&'a str>,
+    /// `--featured-mod`, overrides the one in the metadata
+    featured_mod: Option<&'a str>,
+    /// Given after `--`, appended to the arguments of the game as they are
+    game_args: Vec<&'a str>,
+    /// `--ignore-game-exit-code`, exits with 0 even if the game failed
+    ignore_exit_code: bool,
+    /// `--dry-run`, prints the command instead of running it
+    dry_run: bool,
+    /// `--no-launch`, stops after preparing the replay
+    no_launch: bool,
+    /// `--keep-temp`, the extracted replay isn't removed after the game exited
+    keep_temp: bool,
+    /// `--detach`, exits right after launching instead of waiting for the game
+    detach: bool,
+    /// `env` of the config file and `--env`, set in that order so `--env` wins
+    env: Vec<(String, String)>,
+    /// `--env-clear`, the game gets only `env` and the `--env-pass` variables
+    env_clear: bool,
+    env_pass: Vec<&'a str>,
+    /// `--cwd`, the directory of the executable if None
+    working_dir: Option<&'a Path>,
+    /// `--log-file`, gets the output of the game besides the terminal
+    log_file: Option<&'a Path>,
+    /// `--tee`, gets the output of the game with timestamps
+    tee: Option<&'a Path>,
+    /// `--quiet`, the output of the game isn't shown
+    quiet: bool,
+    /// Not `--no-auto-log`, the game writes its log to the log directory
+    auto_log: bool,
+    /// Not `--no-log-analysis`, known problems in the game log are explained
+    log_analysis: bool,
+    /// `--timeout`, the game is stopped if it runs longer
+    timeout: Option<Duration>,
+    /// `--launch-retries`, how often a transient startup failure is retried
+    launch_retries: u32,
+    /// `--force`, launches even if another replay is watched already
+    force: bool,
+    /// `--keep-extracted`, a file or the directory legacy replays are extracted to and reused from
+    keep_extracted: Option<PathBuf>,
+    /// `--path-style windows`, by default with `--proton` or a wine wrapper:
+    /// paths passed to the game are translated to the `Z:` drive of wine
+    windows_paths: bool,
+    /// `--bug-report`, the game shows its bug report dialog when it crashes
+    bug_report: bool,
+    /// `--windowed` or `launch.windowed` of the config file, unless `--fullscreen`
+    windowed: bool,
+    /// The size given to `windowed`, otherwise the game picks it
+    window_size: Option<(u32, u32)>,
+    /// Unless `--skip-preflight`: the init file and game data are checked before launching
+    preflight: bool,
+    /// `--game-version`, the game version of the executable if it can't be read from it
+    game_version: Option<u32>,
+    /// `--strict-version`, replays of another game version than the executable aren't launched
+    strict_version: bool,
+    /// `--profile` or the default one of `[[executables]]` in the config file, `executable` is its path
+    profile: Option<&'static ExecutableProfile>,
+    /// Profiles of the config file used instead if they match the replay, none with `--executable` or `--profile`
+    profiles: &'static [ExecutableProfile],
 }
 
-enum ReplayLocation<'a> {
-    AtPath(&'a Path),
-    AtTempFile(NamedTempFile),
+/// Where the FAF client keeps the game it launches, for error messages
+const FAF_BIN_DIR_EXAMPLE: &str = "C:\\ProgramData\\FAForever\\bin or ~/.faforever/bin";
+
+/// Like `println!`, but on stderr with `--progress-format json` to keep stdout to the events
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if events::enabled() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
 }
 
-fn build_cli() -> ArgMatches<'static> {
+/// Shown by `--version` and `--version-string`
+const VERSION: &str = "0.1";
+
+/// All arguments and subcommands, also the source of `completions`
+fn build_cli() -> App<'static, 'static> {
     App::new("faf-replay-cli")
         .about("A replay launcher for FAForever")
-        .version("0.1")
+        .version(VERSION)
         .author("Brutus5000 <Brutus5000@gmx.net>")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("executable")
                 .long("executable")
                 .short("e")
                 .value_name("PATH TO ForgedAlliance.exe")
-                .help("Path to the ForgedAlliance.exe")
+                .help("Path to the ForgedAlliance.exe [default: the one of the config file, else found in the FAF client and Steam directories and stored in the config file]")
                 .takes_value(true)
-                .required(true),
+                .env("FAF_REPLAY_EXECUTABLE"),
+        )
+        .arg(
+            Arg::with_name("skip-preflight")
+                .long("skip-preflight")
+                .help("Launches without checking that the init file and the game data (gamedata/*.scd) exist"),
+        )
+        .arg(
+            Arg::with_name("skip-exe-check")
+                .long("skip-exe-check")
+                .help("Doesn't check that the executable is the Windows executable of the game, e.g. for a script starting it"),
+        )
+        .arg(
+            Arg::with_name("game-version")
+                .long("game-version")
+                .value_name("VERSION")
+                .help("Game version of the executable, e.g. 3701, compared to the one of the replay [default: read from a version file next to the executable or its version resource]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strict-version")
+                .long("strict-version")
+                .help("Doesn't launch replays of another game version than the executable, they would desync"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Launches with the executable of this [[executables]] profile of the config file [default: the first one matching the replay, see profiles list]")
+                .takes_value(true)
+                .conflicts_with_all(&["executable", "executable-from-steam"]),
+        )
+        .arg(
+            Arg::with_name("executable-from-steam")
+                .long("executable-from-steam")
+                .help("Uses the Forged Alliance installed with Steam, found in its library folders, instead of --executable")
+                .conflicts_with("executable"),
         )
         .arg(
             Arg::with_name("local-file")
                 .long("local-file")
                 .short("f")
                 .value_name("FILE")
-                .help("Path to the replay file you want to watch")
+                .help("Path, url, vault id or faflive:// uri of the replay you want to watch")
+                .takes_value(true)
+                .required_unless_one(&["replay", "watch-dir", "print-detected-paths"]),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .value_name("REPLAY")
+                .help("The replay like --local-file, e.g. faf-replay-cli 1234.fafreplay")
+                .index(1)
+                .conflicts_with_all(&["local-file", "watch-dir"]),
+        )
+        .arg(
+            Arg::with_name("print-temp-path")
+                .long("print-temp-path")
+                .help("Only extracts the replay and prints the path of the raw replay, which is kept, instead of launching it")
+                .conflicts_with("watch-dir"),
+        )
+        .arg(
+            Arg::with_name("progress-format")
+                .long("progress-format")
+                .value_name("FORMAT")
+                .help("json prints what is going on as one JSON event per line on stdout for frontends, e.g. {\"event\":\"launching\",\"pid\":1234}, our messages and the output of the game go to stderr then")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .conflicts_with("print-temp-path"),
+        )
+        .arg(
+            Arg::with_name("version-string")
+                .long("version-string")
+                .help("Prints the version of faf-replay-cli and the format and version of the replay in one line, e.g. for bug reports")
+                .conflicts_with_all(&["watch-dir", "print-temp-path"]),
+        )
+        .arg(
+            Arg::with_name("print-detected-paths")
+                .long("print-detected-paths")
+                .help("Prints everywhere the game is looked for without --executable and what is found there"),
+        )
+        .arg(
+            Arg::with_name("watch-dir")
+                .long("watch-dir")
+                .value_name("DIR")
+                .help("Watches the directory and launches every new replay file in it")
+                .takes_value(true)
+                .conflicts_with("local-file"),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .value_name("DIR")
+                .help("Directory for downloaded replays [default: $XDG_CACHE_HOME/faf-replay-cli/replays]")
+                .takes_value(true)
+                .env("FAF_REPLAY_CACHE_DIR")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .value_name("N")
+                .help("How often failed downloads are retried (connection errors, timeouts and 5xx)")
+                .takes_value(true)
+                .default_value("3")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("proxy")
+                .long("proxy")
+                .value_name("URL")
+                .help("http://[user:password@]host[:port] proxy for all network access, overrides HTTP_PROXY, HTTPS_PROXY and NO_PROXY")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .help("Forbids all network access, everything needing it fails right away")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Fails on suspicious replays instead of warning, e.g. on trailing data after the replay stream")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("max-size")
+                .long("max-size")
+                .value_name("BYTES")
+                .help("Fails on replays decompressing to more bytes, guards against decompression bombs [default: 536870912 (512 MiB)]")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Config file with defaults for options [default: $XDG_CONFIG_HOME/faf-replay-cli/config.toml]")
+                .takes_value(true)
+                .env("FAF_REPLAY_CONFIG")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("temp-dir")
+                .long("temp-dir")
+                .value_name("DIR")
+                .help("Directory for extracted and downloaded replays, e.g. if the system temp dir is a small tmpfs [default: $TMPDIR]")
+                .takes_value(true)
+                .env("FAF_REPLAY_TEMP_DIR")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .long("no-cache")
+                .help("Neither reads nor stores downloaded replays in the cache")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("refresh")
+                .long("refresh")
+                .help("Downloads replays again even if they are cached")
+                .conflicts_with("no-cache")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("no-resume")
+                .long("no-resume")
+                .help("Downloads replays from scratch instead of resuming interrupted downloads"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .short("q")
+                .help("Suppresses progress output and the output of the game, our messages and errors are still shown")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .help("Prints debug output")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("error-format")
+                .long("error-format")
+                .value_name("FORMAT")
+                .help("Format of error messages on stderr, json prints {\"error\", \"message\", \"exit_code\"}")
+                .takes_value(true)
+                .possible_values(&["human", "json"])
+                .default_value("human")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("save-live")
+                .long("save-live")
+                .value_name("PATH")
+                .help("Saves the stream of a watched live replay as a replay file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("maps-dir")
+                .long("maps-dir")
+                .value_name("DIR")
+                .help("Directory of the installed custom maps [default: My Games/Gas Powered Games/Supreme Commander Forged Alliance/Maps in the documents]")
+                .takes_value(true)
+                .env("FAF_REPLAY_MAPS_DIR"),
+        )
+        .arg(
+            Arg::with_name("no-map-download")
+                .long("no-map-download")
+                .help("Only warns about a missing map instead of downloading it before launching"),
+        )
+        .arg(
+            Arg::with_name("content-base-url")
+                .long("content-base-url")
+                .value_name("URL")
+                .help("Base url of the FAF content server missing maps are downloaded from")
+                .takes_value(true)
+                .env("FAF_REPLAY_CONTENT_BASE_URL")
+                .default_value(maps::DEFAULT_CONTENT_BASE_URL),
+        )
+        .arg(
+            Arg::with_name("replay-id")
+                .long("replay-id")
+                .value_name("N")
+                .help("Id the game is launched with (/replayid) [default: the vault id, the uid in the metadata or the number the file name starts with]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("replay-port")
+                .long("replay-port")
+                .value_name("PORT")
+                .help("Local port the game connects to for watching a live replay [default: any free port]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("vault-base-url")
+                .long("vault-base-url")
+                .value_name("URL")
+                .help("Base url of the replay vault used to download replays by id")
+                .takes_value(true)
+                .env("FAF_REPLAY_VAULT_BASE_URL")
+                .default_value(download::DEFAULT_VAULT_BASE_URL),
+        )
+        .arg(
+            Arg::with_name("api-base-url")
+                .long("api-base-url")
+                .value_name("URL")
+                .help("Base url of the FAF API used to look up games")
+                .takes_value(true)
+                .env("FAF_REPLAY_API_BASE_URL")
+                .default_value(api::DEFAULT_API_BASE_URL)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("api-token")
+                .long("api-token")
+                .value_name("TOKEN")
+                .help("OAuth access token sent to the FAF API, some games are only found with it [default: the token of login]")
+                .takes_value(true)
+                .env("FAF_API_TOKEN")
+                .hide_env_values(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("init")
+                .long("init")
+                .value_name("FILE")
+                .help("Init file the game is started with, e.g. init_faf.lua, relative to the directory of the executable [default: the one of the featured mod of the replay]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("featured-mod")
+                .long("featured-mod")
+                .value_name("MOD")
+                .help("Featured mod passed to the game (/featuredmod), also picks the init file [default: the one of the replay, faf if unknown]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("game-args")
+                .value_name("GAME ARGS")
+                .help("Extra arguments for the game after --, e.g. -- /log game.log /nomovie")
+                .multiple(true)
+                .last(true),
+        )
+        .arg(
+            Arg::with_name("ignore-game-exit-code")
+                .long("ignore-game-exit-code")
+                .help("Exits with 0 after the game exited, instead of the exit code of the game"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Prepares everything but only prints the command the game would be launched with"),
+        )
+        .arg(
+            Arg::with_name("no-launch")
+                .long("no-launch")
+                .help("Prepares the replay like for launching it and prints its path, but doesn't start the game, e.g. to check in CI that a replay can be decoded")
+                .conflicts_with_all(&["dry-run", "detach"]),
+        )
+        .arg(
+            Arg::with_name("keep-temp")
+                .long("keep-temp")
+                .help("Keeps the extracted replay after the game exited, e.g. to run the command of --dry-run by hand"),
+        )
+        .arg(
+            Arg::with_name("keep-extracted")
+                .long("keep-extracted")
+                .value_name("PATH")
+                .help("Extracts legacy replays to PATH instead of a temp file, a directory keeps them as <uid>.scfareplay to reuse them next time [default: the cache directory]")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1),
+        )
+        .arg(
+            Arg::with_name("cwd")
+                .long("cwd")
+                .value_name("DIR")
+                .help("Working directory of the game, e.g. the installation root for Proton [default: the directory of the executable]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .value_name("KEY=VALUE")
+                .help("Sets an environment variable of the game, e.g. DRI_PRIME=1, can be given several times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("env-clear")
+                .long("env-clear")
+                .help("Starts the game with an empty environment besides --env and --env-pass, e.g. to reproduce how the client launches it"),
+        )
+        .arg(
+            Arg::with_name("env-pass")
+                .long("env-pass")
+                .value_name("NAME")
+                .help("Passes the environment variable on to the game despite --env-clear, can be given several times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("env-clear"),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .long("log-file")
+                .value_name("FILE")
+                .help("Also writes the output of the game to FILE, e.g. to look into crashes afterwards")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tee")
+                .long("tee")
+                .value_name("FILE")
+                .help("Writes the output of the game to FILE line by line with the time of each line, also with --quiet")
+                .takes_value(true)
+                .conflicts_with("detach"),
+        )
+        .arg(
+            Arg::with_name("no-auto-log")
+                .long("no-auto-log")
+                .help("Doesn't let the game write its log (/log) to the log directory in the cache directory, which is shown if the game fails"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .help("Stops the game if it runs longer and exits with 205, e.g. to check in CI that a replay loads")
                 .takes_value(true)
-                .required(true),
+                .conflicts_with("detach"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Launches the game even if another replay is watched already"),
+        )
+        .arg(
+            Arg::with_name("no-log-analysis")
+                .long("no-log-analysis")
+                .help("Doesn't explain known problems found in the game log, like a missing map or mod, after the game exited"),
+        )
+        .arg(
+            Arg::with_name("detach")
+                .long("detach")
+                .help("Exits right after launching the game instead of waiting for it, extracted replays are kept in the cache directory"),
         )
         .arg(
             Arg::with_name("wrapper")
                 .long("wrapper")
                 .short("w")
                 .value_name("WRAPPER")
-                .help("Path to the wrapper script (usually for Linux)")
+                .help("Path or command of the wrapper the game is started with (usually for Linux), with arguments quoted like in a shell, e.g. \"gamemoderun mangohud --dlsym\"")
                 .takes_value(true)
+                .env("FAF_REPLAY_WRAPPER")
                 .required(false),
         )
-        .get_matches()
+        .arg(
+            Arg::with_name("windowed")
+                .long("windowed")
+                .value_name("WxH")
+                .help("Launches the game in a window, e.g. --windowed=1280x720 [default: launch.windowed of the config file]")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                // a replay after it isn't taken for the size
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("fullscreen")
+                .long("fullscreen")
+                .help("Launches the game with its own display settings, even if launch.windowed is in the config file")
+                .conflicts_with("windowed"),
+        )
+        .arg(
+            Arg::with_name("launch-retries")
+                .long("launch-retries")
+                .value_name("N")
+                .help("Relaunches the game up to N times if it exits within seconds with a transient startup failure in its log, e.g. of the sound or graphics device under wine (more in launch.retry_patterns of the config file)")
+                .takes_value(true)
+                .conflicts_with_all(&["detach", "no-auto-log"]),
+        )
+        .arg(
+            Arg::with_name("seek")
+                .long("seek")
+                .value_name("TICK")
+                .help("Would start the replay at TICK (10 per second of game time), the game has no switch for it: the replay starts at the beginning with a warning")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bug-report")
+                .long("bug-report")
+                .help("Lets the game show its bug report dialog with the crash log when it crashes, it's suppressed with /nobugreport otherwise"),
+        )
+        .arg(
+            Arg::with_name("proton")
+                .long("proton")
+                .value_name("PROTON_DIR")
+                .help("Starts the game with proton run instead of a --wrapper, the replay is passed as Z:\\ path [default: the newest Proton in the Steam libraries]")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .conflicts_with("wrapper"),
+        )
+        .arg(
+            Arg::with_name("wine-prefix")
+                .long("wine-prefix")
+                .value_name("DIR")
+                .help("Wine prefix the game runs in (WINEPREFIX) [default: the one of the environment, usually ~/.wine]")
+                .takes_value(true)
+                .conflicts_with("proton"),
+        )
+        .arg(
+            Arg::with_name("path-style")
+                .long("path-style")
+                .value_name("STYLE")
+                .help("How paths are passed to the game, windows ones as Z:\\ path for wine [default: windows with --proton or a wine --wrapper, else unix]")
+                .takes_value(true)
+                .possible_values(&["windows", "unix"]),
+        )
+        .arg(
+            Arg::with_name("compat-data")
+                .long("compat-data")
+                .value_name("DIR")
+                .help("Proton prefix of the game (STEAM_COMPAT_DATA_PATH) [default: the one Steam created for Forged Alliance]")
+                .takes_value(true)
+                .requires("proton"),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Converts a replay file into another replay format")
+                .arg(
+                    Arg::with_name("local-file")
+                        .value_name("FILE")
+                        .help("Path to the replay file you want to convert")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Path of the converted replay file")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output-format")
+                        .long("output-format")
+                        .value_name("FORMAT")
+                        .help("Format of the converted replay file")
+                        .takes_value(true)
+                        .possible_values(&["raw", "fafreplay"])
+                        .default_value("raw"),
+                )
+                .arg(
+                    Arg::with_name("include-metadata")
+                        .long("include-metadata")
+                        .value_name("PATH")
+                        .help("Also writes the json metadata of a legacy replay to PATH, e.g. 1234.json next to the raw replay")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("decode-base64")
+                .about("Decodes the base64 replay stream of a .fafreplay (its second line) into a raw replay file")
+                .arg(
+                    Arg::with_name("stream")
+                        .value_name("STREAM")
+                        .help("The base64 stream, - reads it from stdin [default: -]")
+                        .conflicts_with("from-file"),
+                )
+                .arg(
+                    Arg::with_name("from-file")
+                        .long("from-file")
+                        .value_name("FILE")
+                        .help("Reads the base64 stream from FILE instead")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Path of the raw replay file")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Shows the map, players and metadata of a replay file")
+                .arg(
+                    Arg::with_name("local-file")
+                        .value_name("FILE")
+                        .help("Path to the replay file you want to inspect")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("online")
+                        .long("online")
+                        .help("Also looks the game up in the FAF API, e.g. for ratings and the official title"),
+                )
+                .arg(
+                    Arg::with_name("peek")
+                        .long("peek")
+                        .help("Only prints the decompressed size and the first header line, e.g. to spot truncated replays")
+                        .conflicts_with("online"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output of the replay info, kv (key=value lines) and csv have a fixed field order for scripts [default: text]")
+                        .takes_value(true)
+                        .possible_values(&["text", "json", "kv", "csv"])
+                        .conflicts_with_all(&["online", "peek"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Streams a replay file to connecting games like a live replay server")
+                .arg(
+                    Arg::with_name("local-file")
+                        .value_name("FILE")
+                        .help("Path to the replay file you want to serve")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .help("Port the games connect to")
+                        .takes_value(true)
+                        .default_value("15000"),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .long("rate")
+                        .value_name("RATE")
+                        .help("Playback speed, e.g. 2x or 0.5x")
+                        .takes_value(true)
+                        .default_value("1x"),
+                )
+                .arg(
+                    Arg::with_name("burst")
+                        .long("burst")
+                        .help("Sends the whole replay at once instead of pacing it")
+                        .conflicts_with("rate"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Searches the FAF API for games of a player, latest first")
+                .arg(
+                    Arg::with_name("player")
+                        .long("player")
+                        .value_name("NAME")
+                        .help("Login of a player of the game")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("map")
+                        .long("map")
+                        .value_name("NAME")
+                        .help("Display name of the map, e.g. \"Seton's Clutch\"")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("mod")
+                        .long("mod")
+                        .value_name("NAME")
+                        .help("Technical name of the featured mod, e.g. faf or ladder1v1")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .long("since")
+                        .value_name("DATE")
+                        .help("Only games started on or after this day, e.g. 2024-01-01")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .value_name("N")
+                        .help("Number of games per page")
+                        .takes_value(true)
+                        .default_value("20"),
+                )
+                .arg(
+                    Arg::with_name("page")
+                        .long("page")
+                        .value_name("N")
+                        .help("Page of the results to show")
+                        .takes_value(true)
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Prints the games as json"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .value_name("N")
+                        .help("Downloads and watches the Nth game of the results (needs --executable)")
+                        .takes_value(true)
+                        .conflicts_with("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("download-latest")
+                .about("Downloads and watches the most recent finished game of a player")
+                .arg(
+                    Arg::with_name("player")
+                        .long("player")
+                        .value_name("NAME")
+                        .help("Exact login of the player")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("no-launch")
+                        .long("no-launch")
+                        .help("Only downloads the replay and prints where it is saved"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Also saves the downloaded replay at this path")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("live")
+                        .long("live")
+                        .help("Spectates the latest game instead if it is still in progress")
+                        .conflicts_with("no-launch"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("download")
+                .about("Downloads many vault replays into a directory without launching them, e.g. for datasets")
+                .arg(
+                    Arg::with_name("ids")
+                        .value_name("REPLAY ID")
+                        .help("Ids of the replays to download")
+                        .multiple(true)
+                        .required_unless("ids-file"),
+                )
+                .arg(
+                    Arg::with_name("ids-file")
+                        .long("ids-file")
+                        .value_name("FILE")
+                        .help("File with one replay id per line, # starts a comment")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("DIR")
+                        .help("Directory the replays are saved in, replays already there are skipped")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .long("concurrency")
+                        .value_name("N")
+                        .help("How many replays are downloaded at the same time")
+                        .takes_value(true)
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::with_name("delay")
+                        .long("delay")
+                        .value_name("DURATION")
+                        .help("Minimum time between two requests to the vault, e.g. 500ms or 2s")
+                        .takes_value(true)
+                        .default_value("500ms"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("profiles")
+                .about("Executable profiles of the config file ([[executables]])")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists the profiles with their rules, in the order they are matched"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Checks the executable, wrapper, temp dir and replay server, e.g. faf-replay-cli -e ... doctor"),
+        )
+        .subcommand(
+            SubCommand::with_name("login")
+                .about("Logs in to FAF in the browser, the API is then queried as that user")
+                .arg(
+                    Arg::with_name("hydra-base-url")
+                        .long("hydra-base-url")
+                        .value_name("URL")
+                        .help("Base url of the FAF OAuth server")
+                        .takes_value(true)
+                        .default_value(auth::DEFAULT_HYDRA_BASE_URL),
+                )
+                .arg(
+                    Arg::with_name("client-id")
+                        .long("client-id")
+                        .value_name("ID")
+                        .help("OAuth client id the login is requested for")
+                        .takes_value(true)
+                        .default_value(auth::DEFAULT_CLIENT_ID),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("Manages the cache of downloaded replays")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("ls").about("Lists all cached replays"))
+                .subcommand(
+                    SubCommand::with_name("clean")
+                        .about("Removes cached replays older than the given age")
+                        .arg(
+                            Arg::with_name("older-than")
+                                .long("older-than")
+                                .value_name("AGE")
+                                .help("Maximum age of kept replays, e.g. 30d, 12h or 90m")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(SubCommand::with_name("clear").about("Removes all cached replays"))
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Checks cached replays against their stored checksums and removes corrupt ones"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stop")
+                .about("Stops a game launched by faf-replay-cli, e.g. a detached one")
+                .arg(
+                    Arg::with_name("pid")
+                        .value_name("PID")
+                        .help("The game to stop, as shown by status, needed if several run")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Stops all games launched by faf-replay-cli")
+                        .conflicts_with("pid"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Lists the running games launched by faf-replay-cli"),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Prints the completion script for SHELL, e.g. faf-replay-cli completions bash > /etc/bash_completion.d/faf-replay-cli")
+                .setting(AppSettings::Hidden)
+                .arg(
+                    Arg::with_name("shell")
+                        .value_name("SHELL")
+                        .possible_values(&Shell::variants())
+                        .required(true),
+                ),
+        )
 }
 
-fn get_executable_path<'a>(args: &'a ArgMatches) -> &'a Path {
-    let executable_str = args.value_of("executable").unwrap();
-    let executable_path = Path::new(executable_str);
+fn get_executable_path(args: &ArgMatches) -> PathBuf {
+    if args.is_present("executable-from-steam") {
+        return find_steam_executable();
+    }
 
-    if !executable_path.exists() {
-        eprintln!("No executable found at {}", executable_str);
-        exit(1)
+    let executable_str = match args.value_of("executable") {
+        Some(executable_str) => executable_str,
+        None => return get_configured_executable().unwrap_or_else(|| discover_executable(args)),
+    };
+    let executable_path = Path::new(executable_str);
+    // a bare name not in the working directory is looked up in PATH like the shell does, e.g. a launcher script
+    if executable_path.components().count() == 1 && !executable_path.is_file() {
+        return command_line::find_in_path(executable_str).unwrap_or_else(|| {
+            let search_path = env::var_os("PATH").unwrap_or_default();
+            let dirs: Vec<String> = env::split_paths(&search_path)
+                .map(|dir| dir.display().to_string())
+                .collect();
+            fail(
+                "FileNotFound",
+                format!(
+                    "No executable {} found, neither in the working directory {} nor in PATH ({})",
+                    executable_str,
+                    env::current_dir().unwrap_or_default().display(),
+                    dirs.join(", ")
+                ),
+                GENERAL_EXIT_CODE,
+            )
+        });
     }
 
-    executable_path
-}
+    // the game only needs to be launchable, not readable by us
+    check_file_access(executable_path, "executable", fs::metadata(executable_path));
 
-fn get_replay_path<'a>(args: &'a ArgMatches) -> &'a Path {
-    let replay_str = args.value_of("local-file").unwrap();
-    let replay_path = Path::new(replay_str);
+    executable_path.to_path_buf()
+}
 
-    if !replay_path.exists() {
-        eprintln!("No replay file found at {}", replay_str);
-        exit(1)
+/// The executable stored in the config file, unless it was removed since
+fn get_configured_executable() -> Option<PathBuf> {
+    let executable = CONFIG.get()?.executable.as_ref()?;
+    if executable.is_file() {
+        return Some(executable.clone());
     }
 
-    replay_path
+    eprintln!(
+        "Warning: The executable {} of the config file doesn't exist anymore, looking for the game",
+        executable.display()
+    );
+    None
 }
 
-fn get_wrapper_path<'a>(args: &'a ArgMatches) -> Option<&'a Path> {
-    args.value_of("wrapper").map(|wrapper_str| {
-        let wrapper_path = Path::new(wrapper_str);
-
-        if !wrapper_path.exists() {
-            eprintln!("No wrapper file found at {}", wrapper_str);
-            exit(1)
-        }
+/// Looks for the game in `discovery::candidates` and stores it in the config file
+/// to not look for it again
+fn discover_executable(args: &ArgMatches) -> PathBuf {
+    let candidates = discovery::candidates();
+    let executable = discovery::find_executable(&candidates).unwrap_or_else(|| {
+        fail(
+            "NotFound",
+            format!(
+                "Forged Alliance not found in {}, use --executable <PATH TO ForgedAlliance.exe>",
+                join_candidates(&candidates)
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    });
 
-        wrapper_path
-    })
+    match get_config_path(args) {
+        Some(config_path) => match Config::store_executable(&config_path, &executable) {
+            Ok(()) => eprintln!(
+                "Using the game found at {}, stored in {}",
+                executable.display(),
+                config_path.display()
+            ),
+            Err(e) => eprintln!(
+                "Using the game found at {}\nWarning: Could not store it in {}: {}",
+                executable.display(),
+                config_path.display(),
+                e
+            ),
+        },
+        None => eprintln!("Using the game found at {}", executable.display()),
+    }
+    executable
 }
 
-fn main() {
-    let matches = build_cli();
+/// Exits if the executable can't be the game, which would fail in some confusing way.
+/// Only warns if it is another executable, e.g. a renamed or patched one.
+fn check_game_executable(executable: &Path) {
+    // e.g. ForgedAlliance.exe linking to the launcher of the FAF client
+    let description = match fs::canonicalize(executable) {
+        Ok(target) if target != executable => {
+            format!("{} (a link to {})", executable.display(), target.display())
+        }
+        _ => executable.display().to_string(),
+    };
 
-    let executable = get_executable_path(&matches);
-    let replay_path = get_replay_path(&matches);
-    let wrapper = get_wrapper_path(&matches);
+    let problem = match pe::inspect(executable) {
+        Ok(pe::ExeKind::ForgedAlliance) => return,
+        Ok(pe::ExeKind::OtherExecutable) => {
+            eprintln!(
+                "Warning: {} doesn't look like ForgedAlliance.exe, launching it anyway",
+                description
+            );
+            return;
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: Could not check the executable {}: {}",
+                description, e
+            );
+            return;
+        }
+        Ok(pe::ExeKind::Shortcut) => {
+            "is a Windows shortcut, use the ForgedAlliance.exe it points to"
+        }
+        Ok(pe::ExeKind::NotExecutable) => {
+            "is no Windows executable, use the ForgedAlliance.exe of the game"
+        }
+    };
+    fail(
+        "InvalidArgument",
+        format!(
+            "The executable {} {} (or --skip-exe-check)",
+            description, problem
+        ),
+        GENERAL_EXIT_CODE,
+    )
+}
 
-    let replay_preparation_result = prepare_replay_file(replay_path).expect("Replay file issues!");
+/// `--executable-from-steam`, the only Forged Alliance installation in the Steam libraries
+fn find_steam_executable() -> PathBuf {
+    let steam_dirs = steam::default_steam_dirs();
+    if steam_dirs.is_empty() {
+        fail(
+            "NotFound",
+            "No Steam installation found, use --executable <PATH TO ForgedAlliance.exe>",
+            GENERAL_EXIT_CODE,
+        )
+    }
+    let libraries = steam::find_library_folders(&steam_dirs);
+    let mut executables = steam::find_executables(&libraries);
 
-    let raw_replay_path = match &replay_preparation_result {
-        ReplayLocation::AtPath(path) => path,
-        ReplayLocation::AtTempFile(f) => f.path(),
+    match executables.len() {
+        1 => executables.remove(0),
+        0 => fail(
+            "NotFound",
+            format!(
+                "Forged Alliance is not installed in the Steam libraries {}, use --executable <PATH TO ForgedAlliance.exe>",
+                join_paths(&libraries)
+            ),
+            GENERAL_EXIT_CODE,
+        ),
+        _ => fail(
+            "InvalidArgument",
+            format!(
+                "Forged Alliance is installed several times ({}), pick one with --executable",
+                join_paths(&executables)
+            ),
+            GENERAL_EXIT_CODE,
+        ),
     }
-        .to_str()
-        .unwrap();
+}
 
-    launch_game(executable, &raw_replay_path, 12345, wrapper);
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-fn get_replay_type(file_name: &str) -> ReplayType {
-    match file_name {
-        _ if file_name.ends_with(".scfareplay") => ReplayType::ForgedAlliance,
-        _ if file_name.ends_with(".fafreplay") => ReplayType::FafLegacy,
-        _ => ReplayType::Unknown,
-    }
+fn join_candidates(candidates: &[discovery::Candidate]) -> String {
+    let bin_dirs: Vec<PathBuf> = candidates
+        .iter()
+        .map(|candidate| candidate.bin_dir.clone())
+        .collect();
+    join_paths(&bin_dirs)
 }
 
-fn prepare_replay_file(replay_path: &Path) -> io::Result<ReplayLocation> {
-    let file_name = replay_path.to_str().unwrap();
+/// `--print-detected-paths`, every place the game is looked for and what is found there
+fn print_detected_paths() {
+    let mut chosen = false;
+    let mut print = |source: &str, location: &Path, executable: Option<&Path>| {
+        let detail = match executable {
+            Some(executable) if !chosen => {
+                chosen = true;
+                format!("{} (used without --executable)", executable.display())
+            }
+            Some(executable) => executable.display().to_string(),
+            None => format!("{}, not found", location.display()),
+        };
+        println!("{}: {}", source, detail);
+    };
 
-    match get_replay_type(file_name) {
-        ReplayType::Unknown => Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Unknown replay format!",
-        )),
-        ReplayType::ForgedAlliance => Ok(ReplayLocation::AtPath(replay_path)),
-        ReplayType::FafLegacy => {
-            extract_faf_legacy_replay(file_name).map(ReplayLocation::AtTempFile)
-        }
+    if let Some(executable) = CONFIG.get().and_then(|config| config.executable.as_deref()) {
+        let found = Some(executable).filter(|executable| executable.is_file());
+        print("Config file", executable, found);
+    }
+    for candidate in discovery::candidates() {
+        let executable = steam::find_executable_in(&candidate.bin_dir);
+        print(&candidate.source, &candidate.bin_dir, executable.as_deref());
     }
 }
 
-fn extract_faf_legacy_replay(file_name: &str) -> io::Result<NamedTempFile> {
-    let file = File::open(file_name)?;
-
-    let mut lines = io::BufReader::new(file).lines();
+fn get_game_launch<'a>(args: &'a ArgMatches) -> GameLaunch<'a> {
+    let all_profiles = CONFIG
+        .get()
+        .map_or(&[][..], |config| config.executables.as_slice());
+    let explicit_executable =
+        args.is_present("executable") || args.is_present("executable-from-steam");
+    let profile = match args.value_of("profile") {
+        Some(name) => Some(find_profile(all_profiles, name)),
+        None if explicit_executable => None,
+        None => all_profiles.iter().find(|profile| profile.is_default()),
+    };
+    let profiles = if args.is_present("profile") || explicit_executable {
+        &[][..]
+    } else {
+        all_profiles
+    };
 
-    let _json_metadata = lines.next().unwrap_or_else(|| {
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Replay corrupt - replay metadata json is missing",
-        ))
-    })?;
-
-    let base64_replay_stream = lines.next().unwrap_or_else(|| {
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Replay corrupt - binary replay stream is missing",
-        ))
-    })?;
+    let executable = match profile {
+        Some(profile) => {
+            check_file_access(&profile.path, "executable", fs::metadata(&profile.path));
+            profile.path.clone()
+        }
+        None => get_executable_path(args),
+    };
+    if !args.is_present("skip-exe-check") {
+        check_game_executable(&executable);
+    }
+    let (wrapper, mut env) = match get_proton_launch(args) {
+        Some((proton, proton_env)) => (Some(proton), proton_env),
+        None => (get_wrapper(args), Vec::new()),
+    };
+    if let Some(wine_prefix) = args.value_of_os("wine-prefix") {
+        // wine refuses relative prefixes
+        let wine_prefix = Path::new(wine_prefix);
+        let absolute_wine_prefix = get_absolute_path(wine_prefix).unwrap_or_else(|| {
+            fail(
+                "InvalidArgument",
+                format!(
+                    "The wine prefix {} is not valid UTF-8",
+                    wine_prefix.display()
+                ),
+                GENERAL_EXIT_CODE,
+            )
+        });
+        env.push(("WINEPREFIX".to_string(), absolute_wine_prefix));
+    }
+    // after the Proton variables and WINEPREFIX, so they can be overridden
+    env.extend(get_game_env(args));
+    let windows_paths = match args.value_of("path-style") {
+        Some(path_style) => path_style == "windows",
+        None => {
+            args.is_present("proton")
+                || wrapper.as_ref().is_some_and(|wrapper| {
+                    let words = std::iter::once(&wrapper.program).chain(&wrapper.args);
+                    command_line::is_wine_command(words.map(String::as_str))
+                })
+        }
+    };
+    let init_file = args.value_of("init");
+    let (windowed, window_size) = get_windowed(args);
+    warn_about_wrapper(&executable, wrapper.as_ref());
+    // a wrapper like wine only reads the executable
+    if wrapper.is_none() {
+        check_launchable(&executable, "executable");
+    }
+    warn_about_seek(args);
 
-    let tempfile = convert_legacy_replay_stream_to_raw(&base64_replay_stream)?;
+    if let Some(init_file) = init_file.or(profile.and_then(|profile| profile.init.as_deref())) {
+        check_init_file(&executable, init_file);
+    }
 
-    Ok(tempfile)
+    GameLaunch {
+        executable,
+        wrapper,
+        init_file,
+        featured_mod: args.value_of("featured-mod"),
+        game_args: args.values_of("game-args").into_iter().flatten().collect(),
+        ignore_exit_code: args.is_present("ignore-game-exit-code"),
+        dry_run: args.is_present("dry-run"),
+        no_launch: args.is_present("no-launch"),
+        keep_temp: args.is_present("keep-temp"),
+        detach: args.is_present("detach"),
+        keep_extracted: get_keep_extracted_path(args),
+        log_file: args.value_of_os("log-file").map(Path::new),
+        tee: args.value_of_os("tee").map(Path::new),
+        quiet: args.is_present("quiet"),
+        auto_log: !args.is_present("no-auto-log"),
+        log_analysis: !args.is_present("no-log-analysis"),
+        timeout: args
+            .value_of("timeout")
+            .map(|_| Duration::from_secs(parse_positive_number(args, "timeout").into())),
+        launch_retries: args
+            .value_of("launch-retries")
+            .map_or(0, |_| parse_positive_number(args, "launch-retries")),
+        force: args.is_present("force"),
+        working_dir: get_working_dir(args),
+        env,
+        env_clear: args.is_present("env-clear"),
+        env_pass: args.values_of("env-pass").into_iter().flatten().collect(),
+        windows_paths,
+        bug_report: args.is_present("bug-report"),
+        windowed,
+        window_size,
+        preflight: !args.is_present("skip-preflight"),
+        game_version: get_game_version_override(args),
+        strict_version: args.is_present("strict-version"),
+        profile,
+        profiles,
+    }
 }
 
-fn convert_legacy_replay_stream_to_raw(base64_stream: &str) -> io::Result<NamedTempFile> {
-    let zipped_qt_data = base64::decode_config(base64_stream, base64::STANDARD).map_err(|_| {
-        io::Error::new(
-            ErrorKind::InvalidData,
-            "Replay corrupt - couldn't decode base64",
-        )
-    })?;
+/// Whether the game is windowed, with the size if given. `--windowed` takes precedence over the config file.
+fn get_windowed(args: &ArgMatches) -> (bool, Option<(u32, u32)>) {
+    if args.is_present("fullscreen") {
+        return (false, None);
+    }
+    let (resolution, source) = match args.value_of("windowed") {
+        Some(resolution) => (resolution, "--windowed"),
+        None if args.is_present("windowed") => return (true, None),
+        None => match CONFIG
+            .get()
+            .and_then(|config| config.launch.windowed.as_deref())
+        {
+            Some(resolution) => (resolution, "launch.windowed of the config file"),
+            None => return (false, None),
+        },
+    };
 
-    let (_, zipped_data_slice) = zipped_qt_data.split_at(4);
-    let zipped_data = Vec::from(zipped_data_slice);
+    match config::parse_resolution(resolution) {
+        Some(size) => (true, Some(size)),
+        None => fail(
+            "InvalidArgument",
+            format!(
+                "Invalid {} {}, expected a size like 1280x720",
+                source, resolution
+            ),
+            GENERAL_EXIT_CODE,
+        ),
+    }
+}
 
-    let mut temp_replay_file = tempfile::NamedTempFile::new()?;
+fn get_game_version_override(args: &ArgMatches) -> Option<u32> {
+    args.value_of("game-version")
+        .map(|_| parse_positive_number(args, "game-version"))
+}
 
-    let mut decoder = ZlibDecoder::new(zipped_data.as_slice());
-    let mut output = Vec::new();
-    decoder.read_to_end(&mut output)?;
-    temp_replay_file.as_file_mut().write_all(&output)?;
+/// Warns if the replay is of another game version than the executable, exits with `--strict-version`
+fn check_game_version(
+    game_launch: &GameLaunch,
+    executable: &Path,
+    replay_game_version: Option<&str>,
+) {
+    let replay_build = match replay_game_version.and_then(game_version::parse_build) {
+        Some(replay_build) => replay_build,
+        None => return,
+    };
+    let executable_build = game_launch
+        .game_version
+        .or_else(|| game_version::read_executable_build(executable));
 
-    Ok(temp_replay_file)
+    let mismatch = executable_build.and_then(|executable_build| {
+        game_version::describe_mismatch(replay_build, executable_build)
+    });
+    if let Some(mismatch) = mismatch {
+        if game_launch.strict_version {
+            fail(
+                "GameVersion",
+                format!("{}, not launching it (--strict-version)", mismatch),
+                GENERAL_EXIT_CODE,
+            )
+        }
+        eprintln!("Warning: {}", mismatch);
+    }
 }
 
-fn launch_game(executable: &Path, file_name: &str, replay_id: u32, wrapper: Option<&Path>) {
-    let executable_str = executable.to_str().unwrap();
-    let executable_dir_str = executable.parent().unwrap().to_str().unwrap();
+/// `--profile NAME`
+fn find_profile(profiles: &'static [ExecutableProfile], name: &str) -> &'static ExecutableProfile {
+    profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .unwrap_or_else(|| {
+            let names: Vec<&str> = profiles
+                .iter()
+                .map(|profile| profile.name.as_str())
+                .collect();
+            fail(
+                "InvalidArgument",
+                format!(
+                    "No profile {} in [[executables]] of the config file, there are: {}",
+                    name,
+                    names.join(", ")
+                ),
+                GENERAL_EXIT_CODE,
+            )
+        })
+}
 
-    let launch_arg = wrapper
-        .map(|w| w.to_str().unwrap())
-        .unwrap_or_else(|| executable_str);
+/// The first of `profiles` matching the replay, None launches it with `executable`
+fn find_matching_profile(
+    game_launch: &GameLaunch,
+    metadata: Option<&ReplayMetadata>,
+    game_version: Option<&str>,
+) -> Option<&'static ExecutableProfile> {
+    let featured_mod = game_launch
+        .featured_mod
+        .or_else(|| metadata.and_then(|metadata| metadata.featured_mod.as_deref()));
 
-    let mut launch_command = Command::new(launch_arg);
+    game_launch
+        .profiles
+        .iter()
+        .find(|profile| profile.matches(featured_mod, game_version))
+}
 
-    if wrapper.is_some() {
-        launch_command.arg(executable_str);
+fn get_working_dir<'a>(args: &'a ArgMatches) -> Option<&'a Path> {
+    let working_dir = Path::new(args.value_of_os("cwd")?);
+    if !working_dir.is_dir() {
+        fail(
+            "FileNotFound",
+            format!("No directory found at {} (--cwd)", working_dir.display()),
+            GENERAL_EXIT_CODE,
+        )
     }
+    Some(working_dir)
+}
 
-    launch_command
-        .args(&[
-            "/init",
-            "init.lua",
-            "/nobugreport",
-            "/replay",
-            file_name,
-            "/replayid",
-            &replay_id.to_string(),
-        ])
-        .current_dir(executable_dir_str);
+/// The replay path passed to the game, which runs in another working directory than we do.
+/// Not canonicalized, the game doesn't understand the `\\?\` paths that gives on Windows.
+/// None if it isn't valid UTF-8, the game gets its paths as strings, e.g. translated for wine.
+fn get_absolute_path(raw_replay_path: &Path) -> Option<String> {
+    std::path::absolute(raw_replay_path)
+        .unwrap_or_else(|_| raw_replay_path.to_path_buf())
+        .into_os_string()
+        .into_string()
+        .ok()
+}
 
-    // game_directory.map(|dir| launch_command.current_dir(Path::new(dir)));
+/// The `env` table of the config file followed by the `--env KEY=VALUE` options
+fn get_game_env(args: &ArgMatches) -> Vec<(String, String)> {
+    let mut game_env: Vec<(String, String)> = CONFIG
+        .get()
+        .map(|config| config.env.clone().into_iter().collect())
+        .unwrap_or_default();
 
-    let result = launch_command.output().expect("Game failed to launch");
+    for variable in args.values_of("env").into_iter().flatten() {
+        // only the first = separates, the value may contain more
+        match variable.split_once('=') {
+            Some((name, value)) if !name.is_empty() => {
+                game_env.push((name.to_string(), value.to_string()))
+            }
+            _ => fail(
+                "InvalidArgument",
+                format!("Invalid --env {}, expected KEY=VALUE", variable),
+                GENERAL_EXIT_CODE,
+            ),
+        }
+    }
+    game_env
+}
 
-    io::stdout().write_all(&result.stdout).unwrap();
-    io::stderr().write_all(&result.stderr).unwrap();
+/// `--keep-extracted PATH`, the cache directory if given without path
+fn get_keep_extracted_path(args: &ArgMatches) -> Option<PathBuf> {
+    if !args.is_present("keep-extracted") {
+        return None;
+    }
+    if let Some(path) = args.value_of_os("keep-extracted") {
+        return Some(PathBuf::from(path));
+    }
 
-    println!("We launched the game. Check for errors!");
+    let cache = get_replay_cache(args).unwrap_or_else(|| {
+        fail(
+            "InvalidArgument",
+            "No cache directory to keep the extracted replay in, use --keep-extracted PATH",
+            GENERAL_EXIT_CODE,
+        )
+    });
+    if let Err(e) = fs::create_dir_all(cache.dir()) {
+        fail(
+            "Cache",
+            format!("Could not create {}: {}", cache.dir().display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    }
+    Some(cache.dir().to_path_buf())
+}
+
+/// Exits listing the init files next to the executable if `init_file` isn't one of them
+fn check_init_file(executable: &Path, init_file: &str) {
+    let executable_dir = executable.parent().unwrap();
+    if executable_dir.join(init_file).is_file() {
+        return;
+    }
+
+    let mut found_init_files: Vec<String> = fs::read_dir(executable_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|dir_entry| dir_entry.ok())
+        .map(|dir_entry| dir_entry.file_name().to_string_lossy().into_owned())
+        .filter(|file_name| file_name.starts_with("init") && file_name.ends_with(".lua"))
+        .collect();
+    found_init_files.sort();
+
+    let found = if found_init_files.is_empty() {
+        "none".to_string()
+    } else {
+        found_init_files.join(", ")
+    };
+    fail(
+        "FileNotFound",
+        format!(
+            "No init file {} found in {}, init files there: {}",
+            init_file,
+            executable_dir.display(),
+            found
+        ),
+        GENERAL_EXIT_CODE,
+    )
+}
+
+/// `--local-file` or the positional replay
+fn get_replay_source<'a>(args: &'a ArgMatches) -> &'a str {
+    args.value_of("local-file")
+        .or_else(|| args.value_of("replay"))
+        .unwrap()
+}
+
+fn get_replay_path<'a>(args: &'a ArgMatches) -> &'a Path {
+    let replay_str = get_replay_source(args);
+    let replay_path = Path::new(replay_str);
+
+    check_file_access(replay_path, "replay file", fs::File::open(replay_path));
+    // opening a directory works on unix, it would only fail later as unknown replay format
+    if replay_path.is_dir() {
+        fail(
+            "InvalidArgument",
+            format!(
+                "Expected a replay file, got a directory at {}",
+                replay_path.display()
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    replay_path
+}
+
+/// Splits `--wrapper` into the program and its arguments, an existing file is taken as it is
+/// even if its path contains spaces
+fn get_wrapper(args: &ArgMatches) -> Option<Wrapper> {
+    let wrapper_str = args.value_of("wrapper")?;
+
+    let mut words = if Path::new(wrapper_str).is_file() {
+        vec![wrapper_str.to_string()]
+    } else if cfg!(windows) {
+        // backslashes are path separators there, a shell would take them for escapes
+        command_line::split_windows(wrapper_str)
+    } else {
+        shell_words::split(wrapper_str).unwrap_or_else(|e| {
+            fail(
+                "InvalidArgument",
+                format!("Invalid --wrapper {}: {}", wrapper_str, e),
+                GENERAL_EXIT_CODE,
+            )
+        })
+    };
+    if words.is_empty() {
+        fail("InvalidArgument", "--wrapper is empty", GENERAL_EXIT_CODE)
+    }
+    let mut program = words.remove(0);
+
+    // a bare command name is looked up in PATH like the shell does
+    if Path::new(&program).components().count() > 1 {
+        let wrapper_path = Path::new(&program);
+        check_file_access(wrapper_path, "wrapper file", fs::File::open(wrapper_path));
+        check_launchable(wrapper_path, "wrapper");
+    } else {
+        match command_line::find_in_path(&program) {
+            // Command only starts them by path, through cmd.exe with the arguments quoted for it
+            Some(path) if command_line::is_batch_file(&path) => {
+                program = path.to_string_lossy().into_owned()
+            }
+            Some(path) => check_launchable(&path, "wrapper"),
+            None => fail(
+                "FileNotFound",
+                format!("No wrapper {} found in PATH", program),
+                GENERAL_EXIT_CODE,
+            ),
+        }
+    }
+
+    Some(Wrapper {
+        program,
+        args: words,
+    })
+}
+
+/// `--seek` is accepted for frontends passing it, neither the retail game nor FAF versions can start a replay
+/// partway through
+fn warn_about_seek(args: &ArgMatches) {
+    let tick = match args.value_of("seek") {
+        Some(tick) => tick,
+        None => return,
+    };
+    let tick: u32 = tick.parse().unwrap_or_else(|_| {
+        fail(
+            "InvalidArgument",
+            format!("--seek must be a tick, e.g. 3000, not {}", tick),
+            GENERAL_EXIT_CODE,
+        )
+    });
+    eprintln!(
+        "Warning: The game has no switch to start a replay partway through, --seek {} is ignored. \
+         The tick is at {} of game time, fast forward to it with the replay speed in the game.",
+        tick,
+        info::format_duration(Duration::from_secs(u64::from(tick) / 10))
+    );
+}
+
+/// Non-fatal, some setups need a wrapper on Windows or run .exe files without one
+fn warn_about_wrapper(executable: &Path, wrapper: Option<&Wrapper>) {
+    let wrapper_words: Option<Vec<&str>> = wrapper.map(|wrapper| {
+        std::iter::once(&wrapper.program)
+            .chain(&wrapper.args)
+            .map(String::as_str)
+            .collect()
+    });
+    let warning = command_line::wrapper_warning(
+        cfg!(windows),
+        executable,
+        wrapper_words.as_deref(),
+        command_line::wine_binfmt_registered(),
+        command_line::find_in_path("wine").as_deref(),
+    );
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// `--proton`, the wrapper `proton run` and the environment it needs for the prefix of `--compat-data`
+fn get_proton_launch(args: &ArgMatches) -> Option<(Wrapper, Vec<(String, String)>)> {
+    if !args.is_present("proton") {
+        return None;
+    }
+    let steam_dirs = steam::default_steam_dirs();
+    let libraries = steam::find_library_folders(&steam_dirs);
+
+    let proton_dir = match args.value_of_os("proton") {
+        Some(proton_dir) => PathBuf::from(proton_dir),
+        None => {
+            let proton_dir = proton::find_newest(&libraries).unwrap_or_else(|| {
+                fail(
+                    "NotFound",
+                    format!(
+                        "No Proton found in the Steam libraries {}, use --proton <PROTON_DIR>",
+                        join_paths(&libraries)
+                    ),
+                    GENERAL_EXIT_CODE,
+                )
+            });
+            eprintln!("Using {}", proton_dir.display());
+            proton_dir
+        }
+    };
+    let proton_script = proton_dir.join("proton");
+    check_file_access(
+        &proton_script,
+        "Proton script",
+        fs::File::open(&proton_script),
+    );
+
+    let compat_data = match args.value_of_os("compat-data") {
+        Some(compat_data) => PathBuf::from(compat_data),
+        None => proton::find_compat_data(&libraries).unwrap_or_else(|| {
+            fail(
+                "NotFound",
+                "No Proton prefix of Forged Alliance found in the Steam libraries, start the game with Steam once or use --compat-data <DIR>",
+                GENERAL_EXIT_CODE,
+            )
+        }),
+    };
+
+    let mut proton_env = vec![(
+        "STEAM_COMPAT_DATA_PATH".to_string(),
+        compat_data.display().to_string(),
+    )];
+    if let Some(steam_dir) = steam_dirs.first() {
+        proton_env.push((
+            "STEAM_COMPAT_CLIENT_INSTALL_PATH".to_string(),
+            steam_dir.display().to_string(),
+        ));
+    }
+    let proton = Wrapper {
+        program: proton_script.display().to_string(),
+        args: vec!["run".to_string()],
+    };
+    Some((proton, proton_env))
+}
+
+/// Fails before the launch with how to fix it, e.g. chmod +x for a script
+fn check_launchable(path: &Path, description: &str) {
+    if let Some(problem) = command_line::launch_problem(path) {
+        fail(
+            "PermissionDenied",
+            format!("The {} {} {}", description, path.display(), problem),
+            GENERAL_EXIT_CODE,
+        )
+    }
+}
+
+/// Exits with a message naming the actual problem if `access` failed
+fn check_file_access<T>(path: &Path, description: &str, access: io::Result<T>) {
+    let e = match access {
+        Ok(_) => return,
+        Err(e) => e,
+    };
+
+    match e.kind() {
+        io::ErrorKind::NotFound => fail(
+            "FileNotFound",
+            format!("No {} found at {}", description, path.display()),
+            GENERAL_EXIT_CODE,
+        ),
+        io::ErrorKind::PermissionDenied => fail(
+            "PermissionDenied",
+            format!(
+                "Permission denied for the {} at {}, check its owner and permissions",
+                description,
+                path.display()
+            ),
+            GENERAL_EXIT_CODE,
+        ),
+        _ => fail(
+            "FileAccess",
+            format!(
+                "Could not access the {} at {}: {}",
+                description,
+                path.display(),
+                e
+            ),
+            GENERAL_EXIT_CODE,
+        ),
+    }
+}
+
+/// Prints the error as chosen with `--error-format` and exits with `exit_code`
+fn fail(code: &str, message: impl Display, exit_code: i32) -> ! {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        let error = serde_json::json!({
+            "error": code,
+            "message": message.to_string(),
+            "exit_code": exit_code,
+        });
+        eprintln!("{}", error);
+    } else {
+        eprintln!("{}", message);
+    }
+    events::emit(&Event::Error {
+        kind: events::to_kind(code),
+        message: &message.to_string(),
+    });
+    exit(exit_code)
+}
+
+fn fail_replay(e: &ReplayError) -> ! {
+    fail(e.code(), e, e.exit_code())
+}
+
+fn main() {
+    let matches = build_cli().get_matches();
+    JSON_ERRORS.store(
+        matches.value_of("error-format") == Some("json"),
+        Ordering::Relaxed,
+    );
+    // only for watching replays, subcommands keep their output
+    if matches.value_of("progress-format") == Some("json") && matches.subcommand_name().is_none() {
+        events::enable();
+    }
+    let config = load_config(&matches);
+    OFFLINE.store(
+        matches.is_present("offline") || config.offline,
+        Ordering::Relaxed,
+    );
+    STRICT.store(matches.is_present("strict"), Ordering::Relaxed);
+    MAX_SIZE.store(get_max_size(&matches), Ordering::Relaxed);
+    CONFIG.get_or_init(|| config);
+    TEMP_DIR.get_or_init(|| resolve_temp_dir(&matches));
+    let json_output = matches
+        .subcommand_matches("search")
+        .is_some_and(|search_args| search_args.is_present("json"));
+    SHOW_PROGRESS.store(
+        !matches.is_present("quiet") && !json_output && io::stderr().is_terminal(),
+        Ordering::Relaxed,
+    );
+
+    match matches.subcommand() {
+        ("convert", Some(convert_args)) => convert_replay(convert_args),
+        ("decode-base64", Some(decode_args)) => decode_base64_stream(decode_args),
+        ("info", Some(info_args)) => show_replay_info(&matches, info_args),
+        ("serve", Some(serve_args)) => serve_replay(serve_args),
+        ("search", Some(search_args)) => search_games(&matches, search_args),
+        ("download-latest", Some(latest_args)) => download_latest_game(&matches, latest_args),
+        ("download", Some(download_args)) => download_replay_list(&matches, download_args),
+        ("login", Some(login_args)) => login(login_args),
+        ("doctor", Some(_)) => run_doctor(&matches),
+        ("profiles", Some(_)) => list_profiles(),
+        ("cache", Some(cache_args)) => manage_cache(cache_args),
+        ("stop", Some(stop_args)) => stop_games(stop_args),
+        ("status", Some(_)) => show_status(),
+        ("completions", Some(completions_args)) => print_completions(completions_args),
+        _ if matches.is_present("version-string") => print_version_string(&matches),
+        _ if matches.is_present("print-detected-paths") => print_detected_paths(),
+        _ => watch_replay(&matches),
+    }
+}
+
+fn watch_replay(matches: &ArgMatches) {
+    let print_temp_path = matches.is_present("print-temp-path");
+    // checked up front to not download in vain
+    let game_launch = if print_temp_path {
+        None
+    } else {
+        Some(get_game_launch(matches))
+    };
+
+    if let (Some(dir), Some(game_launch)) = (matches.value_of("watch-dir"), &game_launch) {
+        watch_replay_dir(matches, Path::new(dir), game_launch);
+        return;
+    }
+
+    let replay_source = get_replay_source(matches);
+    events::emit(&Event::Detecting);
+
+    if print_temp_path && live::is_live_replay_uri(replay_source) {
+        fail(
+            "InvalidArgument",
+            "--print-temp-path needs a replay file, live replays are only streamed",
+            GENERAL_EXIT_CODE,
+        )
+    }
+    if matches.is_present("detach") && live::is_live_replay_uri(replay_source) {
+        fail(
+            "InvalidArgument",
+            "--detach can't be used for live replays, the stream is relayed by faf-replay-cli",
+            GENERAL_EXIT_CODE,
+        )
+    }
+    if let (true, Some(game_launch)) = (live::is_live_replay_uri(replay_source), &game_launch) {
+        let relay = start_live_replay_relay(matches, replay_source);
+        let replay_id = get_replay_id_override(matches).unwrap_or(relay.game_id);
+        let status = launch_game(game_launch, &relay.game_url, replay_id, None, None);
+        exit_like_game(game_launch, status);
+        return;
+    }
+
+    let vault_replay_id = replay_source.parse::<u32>().ok();
+    // keeps a downloaded replay alive until the game has finished
+    let fetched_replay = match vault_replay_id {
+        Some(vault_replay_id) => Some(fetch_vault_replay(matches, vault_replay_id)),
+        None if download::is_url(replay_source) => Some(FetchedReplay::Downloaded(
+            exit_on_download_error(get_downloader(matches).download_replay_url(replay_source)),
+        )),
+        None => None,
+    };
+    // a detached game still reads the download after we exited
+    let fetched_replay = match fetched_replay {
+        Some(FetchedReplay::Downloaded(f)) if matches.is_present("detach") => {
+            Some(FetchedReplay::Cached(keep_for_detached_game(matches, f)))
+        }
+        fetched_replay => fetched_replay,
+    };
+    let replay_path = match &fetched_replay {
+        Some(fetched_replay) => fetched_replay.path(),
+        None => get_replay_path(matches),
+    };
+
+    match &game_launch {
+        Some(game_launch) => {
+            let status = launch_replay_file(matches, game_launch, replay_path, vault_replay_id);
+            drop(fetched_replay);
+            exit_like_game(game_launch, status);
+        }
+        None => {
+            let raw_replay_path = extract_kept_replay(replay_path);
+            // a downloaded raw replay isn't extracted, the download itself has to be kept
+            if let Some(FetchedReplay::Downloaded(f)) = fetched_replay {
+                if f.path() == raw_replay_path {
+                    keep_temp_file(f);
+                }
+            }
+            println!("{}", raw_replay_path.display());
+        }
+    }
+}
+
+/// Extracts the replay into a temp file that isn't deleted on exit, for other tools to open it
+fn extract_kept_replay(replay_path: &Path) -> PathBuf {
+    let prepared_replay = prepare_replay(replay_path).unwrap_or_else(|e| fail_replay(&e));
+
+    match prepared_replay.location {
+        ReplayLocation::AtTempFile(f) => keep_temp_file(f),
+        ReplayLocation::AtPath(path) => path.to_path_buf(),
+        ReplayLocation::Extracted(path) => path,
+    }
+}
+
+/// Moves a temp file into the cache directory, where `cache clean` eventually removes it.
+/// Without cache (`--no-cache`) it is kept in the temp dir.
+fn keep_for_detached_game(matches: &ArgMatches, temp_file: NamedTempFile) -> PathBuf {
+    let cache = match get_replay_cache(matches) {
+        Some(cache) if !matches.is_present("no-cache") => cache,
+        _ => return keep_temp_file(temp_file),
+    };
+
+    cache.store_detached(temp_file).unwrap_or_else(|e| {
+        fail(
+            "Cache",
+            format!(
+                "Could not keep the replay in {} for the detached game: {}",
+                cache.dir().display(),
+                e
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    })
+}
+
+fn keep_temp_file(temp_file: NamedTempFile) -> PathBuf {
+    match temp_file.keep() {
+        Ok((_, path)) => path,
+        Err(e) => fail(
+            "FileAccess",
+            format!("Could not keep {}: {}", e.file.path().display(), e.error),
+            GENERAL_EXIT_CODE,
+        ),
+    }
+}
+
+fn watch_replay_dir(matches: &ArgMatches, dir: &Path, game_launch: &GameLaunch) {
+    if !dir.is_dir() {
+        fail(
+            "FileNotFound",
+            format!("No directory found at {}", dir.display()),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    let mut watcher = dir_watch::ReplayDirWatcher::new(dir).unwrap_or_else(|e| {
+        fail(
+            "FileAccess",
+            format!("Could not watch {}: {}", dir.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    });
+    status!("Watching {} for new replays", dir.display());
+
+    while let Some(replay_path) = watcher.next_replay() {
+        status!("New replay {}", replay_path.display());
+        events::emit(&Event::Detecting);
+
+        // a broken replay must not end the watching
+        let prepared_replay =
+            match prepare_replay_kept(&replay_path, game_launch.keep_extracted.as_deref()) {
+                Ok(prepared_replay) => prepared_replay,
+                Err(e) => {
+                    eprintln!("Skipping {}: {}", replay_path.display(), e);
+                    continue;
+                }
+            };
+        let raw_replay_path = prepared_replay.location.path();
+        let game_replay_path = match get_absolute_path(raw_replay_path) {
+            Some(game_replay_path) => game_replay_path,
+            None => {
+                eprintln!(
+                    "Skipping {}: {} is not valid UTF-8",
+                    replay_path.display(),
+                    raw_replay_path.display()
+                );
+                continue;
+            }
+        };
+
+        ensure_map_installed(matches, raw_replay_path);
+        let status = launch_game(
+            game_launch,
+            &game_replay_path,
+            choose_replay_id(matches, None, &prepared_replay, &replay_path),
+            prepared_replay.metadata.as_ref(),
+            read_game_version(raw_replay_path).as_deref(),
+        );
+        // the next replay is watched anyway
+        if let Some((_, message, _)) = get_game_failure(status) {
+            eprintln!("{}", message);
+        }
+        drop(prepared_replay);
+        exit_if_interrupted();
+    }
+}
+
+/// Extracts the replay if needed, makes sure its map is installed and launches the game with it.
+/// The extracted replay is removed again once the game exited.
+fn launch_replay_file(
+    matches: &ArgMatches,
+    game_launch: &GameLaunch,
+    replay_path: &Path,
+    vault_id: Option<u32>,
+) -> GameExit {
+    let prepared_replay = prepare_replay_kept(replay_path, game_launch.keep_extracted.as_deref())
+        .unwrap_or_else(|e| fail_replay(&e));
+    let replay_id = choose_replay_id(matches, vault_id, &prepared_replay, replay_path);
+    if matches.is_present("verbose") {
+        eprintln!(
+            "debug: {} is a {} replay",
+            replay_path.display(),
+            get_prepared_type(&prepared_replay)
+        );
+    }
+
+    // the extracted replay is removed when we exit, long before a detached game read it
+    let detached_replay_path;
+    let location = match prepared_replay.location {
+        ReplayLocation::AtTempFile(f) if game_launch.detach && !game_launch.dry_run => {
+            detached_replay_path = keep_for_detached_game(matches, f);
+            status!(
+                "The extracted replay is kept at {}",
+                detached_replay_path.display()
+            );
+            ReplayLocation::AtPath(&detached_replay_path)
+        }
+        location => location,
+    };
+    if let ReplayLocation::Extracted(kept_path) = &location {
+        status!("The extracted replay is kept at {}", kept_path.display());
+    }
+    let raw_replay_path = location.path();
+    let game_replay_path = match get_absolute_path(raw_replay_path) {
+        Some(game_replay_path) => game_replay_path,
+        None => {
+            let message = format!(
+                "The replay path {} is not valid UTF-8",
+                raw_replay_path.display()
+            );
+            // removes the extracted replay before exiting
+            drop(location);
+            fail("InvalidArgument", message, GENERAL_EXIT_CODE)
+        }
+    };
+
+    ensure_map_installed(matches, raw_replay_path);
+    let status = launch_game(
+        game_launch,
+        &game_replay_path,
+        replay_id,
+        prepared_replay.metadata.as_ref(),
+        read_game_version(raw_replay_path).as_deref(),
+    );
+
+    if let ReplayLocation::AtTempFile(f) = location {
+        if game_launch.keep_temp {
+            let kept_path = keep_temp_file(f);
+            status!("The extracted replay is kept at {}", kept_path.display());
+        } else if game_launch.dry_run || game_launch.no_launch {
+            status!(
+                "The extracted replay {} is removed on exit, use --keep-temp to keep it",
+                f.path().display()
+            );
+        }
+    }
+
+    status
+}
+
+/// The game version in the header, to pick the profile. A broken header is reported by the game.
+fn read_game_version(raw_replay_path: &Path) -> Option<String> {
+    let raw_replay = fs::read(raw_replay_path).ok()?;
+    parse_replay_header(&raw_replay)
+        .ok()
+        .map(|header| header.game_version)
+}
+
+/// Exits like the game did, unless it succeeded or `--ignore-game-exit-code` is given.
+/// Temp files have to be dropped before, exiting skips their cleanup.
+fn exit_like_game(game_launch: &GameLaunch, status: GameExit) {
+    exit_if_interrupted();
+    if game_launch.ignore_exit_code {
+        return;
+    }
+
+    if let Some((code, message, exit_code)) = get_game_failure(status) {
+        fail(code, message, exit_code)
+    }
+}
+
+/// Exits like for Ctrl+C if it was pressed after the game exited, the extracted replay has to be removed before
+fn exit_if_interrupted() {
+    LAUNCH_ACTIVE.store(false, Ordering::SeqCst);
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        // like shells report SIGINT
+        exit(130)
+    }
+}
+
+/// The error code, message and exit code of a failed game, None if it exited with 0
+fn get_game_failure(game_exit: GameExit) -> Option<(&'static str, String, i32)> {
+    let status = game_exit.status;
+    if game_exit.timed_out {
+        return Some((
+            "GameTimeout",
+            "The game was stopped since it ran longer than --timeout".to_string(),
+            TIMEOUT_EXIT_CODE,
+        ));
+    }
+    if status.success() {
+        return None;
+    }
+    if let Some(code) = status.code() {
+        return Some((
+            "GameExit",
+            format!("The game exited with code {}", code),
+            code,
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            // like shells report it
+            return Some((
+                "GameKilled",
+                format!("The game was killed by signal {}", signal),
+                128 + signal,
+            ));
+        }
+    }
+
+    Some((
+        "GameExit",
+        format!("The game exited with {}", status),
+        GENERAL_EXIT_CODE,
+    ))
+}
+
+/// `--featured-mod`, otherwise the featured mod the replay was recorded with. Raw replays and live replays
+/// don't tell, most of them are played with `faf`.
+fn choose_featured_mod<'a>(
+    game_launch: &'a GameLaunch,
+    metadata: Option<&'a ReplayMetadata>,
+) -> &'a str {
+    game_launch
+        .featured_mod
+        .or_else(|| metadata.and_then(|metadata| metadata.featured_mod.as_deref()))
+        .unwrap_or(featured_mods::DEFAULT_FEATURED_MOD)
+}
+
+/// `--init` or the one of the profile, otherwise the init file of the featured mod (`--featured-mod` or the one
+/// the replay was recorded with). Falls back to the retail init file if that is missing, the game wouldn't start
+/// at all otherwise.
+fn choose_init_file(
+    game_launch: &GameLaunch,
+    executable: &Path,
+    profile: Option<&ExecutableProfile>,
+    metadata: Option<&ReplayMetadata>,
+) -> String {
+    let profile_init_file = profile.and_then(|profile| profile.init.as_deref());
+    if let Some(init_file) = game_launch.init_file.or(profile_init_file) {
+        return init_file.to_string();
+    }
+    let recorded_mod = metadata.and_then(|metadata| metadata.featured_mod.as_deref());
+    let featured_mod = match game_launch.featured_mod.or(recorded_mod) {
+        Some(featured_mod) => featured_mod,
+        None => return featured_mods::DEFAULT_INIT_FILE.to_string(),
+    };
+
+    let no_overrides = BTreeMap::new();
+    let overrides = CONFIG
+        .get()
+        .map_or(&no_overrides, |config| &config.init_files);
+    let init_file = featured_mods::get_init_file(featured_mod, overrides);
+
+    let executable_dir = executable.parent().unwrap();
+    if executable_dir.join(&init_file).is_file() {
+        return init_file;
+    }
+
+    eprintln!(
+        "WARNING: The replay was recorded with the featured mod {}, but its init file {} is missing in {}.\n\
+         Starting with {} instead, the replay will most likely desync. Start a {} game in the FAF client once \
+         to install the featured mod, or use --executable of the FAF installation having it (init_files in the \
+         config file map featured mods to other init files).",
+        featured_mod,
+        init_file,
+        executable_dir.display(),
+        featured_mods::DEFAULT_INIT_FILE,
+        featured_mod
+    );
+    featured_mods::DEFAULT_INIT_FILE.to_string()
+}
+
+/// The `/replayid` of a replay file: `--replay-id`, the vault id, the `uid` in the metadata
+/// or the number a file name like `21934412.scfareplay` starts with, in that order
+fn choose_replay_id(
+    matches: &ArgMatches,
+    vault_id: Option<u32>,
+    prepared_replay: &PreparedReplay,
+    replay_path: &Path,
+) -> u32 {
+    get_replay_id_override(matches)
+        .or(vault_id)
+        .or_else(|| {
+            prepared_replay
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.uid)
+        })
+        .or_else(|| bulk::parse_file_name_replay_id(replay_path))
+        .unwrap_or(UNKNOWN_REPLAY_ID)
+}
+
+fn get_replay_id_override(matches: &ArgMatches) -> Option<u32> {
+    matches
+        .value_of("replay-id")
+        .map(|_| parse_positive_number(matches, "replay-id"))
+}
+
+/// Makes sure the map of the replay is installed and downloads it unless `--no-map-download`.
+/// A missing map only shows a black screen in game, so problems don't stop the launch.
+fn ensure_map_installed(matches: &ArgMatches, raw_replay_path: &Path) {
+    let header = fs::read(raw_replay_path)
+        .map_err(ReplayError::from)
+        .and_then(|raw_replay| parse_replay_header(&raw_replay));
+    let header = match header {
+        Ok(header) => header,
+        Err(e) => {
+            eprintln!("Could not check the map of the replay: {}", e);
+            return;
+        }
+    };
+    let map_folder = match maps::get_map_folder(&header.map_path) {
+        Some(map_folder) if !maps::is_official_map(map_folder) => map_folder,
+        _ => return,
+    };
+
+    let maps_dir = match matches
+        .value_of("maps-dir")
+        .map(PathBuf::from)
+        .or_else(MapsDir::default_dir)
+    {
+        Some(dir) => MapsDir::new(dir),
+        None => {
+            eprintln!(
+                "No maps directory found, use --maps-dir to check for the map {}",
+                map_folder
+            );
+            return;
+        }
+    };
+
+    match maps_dir.check(map_folder) {
+        Ok(MapCheck::Installed) => return,
+        Ok(MapCheck::Missing) => eprintln!(
+            "Map {} is not installed in {}",
+            map_folder,
+            maps_dir.dir().display()
+        ),
+        Ok(MapCheck::OtherVersions(versions)) => eprintln!(
+            "Map version mismatch: the replay was recorded on {}, but {} only has {}",
+            map_folder,
+            maps_dir.dir().display(),
+            versions.join(", ")
+        ),
+        Err(e) => {
+            eprintln!(
+                "Could not check the maps in {}: {}",
+                maps_dir.dir().display(),
+                e
+            );
+            return;
+        }
+    }
+
+    if matches.is_present("no-map-download") {
+        eprintln!(
+            "Not downloading it (--no-map-download), the game will likely show a black screen"
+        );
+        return;
+    }
+
+    let content_base_url = matches.value_of("content-base-url").unwrap();
+    match maps_dir.install(&get_downloader(matches), content_base_url, map_folder) {
+        Ok(map_path) => status!("Map {} installed to {}", map_folder, map_path.display()),
+        Err(e) => eprintln!(
+            "Could not download the map {}, launching anyway: {}",
+            map_folder, e
+        ),
+    }
+}
+
+/// `--max-size`, `DEFAULT_MAX_SIZE` without it
+fn get_max_size(matches: &ArgMatches) -> u64 {
+    match matches.value_of("max-size") {
+        Some(max_size) => max_size
+            .parse()
+            .ok()
+            .filter(|&max_size| max_size > 0)
+            .unwrap_or_else(|| {
+                fail(
+                    "InvalidArgument",
+                    format!(
+                        "Invalid --max-size {}, expected a number of bytes",
+                        max_size
+                    ),
+                    GENERAL_EXIT_CODE,
+                )
+            }),
+        None => DEFAULT_MAX_SIZE,
+    }
+}
+
+/// Like `prepare_replay_file`, extracts into `--temp-dir` and shows a progress bar
+/// while large legacy replays are decompressed
+fn prepare_replay(replay_path: &Path) -> Result<PreparedReplay<'_>, ReplayError> {
+    prepare_replay_kept(replay_path, None)
+}
+
+/// Like `prepare_replay`, extracts to `keep_extracted` instead of a temp file if given
+fn prepare_replay_kept<'a>(
+    replay_path: &'a Path,
+    keep_extracted: Option<&Path>,
+) -> Result<PreparedReplay<'a>, ReplayError> {
+    let mut progress = None;
+    let mut extracting = false;
+
+    let options = PrepareOptions {
+        temp_dir: get_temp_dir(),
+        progress: Some(&mut |decompressed, total| {
+            if !extracting {
+                extracting = true;
+                events::emit(&Event::Extracting);
+            }
+            if total >= LARGE_REPLAY_SIZE {
+                progress
+                    .get_or_insert_with(|| {
+                        ByteProgress::new(
+                            SHOW_PROGRESS.load(Ordering::Relaxed),
+                            Some(total),
+                            "Decompressing",
+                            "Decompressed",
+                        )
+                    })
+                    .set_position(decompressed);
+            }
+        }),
+        keep_extracted,
+        strict: STRICT.load(Ordering::Relaxed),
+        warning: Some(&mut |warning| eprintln!("Warning: {}", warning)),
+        max_size: Some(MAX_SIZE.load(Ordering::Relaxed)),
+    };
+    let prepared_replay = prepare_replay_file_with_options(replay_path, options);
+
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    prepared_replay
+}
+
+/// `--temp-dir` or `$TMPDIR`, None for the system temp dir
+fn get_temp_dir() -> Option<&'static Path> {
+    TEMP_DIR.get().and_then(|temp_dir| temp_dir.as_deref())
+}
+
+fn resolve_temp_dir(matches: &ArgMatches) -> Option<PathBuf> {
+    let temp_dir = paths::temp_dir(matches.value_of_os("temp-dir"))?;
+
+    if !temp_dir.is_dir() {
+        fail(
+            "FileNotFound",
+            format!("No temp directory found at {}", temp_dir.display()),
+            GENERAL_EXIT_CODE,
+        )
+    }
+    Some(temp_dir)
+}
+
+fn get_downloader(args: &ArgMatches) -> Downloader {
+    let retries = args
+        .value_of("retries")
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|_| {
+            fail(
+                "InvalidArgument",
+                "--retries must be a number",
+                GENERAL_EXIT_CODE,
+            )
+        });
+
+    Downloader::new(DownloadOptions {
+        show_progress: SHOW_PROGRESS.load(Ordering::Relaxed),
+        progress_events: events::enabled(),
+        verbose: args.is_present("verbose"),
+        retries,
+        proxy: get_proxy_config(args),
+        offline: OFFLINE.load(Ordering::Relaxed),
+        temp_dir: get_temp_dir().map(Path::to_path_buf),
+    })
+    .unwrap_or_else(|e| fail("Proxy", e, GENERAL_EXIT_CODE))
+}
+
+fn get_api<'a>(args: &'a ArgMatches, downloader: &'a Downloader) -> FafApi<'a> {
+    FafApi::new(downloader, args.value_of("api-base-url").unwrap())
+        .with_token(get_api_token(args, downloader))
+}
+
+/// `--api-token`, otherwise the access token of `login`, renewed if it expired.
+/// Without either the API is queried anonymously.
+fn get_api_token(args: &ArgMatches, downloader: &Downloader) -> Option<String> {
+    if let Some(token) = args.value_of("api-token") {
+        return Some(token.to_string());
+    }
+
+    let path = StoredLogin::default_path()?;
+    let login = match StoredLogin::load(&path) {
+        Ok(login) => login?,
+        Err(e) => {
+            eprintln!("Ignoring the stored login: {}", e);
+            return None;
+        }
+    };
+    if !login.is_expired(SystemTime::now()) {
+        return Some(login.access_token);
+    }
+    if OFFLINE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let hydra = Hydra::new(downloader, &login.hydra_base_url, &login.client_id);
+    let refreshed = hydra.refresh(&login).and_then(|refreshed| {
+        refreshed.save(&path)?;
+        Ok(refreshed)
+    });
+
+    match refreshed {
+        Ok(refreshed) => Some(refreshed.access_token),
+        Err(e) => {
+            eprintln!(
+                "Could not renew the stored login, querying the FAF API anonymously (run login again): {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+fn list_profiles() {
+    let profiles = CONFIG
+        .get()
+        .map_or(&[][..], |config| config.executables.as_slice());
+    if profiles.is_empty() {
+        println!("No profiles, add [[executables]] entries to the config file");
+        return;
+    }
+
+    for profile in profiles {
+        let mut rules = Vec::new();
+        if let Some(featured_mod) = &profile.featured_mod {
+            rules.push(format!("featured mod {}", featured_mod));
+        }
+        if let Some(game_version) = &profile.game_version {
+            rules.push(format!("game version containing {}", game_version));
+        }
+        let rules = if rules.is_empty() {
+            "default".to_string()
+        } else {
+            rules.join(" and ")
+        };
+
+        println!("{}: {} ({})", profile.name, profile.path.display(), rules);
+        if let Some(init_file) = &profile.init {
+            println!("  init file {}", init_file);
+        }
+    }
+}
+
+fn run_doctor(matches: &ArgMatches) {
+    use doctor::{Check, Outcome};
+
+    let (given_wrapper, wrapper) = if matches.is_present("proton") {
+        (Some("proton"), find_doctor_proton(matches))
+    } else {
+        let given_wrapper = matches.value_of("wrapper");
+        (
+            given_wrapper,
+            given_wrapper.and_then(resolve_wrapper_program),
+        )
+    };
+    let mut checks = Vec::new();
+
+    checks.push(Check::new(
+        "Executable",
+        match find_doctor_executable(matches) {
+            Ok(executable) => doctor::check_executable(&executable, wrapper.is_some()),
+            Err(reason) => Outcome::Fail(reason),
+        },
+    ));
+    checks.push(Check::new(
+        "Wrapper",
+        doctor::check_wrapper(
+            given_wrapper,
+            wrapper.as_deref(),
+            command_line::find_in_path("wine").as_deref(),
+        ),
+    ));
+    checks.push(Check::new(
+        "Temp dir",
+        doctor::check_temp_dir(get_temp_dir()),
+    ));
+
+    let vault_base_url = matches.value_of("vault-base-url").unwrap();
+    checks.push(Check::new(
+        "Replay server",
+        if OFFLINE.load(Ordering::Relaxed) {
+            Outcome::Warn("not checked, network access is forbidden (--offline)".to_string())
+        } else {
+            // local replays can be watched anyway
+            match get_downloader(matches).check_reachable(vault_base_url) {
+                Ok(_) => Outcome::Pass(format!("{} is reachable", vault_base_url)),
+                Err(e) => Outcome::Warn(format!("{}, only local replays can be watched", e)),
+            }
+        },
+    ));
+
+    let failed = doctor::print_checklist(&checks);
+    if failed > 0 {
+        fail(
+            "Doctor",
+            format!("{} of {} checks failed", failed, checks.len()),
+            GENERAL_EXIT_CODE,
+        )
+    }
+}
+
+/// Like `get_executable_path`, but reports problems instead of exiting
+fn find_doctor_executable(matches: &ArgMatches) -> Result<PathBuf, String> {
+    if let Some(executable) = matches.value_of_os("executable") {
+        return Ok(PathBuf::from(executable));
+    }
+    if !matches.is_present("executable-from-steam") {
+        if let Some(executable) = CONFIG.get().and_then(|config| config.executable.clone()) {
+            return Ok(executable);
+        }
+        let candidates = discovery::candidates();
+        return discovery::find_executable(&candidates).ok_or_else(|| {
+            format!(
+                "none given and not found in {}, use --executable",
+                join_candidates(&candidates)
+            )
+        });
+    }
+
+    let libraries = steam::find_library_folders(&steam::default_steam_dirs());
+    let mut executables = steam::find_executables(&libraries);
+    match executables.len() {
+        1 => Ok(executables.remove(0)),
+        0 => Err("Forged Alliance is not installed in any Steam library".to_string()),
+        _ => Err(format!(
+            "Forged Alliance is installed several times ({})",
+            join_paths(&executables)
+        )),
+    }
+}
+
+/// The `proton` script of `--proton` or the newest Proton, None if there is none
+fn find_doctor_proton(matches: &ArgMatches) -> Option<PathBuf> {
+    let proton_dir = match matches.value_of_os("proton") {
+        Some(proton_dir) => PathBuf::from(proton_dir),
+        None => {
+            let libraries = steam::find_library_folders(&steam::default_steam_dirs());
+            proton::find_newest(&libraries)?
+        }
+    };
+    Some(proton_dir.join("proton")).filter(|proton_script| proton_script.is_file())
+}
+
+/// The first word of `--wrapper` as found on disk or in PATH, None if it isn't found
+fn resolve_wrapper_program(wrapper_str: &str) -> Option<PathBuf> {
+    if Path::new(wrapper_str).is_file() {
+        return Some(PathBuf::from(wrapper_str));
+    }
+    let program = shell_words::split(wrapper_str).ok()?.into_iter().next()?;
+
+    if Path::new(&program).components().count() > 1 {
+        Some(PathBuf::from(program)).filter(|path| path.is_file())
+    } else {
+        command_line::find_in_path(&program)
+    }
+}
+
+fn login(args: &ArgMatches) {
+    let path = StoredLogin::default_path().unwrap_or_else(|| {
+        fail(
+            "Config",
+            "No config directory to store the login in, set XDG_CONFIG_HOME",
+            GENERAL_EXIT_CODE,
+        )
+    });
+    let downloader = get_downloader(args);
+    let hydra = Hydra::new(
+        &downloader,
+        args.value_of("hydra-base-url").unwrap(),
+        args.value_of("client-id").unwrap(),
+    );
+    let exit_on_login_error = |e: io::Error| -> ! {
+        fail(
+            "Login",
+            format!("Could not log in: {}", e),
+            GENERAL_EXIT_CODE,
+        )
+    };
+
+    let authorization = hydra
+        .start_device_login()
+        .unwrap_or_else(|e| exit_on_login_error(e));
+    match &authorization.verification_uri_complete {
+        Some(uri) => eprintln!(
+            "Open {} and confirm the code {}",
+            uri, authorization.user_code
+        ),
+        None => eprintln!(
+            "Open {} and enter the code {}",
+            authorization.verification_uri, authorization.user_code
+        ),
+    }
+
+    let login = hydra
+        .wait_for_login(&authorization)
+        .unwrap_or_else(|e| exit_on_login_error(e));
+    login.save(&path).unwrap_or_else(|e| {
+        fail(
+            "FileAccess",
+            format!("Could not store the login in {}: {}", path.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    println!("Logged in, the login is stored in {}", path.display());
+}
+
+fn load_config(matches: &ArgMatches) -> Config {
+    let required = matches.is_present("config");
+    let config = get_config_path(matches)
+        .map_or(Ok(Config::default()), |path| Config::load(&path, required));
+
+    config.unwrap_or_else(|e| fail("Config", e, GENERAL_EXIT_CODE))
+}
+
+/// `--config` or the default config file
+fn get_config_path(matches: &ArgMatches) -> Option<PathBuf> {
+    matches
+        .value_of_os("config")
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+}
+
+fn get_proxy_config(args: &ArgMatches) -> ProxyConfig {
+    let config_proxy = CONFIG.get().and_then(|config| config.proxy.as_deref());
+
+    ProxyConfig::new(args.value_of("proxy").or(config_proxy))
+        .unwrap_or_else(|e| fail("Proxy", e, GENERAL_EXIT_CODE))
+}
+
+fn get_replay_cache(args: &ArgMatches) -> Option<ReplayCache> {
+    args.value_of("cache-dir")
+        .map(PathBuf::from)
+        .or_else(ReplayCache::default_dir)
+        .map(ReplayCache::new)
+}
+
+fn fetch_vault_replay(args: &ArgMatches, replay_id: u32) -> FetchedReplay {
+    let cache = if args.is_present("no-cache") {
+        None
+    } else {
+        get_replay_cache(args)
+    };
+    let refresh = args.is_present("refresh");
+
+    if let Some(cache) = &cache {
+        if refresh {
+            cache.remove(replay_id);
+        } else if let Some(cached_path) = cache.find(replay_id) {
+            status!("Replay {} served from cache (cache hit)", replay_id);
+            return FetchedReplay::Cached(cached_path);
+        } else if args.is_present("verbose") {
+            eprintln!(
+                "debug: cache miss for replay {} in {}",
+                replay_id,
+                cache.dir().display()
+            );
+        }
+    }
+
+    let vault_base_url = args.value_of("vault-base-url").unwrap();
+    let downloader = get_downloader(args);
+
+    let fetched_replay = match &cache {
+        Some(cache) => fs::create_dir_all(cache.dir())
+            .and_then(|_| {
+                downloader.download_vault_replay_resumable(
+                    vault_base_url,
+                    replay_id,
+                    &cache.part_path(replay_id),
+                    !args.is_present("no-resume") && !refresh,
+                )
+            })
+            .and_then(|_| cache.store_part(replay_id))
+            .map(FetchedReplay::Cached),
+        None => downloader
+            .download_vault_replay(vault_base_url, replay_id)
+            .map(FetchedReplay::Downloaded),
+    }
+    .unwrap_or_else(|e| fail("Download", e, GENERAL_EXIT_CODE));
+
+    status!("Replay {} downloaded from the replay vault", replay_id);
+    fetched_replay
+}
+
+fn manage_cache(args: &ArgMatches) {
+    let cache = get_replay_cache(args).unwrap_or_else(|| {
+        fail(
+            "InvalidArgument",
+            "No cache directory found, please specify one with --cache-dir",
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    let result = match args.subcommand() {
+        ("ls", _) => cache.entries().map(|entries| {
+            let now = SystemTime::now();
+            for entry in entries {
+                let age = now.duration_since(entry.modified).unwrap_or_default();
+                println!(
+                    "{}\t{} bytes\t{} days old",
+                    entry.path.display(),
+                    entry.size,
+                    age.as_secs() / (24 * 60 * 60)
+                );
+            }
+        }),
+        ("clean", Some(clean_args)) => {
+            let older_than = clean_args.value_of("older-than").unwrap();
+            let max_age = cache::parse_age(older_than).unwrap_or_else(|| {
+                fail(
+                    "InvalidArgument",
+                    format!("Invalid age {}, expected e.g. 30d, 12h or 90m", older_than),
+                    GENERAL_EXIT_CODE,
+                )
+            });
+            cache
+                .clean(max_age)
+                .map(|removed| println!("Removed {} cached replays", removed))
+        }
+        ("verify", _) => cache.verify().map(|checked_entries| {
+            let mut corrupt = 0;
+            for (entry, check) in &checked_entries {
+                match check {
+                    EntryCheck::Valid => println!("ok\t{}", entry.path.display()),
+                    EntryCheck::Corrupt => {
+                        corrupt += 1;
+                        println!("corrupt, removed\t{}", entry.path.display());
+                    }
+                    EntryCheck::Unverified => {
+                        println!("no checksum stored\t{}", entry.path.display())
+                    }
+                }
+            }
+            println!(
+                "Verified {} cached replays, {} corrupt",
+                checked_entries.len(),
+                corrupt
+            );
+            if corrupt > 0 {
+                exit(GENERAL_EXIT_CODE)
+            }
+        }),
+        _ => cache
+            .clear()
+            .map(|removed| println!("Removed {} cached replays", removed)),
+    };
+
+    if let Err(e) = result {
+        fail(
+            "Cache",
+            format!("Cache issues in {}: {}", cache.dir().display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    }
+}
+
+/// The running games we launched, exits if they can't be read
+fn list_sessions() -> Vec<Session> {
+    let dir = match paths::sessions_dir() {
+        Some(dir) => dir,
+        None => return Vec::new(),
+    };
+    sessions::list(&dir).unwrap_or_else(|e| {
+        fail(
+            "FileAccess",
+            format!(
+                "Could not read the launched games in {}: {}",
+                dir.display(),
+                e
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    })
+}
+
+fn print_completions(args: &ArgMatches) {
+    let shell = args.value_of("shell").unwrap().parse::<Shell>().unwrap();
+    build_cli().gen_completions_to("faf-replay-cli", shell, &mut io::stdout());
+}
+
+fn show_status() {
+    let sessions = list_sessions();
+    if sessions.is_empty() {
+        println!("No game launched by faf-replay-cli is running");
+    }
+    for session in sessions {
+        println!(
+            "pid {}\treplay {}\trunning for {}",
+            session.pid,
+            session.replay_id,
+            humantime::format_duration(Duration::from_secs(session.uptime().as_secs()))
+        );
+    }
+}
+
+fn stop_games(args: &ArgMatches) {
+    let mut sessions = list_sessions();
+    if let Some(pid) = args.value_of("pid") {
+        let pid: u32 = pid.parse().unwrap_or_else(|_| {
+            fail(
+                "InvalidArgument",
+                format!("Invalid pid {}", pid),
+                GENERAL_EXIT_CODE,
+            )
+        });
+        sessions.retain(|session| session.pid == pid);
+        if sessions.is_empty() {
+            fail(
+                "InvalidArgument",
+                format!("No game launched by faf-replay-cli runs with pid {}", pid),
+                GENERAL_EXIT_CODE,
+            )
+        }
+    } else if sessions.len() > 1 && !args.is_present("all") {
+        let pids: Vec<String> = sessions
+            .iter()
+            .map(|session| session.pid.to_string())
+            .collect();
+        fail(
+            "InvalidArgument",
+            format!(
+                "Several games are running (pids {}), pass a pid or --all",
+                pids.join(", ")
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+    if sessions.is_empty() {
+        println!("No game launched by faf-replay-cli is running");
+        return;
+    }
+
+    for session in sessions {
+        println!(
+            "Stopping the game of replay {} (pid {})",
+            session.replay_id, session.pid
+        );
+        if let Err(e) = stop_session(&session) {
+            fail(
+                "GameLaunch",
+                format!("Could not stop the game (pid {}): {}", session.pid, e),
+                GENERAL_EXIT_CODE,
+            )
+        }
+    }
+}
+
+/// Terminates the game and kills it if it didn't exit in the grace period. Unlike `stop_game`, the game
+/// isn't our child, so whether it exited is polled.
+fn stop_session(session: &Session) -> io::Result<()> {
+    sessions::terminate(session.pid)?;
+
+    let deadline = Instant::now() + TERMINATE_GRACE_PERIOD;
+    while Instant::now() < deadline {
+        if !session.is_running() {
+            return Ok(());
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+
+    eprintln!(
+        "The game is still running after {} seconds, killing it",
+        TERMINATE_GRACE_PERIOD.as_secs()
+    );
+    sessions::kill(session.pid)
+}
+
+/// Starts relaying the live replay at `uri`, with `--save-live` and `--replay-port` of `matches`
+fn start_live_replay_relay(matches: &ArgMatches, uri: &str) -> live::LiveReplayRelay {
+    if OFFLINE.load(Ordering::Relaxed) {
+        fail(
+            "LiveReplay",
+            format!(
+                "Could not start live replay relay: {}",
+                download::OFFLINE_MESSAGE
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    let source = live::parse_live_replay_uri(uri).unwrap_or_else(|| {
+        let message = format!(
+            "Invalid live replay uri {}, expected faflive://<host>/<game id>/<player>.SCFAreplay",
+            uri
+        );
+        fail("InvalidArgument", message, GENERAL_EXIT_CODE)
+    });
+    let port = match matches.value_of("replay-port") {
+        Some(port) => port.parse().unwrap_or_else(|_| {
+            fail(
+                "InvalidArgument",
+                "--replay-port must be a port number",
+                GENERAL_EXIT_CODE,
+            )
+        }),
+        None => 0,
+    };
+    let save_live = matches.value_of("save-live").map(Path::new);
+
+    live::start_relay(source, save_live, port, &get_proxy_config(matches)).unwrap_or_else(|e| {
+        fail(
+            "LiveReplay",
+            format!("Could not start live replay relay: {}", e),
+            GENERAL_EXIT_CODE,
+        )
+    })
+}
+
+fn convert_replay(args: &ArgMatches) {
+    let replay_path = get_replay_path(args);
+    let output_path = Path::new(args.value_of("output").unwrap());
+
+    let metadata_path = args.value_of_os("include-metadata").map(Path::new);
+
+    let prepared_replay = prepare_replay(replay_path).unwrap_or_else(|e| fail_replay(&e));
+    if metadata_path.is_some() && prepared_replay.metadata.is_none() {
+        fail(
+            "InvalidArgument",
+            format!(
+                "{} is a raw replay without metadata, --include-metadata needs a legacy replay",
+                replay_path.display()
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    let raw_replay_path = prepared_replay.location.path();
+
+    let conversion_result = match args.value_of("output-format").unwrap() {
+        "fafreplay" => fs::read(raw_replay_path).and_then(|raw_replay| {
+            let metadata = prepared_replay.metadata.clone().unwrap_or_default();
+            let legacy_replay = encode_legacy_replay(&raw_replay, &metadata)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fs::write(output_path, legacy_replay)
+        }),
+        _ => fs::copy(raw_replay_path, output_path).map(|_| ()),
+    };
+
+    if let Err(e) = conversion_result {
+        fail(
+            "FileAccess",
+            format!("Could not write {}: {}", output_path.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    println!(
+        "Converted {} to {}",
+        replay_path.display(),
+        output_path.display()
+    );
+
+    if let (Some(metadata_path), Some(metadata)) = (metadata_path, &prepared_replay.metadata) {
+        let metadata_json = serde_json::to_string_pretty(metadata).unwrap();
+        fs::write(metadata_path, metadata_json + "\n").unwrap_or_else(|e| {
+            fail(
+                "FileAccess",
+                format!("Could not write {}: {}", metadata_path.display(), e),
+                GENERAL_EXIT_CODE,
+            )
+        });
+        println!("Wrote the metadata to {}", metadata_path.display());
+    }
+}
+
+fn decode_base64_stream(args: &ArgMatches) {
+    let output_path = Path::new(args.value_of("output").unwrap());
+    let base64_stream = match (args.value_of("stream"), args.value_of_os("from-file")) {
+        (Some(stream), _) if stream != "-" => stream.to_string(),
+        (_, Some(stream_path)) => fs::read_to_string(stream_path).unwrap_or_else(|e| {
+            fail(
+                "FileAccess",
+                format!("Could not read {}: {}", Path::new(stream_path).display(), e),
+                GENERAL_EXIT_CODE,
+            )
+        }),
+        _ => io::read_to_string(io::stdin()).unwrap_or_else(|e| {
+            fail(
+                "FileAccess",
+                format!("Could not read the stream from stdin: {}", e),
+                GENERAL_EXIT_CODE,
+            )
+        }),
+    };
+
+    // pasted streams usually end with a line break
+    let base64_stream = base64_stream.trim();
+    let raw_replay = convert_legacy_replay_stream_to_raw_limited(
+        base64_stream,
+        get_temp_dir(),
+        MAX_SIZE.load(Ordering::Relaxed),
+    )
+    .unwrap_or_else(|e| fail_replay(&e));
+
+    if let Err(e) = fs::copy(raw_replay.path(), output_path) {
+        fail(
+            "FileAccess",
+            format!("Could not write {}: {}", output_path.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    }
+    println!("Decoded the stream to {}", output_path.display());
+}
+
+fn print_version_string(matches: &ArgMatches) {
+    let prepared_replay =
+        prepare_replay(get_replay_path(matches)).unwrap_or_else(|e| fail_replay(&e));
+    let header = fs::read(prepared_replay.location.path())
+        .map_err(ReplayError::from)
+        .and_then(|raw_replay| parse_replay_header(&raw_replay))
+        .unwrap_or_else(|e| fail_replay(&e));
+    println!(
+        "{}",
+        info::format_version_string(VERSION, &get_prepared_type(&prepared_replay), &header)
+    );
+}
+
+/// The format of the replay file, also if it was stored gzip-compressed
+fn get_prepared_type(prepared_replay: &PreparedReplay) -> ReplayType {
+    // only legacy replays carry metadata
+    match prepared_replay.metadata {
+        Some(_) => ReplayType::FafLegacy,
+        None => ReplayType::ForgedAlliance,
+    }
+}
+
+fn show_replay_info(matches: &ArgMatches, args: &ArgMatches) {
+    let replay_path = get_replay_path(args);
+
+    if args.is_present("peek") {
+        let peek = peek_replay_file(replay_path).unwrap_or_else(|e| fail_replay(&e));
+        info::print_peek(&peek);
+        return;
+    }
+
+    let prepared_replay = prepare_replay(replay_path).unwrap_or_else(|e| fail_replay(&e));
+
+    let raw_replay_path = prepared_replay.location.path();
+
+    let raw_replay =
+        fs::read(raw_replay_path).unwrap_or_else(|e| fail_replay(&ReplayError::from(e)));
+    let header = parse_replay_header(&raw_replay).unwrap_or_else(|e| fail_replay(&e));
+    // a truncated body is an incomplete replay, the header is all info needs
+    let duration = parse_replay_commands(&raw_replay, header.length)
+        .ok()
+        .and_then(|commands| replay_duration(&commands));
+    let command_count = matches
+        .is_present("verbose")
+        .then(|| count_replay_commands(&raw_replay, header.length));
+    // raw replays carry no date, the file is usually written when the game ends
+    let modified = fs::metadata(replay_path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    let format = args
+        .value_of("format")
+        .map_or(info::InfoFormat::Text, |format| {
+            info::InfoFormat::parse(format).unwrap()
+        });
+    info::print_local_info(
+        &info::LocalInfo {
+            replay_type: get_prepared_type(&prepared_replay),
+            header: &header,
+            metadata: prepared_replay.metadata.as_ref(),
+            modified,
+            duration,
+            executable_build: get_game_version_override(matches).or_else(|| {
+                // only a known executable, looking for it would store it in the config file
+                let executable = matches
+                    .value_of_os("executable")
+                    .map(PathBuf::from)
+                    .or_else(|| CONFIG.get()?.executable.clone())?;
+                game_version::read_executable_build(&executable)
+            }),
+            command_count,
+        },
+        format,
+    );
+
+    if !args.is_present("online") {
+        return;
+    }
+
+    let downloader = get_downloader(args);
+    let api = get_api(args, &downloader);
+    let query = GameQuery {
+        game_id: prepared_replay
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.uid),
+        map_name: header.map_name(),
+        player_names: header
+            .players
+            .iter()
+            .map(|player| player.name.as_str())
+            .collect(),
+        played_before: modified,
+    };
+
+    // the local data is already printed, so a failing lookup is no reason to fail
+    match api.find_game(&query) {
+        Ok(Some(game)) => info::print_online_info(&game, api.base_url()),
+        Ok(None) => eprintln!("\nNo matching game found in the FAF API"),
+        Err(e) => eprintln!(
+            "\nCould not look the game up in the FAF API, showing local data only: {}",
+            e
+        ),
+    }
+}
+
+fn serve_replay(args: &ArgMatches) {
+    let replay_path = get_replay_path(args);
+    let port = args.value_of("port").unwrap().parse().unwrap_or_else(|_| {
+        fail(
+            "InvalidArgument",
+            "--port must be a port number",
+            GENERAL_EXIT_CODE,
+        )
+    });
+    let rate = serve::parse_rate(args.value_of("rate").unwrap()).unwrap_or_else(|| {
+        fail(
+            "InvalidArgument",
+            "--rate must be a positive speed like 1x, 2x or 0.5x",
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    let prepared_replay = prepare_replay(replay_path).unwrap_or_else(|e| fail_replay(&e));
+    let raw_replay_path = prepared_replay.location.path();
+
+    let (raw_replay, header, commands) = fs::read(raw_replay_path)
+        .map_err(ReplayError::from)
+        .and_then(|raw_replay| {
+            let header = parse_replay_header(&raw_replay)?;
+            let commands = parse_replay_commands(&raw_replay, header.length)?;
+            Ok((raw_replay, header, commands))
+        })
+        .unwrap_or_else(|e| fail_replay(&e));
+    let replay_id = prepared_replay
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.uid)
+        .unwrap_or(UNKNOWN_REPLAY_ID);
+
+    let options = serve::ServeOptions {
+        port,
+        rate,
+        burst: args.is_present("burst"),
+    };
+    let server = serve::ReplayServer::bind(raw_replay, header.length, commands, &options)
+        .and_then(|server| Ok((server.shutdown_handle()?, server)));
+    let (shutdown, server) = server.unwrap_or_else(|e| {
+        fail(
+            "Serve",
+            format!("Could not listen on port {}: {}", port, e),
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    if let Err(e) = ctrlc::set_handler(move || {
+        println!("Shutting down");
+        shutdown.shutdown();
+    }) {
+        eprintln!("Could not install the Ctrl+C handler: {}", e);
+    }
+
+    let game_url = serve::get_game_url("<host>", port, replay_id);
+    println!("Serving {} on port {}", replay_path.display(), port);
+    println!("Launch the games with /replay {}", game_url);
+    println!(
+        "or watch with faf-replay-cli -e ... -f {}",
+        live::get_live_replay_uri(&format!("<host>:{}", port), replay_id, "served")
+    );
+    println!("<host> is 127.0.0.1 on this machine, stop serving with Ctrl+C");
+
+    if let Err(e) = server.run() {
+        fail("Serve", format!("Serving failed: {}", e), GENERAL_EXIT_CODE)
+    }
+}
+
+fn search_games(matches: &ArgMatches, args: &ArgMatches) {
+    let since = args.value_of("since");
+    if let Some(since) = since {
+        if humantime::parse_rfc3339_weak(&format!("{} 00:00:00", since)).is_err() {
+            fail(
+                "InvalidArgument",
+                "--since must be a date like 2024-01-01",
+                GENERAL_EXIT_CODE,
+            )
+        }
+    }
+
+    let search = GameSearch {
+        player_name: args.value_of("player").unwrap(),
+        map_name: args.value_of("map"),
+        featured_mod: args.value_of("mod"),
+        since,
+        page_size: parse_positive_number(args, "limit"),
+        page: parse_positive_number(args, "page"),
+    };
+    let watch = args
+        .value_of("watch")
+        .map(|_| parse_positive_number(args, "watch"));
+    // checked up front to not query the API in vain
+    let launch_with = watch.map(|_| get_game_launch(matches));
+
+    let downloader = get_downloader(args);
+    let api = get_api(args, &downloader);
+
+    if args.is_present("verbose") {
+        eprintln!("debug: querying {}", api.get_search_url(&search));
+    }
+
+    let games = api.search_games(&search).unwrap_or_else(|e| {
+        fail(
+            "Api",
+            format!("Could not search the FAF API: {}", e),
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    if let (Some(watch), Some(game_launch)) = (watch, launch_with) {
+        let game = games.get(watch as usize - 1).unwrap_or_else(|| {
+            fail(
+                "NotFound",
+                format!("There is no game {} in the {} results", watch, games.len()),
+                GENERAL_EXIT_CODE,
+            )
+        });
+
+        let fetched_replay = fetch_vault_replay(matches, game.id);
+        let status =
+            launch_replay_file(matches, &game_launch, fetched_replay.path(), Some(game.id));
+        drop(fetched_replay);
+        exit_like_game(&game_launch, status);
+    } else if args.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&games).unwrap());
+    } else if games.is_empty() {
+        println!("No games found");
+    } else {
+        info::print_game_table(&games);
+    }
+}
+
+fn download_latest_game(matches: &ArgMatches, args: &ArgMatches) {
+    let player = args.value_of("player").unwrap();
+    let no_launch = args.is_present("no-launch");
+    let output_path = args.value_of("output").map(Path::new);
+
+    if no_launch && output_path.is_none() && args.is_present("no-cache") {
+        fail(
+            "InvalidArgument",
+            "--no-launch with --no-cache needs --output, the replay would be gone otherwise",
+            GENERAL_EXIT_CODE,
+        )
+    }
+    let launch_with = if no_launch {
+        None
+    } else {
+        Some(get_game_launch(matches))
+    };
+
+    let downloader = get_downloader(args);
+    let api = get_api(args, &downloader);
+    let exit_on_api_error = |e: io::Error| -> ! {
+        fail(
+            "Api",
+            format!("Could not query the FAF API: {}", e),
+            GENERAL_EXIT_CODE,
+        )
+    };
+
+    let player_id = match api.find_player(player) {
+        Ok(Some(player_id)) => player_id,
+        Ok(None) => {
+            let similar_players = api.find_similar_players(player).unwrap_or_default();
+            let message = if similar_players.is_empty() {
+                format!("There is no player named {}", player)
+            } else {
+                format!(
+                    "There is no player named {}, did you mean: {}",
+                    player,
+                    similar_players.join(", ")
+                )
+            };
+            fail("NotFound", message, GENERAL_EXIT_CODE)
+        }
+        Err(e) => exit_on_api_error(e),
+    };
+
+    let latest_game = api
+        .find_latest_game(player_id, false)
+        .unwrap_or_else(|e| exit_on_api_error(e));
+
+    let finished_game = match latest_game {
+        Some(game) if !game.is_finished() => {
+            if let (true, Some(game_launch)) = (args.is_present("live"), &launch_with) {
+                println!(
+                    "{}'s game {} is in progress, spectating it",
+                    player, game.id
+                );
+                let uri =
+                    live::get_live_replay_uri(live::DEFAULT_LIVE_REPLAY_HOST, game.id, player);
+                let relay = start_live_replay_relay(matches, &uri);
+                let replay_id = get_replay_id_override(matches).unwrap_or(relay.game_id);
+                let status = launch_game(game_launch, &relay.game_url, replay_id, None, None);
+                exit_like_game(game_launch, status);
+                return;
+            }
+
+            println!(
+                "{}'s latest game {} is still in progress (use --live to spectate it), \
+                 taking the latest finished one",
+                player, game.id
+            );
+            api.find_latest_game(player_id, true)
+                .unwrap_or_else(|e| exit_on_api_error(e))
+        }
+        game => game,
+    };
+
+    let game = finished_game.unwrap_or_else(|| {
+        fail(
+            "NotFound",
+            format!("{} has no finished games", player),
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    let fetched_replay = fetch_vault_replay(matches, game.id);
+
+    if let Some(output_path) = output_path {
+        // not fs::copy, it would carry over the private permissions of a temp file
+        let copy_result =
+            fs::read(fetched_replay.path()).and_then(|replay| fs::write(output_path, replay));
+        if let Err(e) = copy_result {
+            fail(
+                "FileAccess",
+                format!("Could not write {}: {}", output_path.display(), e),
+                GENERAL_EXIT_CODE,
+            )
+        }
+    }
+
+    match launch_with {
+        Some(game_launch) => {
+            let status =
+                launch_replay_file(matches, &game_launch, fetched_replay.path(), Some(game.id));
+            drop(fetched_replay);
+            exit_like_game(&game_launch, status);
+        }
+        None => {
+            let saved_path = output_path.unwrap_or_else(|| fetched_replay.path());
+            println!("{}", saved_path.display());
+        }
+    }
+}
+
+fn download_replay_list(matches: &ArgMatches, args: &ArgMatches) {
+    let mut replay_ids = Vec::new();
+    for replay_id in args.values_of("ids").into_iter().flatten() {
+        replay_ids.push(replay_id.parse().unwrap_or_else(|_| {
+            fail(
+                "InvalidArgument",
+                format!("{} is no replay id", replay_id),
+                GENERAL_EXIT_CODE,
+            )
+        }));
+    }
+    if let Some(ids_file) = args.value_of("ids-file") {
+        let content = fs::read_to_string(ids_file).unwrap_or_else(|e| {
+            fail(
+                "FileAccess",
+                format!("Could not read {}: {}", ids_file, e),
+                GENERAL_EXIT_CODE,
+            )
+        });
+        replay_ids.extend(bulk::parse_replay_ids(&content).unwrap_or_else(|e| {
+            fail(
+                "InvalidArgument",
+                format!("Invalid ids file {}: {}", ids_file, e),
+                GENERAL_EXIT_CODE,
+            )
+        }));
+    }
+    let mut seen_ids = HashSet::new();
+    replay_ids.retain(|replay_id| seen_ids.insert(*replay_id));
+
+    let delay = args.value_of("delay").unwrap();
+    let options = bulk::BulkOptions {
+        concurrency: parse_positive_number(args, "concurrency") as usize,
+        delay: humantime::parse_duration(delay).unwrap_or_else(|_| {
+            fail(
+                "InvalidArgument",
+                "--delay must be a duration like 500ms or 2s",
+                GENERAL_EXIT_CODE,
+            )
+        }),
+    };
+
+    let out_dir = Path::new(args.value_of("output").unwrap());
+    if let Err(e) = fs::create_dir_all(out_dir) {
+        fail(
+            "FileAccess",
+            format!("Could not create {}: {}", out_dir.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    // the bars of concurrent downloads would garble each other, there is a line per replay
+    SHOW_PROGRESS.store(false, Ordering::Relaxed);
+    let downloader = get_downloader(args);
+    let report = bulk::download_replays(
+        &downloader,
+        matches.value_of("vault-base-url").unwrap(),
+        &replay_ids,
+        out_dir,
+        &options,
+    );
+
+    let failed_path = out_dir.join("failed.txt");
+    let failed_ids: String = report
+        .failed
+        .iter()
+        .map(|(replay_id, _)| format!("{}\n", replay_id))
+        .collect();
+    let failed_result = if failed_ids.is_empty() {
+        // left over from a previous run that is now complete
+        fs::remove_file(&failed_path).or_else(|e| match e.kind() {
+            io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+    } else {
+        fs::write(&failed_path, failed_ids)
+    };
+    if let Err(e) = failed_result {
+        eprintln!("Could not update {}: {}", failed_path.display(), e);
+    }
+
+    println!(
+        "{} downloaded, {} skipped, {} failed",
+        report.downloaded,
+        report.skipped,
+        report.failed.len()
+    );
+    if !report.failed.is_empty() {
+        println!(
+            "Retry the failed replays with --ids-file {}",
+            failed_path.display()
+        );
+        exit(GENERAL_EXIT_CODE)
+    }
+}
+
+fn parse_positive_number(args: &ArgMatches, name: &str) -> u32 {
+    match args.value_of(name).unwrap().parse() {
+        Ok(number) if number > 0 => number,
+        _ => fail(
+            "InvalidArgument",
+            format!("--{} must be a positive number", name),
+            GENERAL_EXIT_CODE,
+        ),
+    }
+}
+
+fn exit_on_download_error(download_result: io::Result<NamedTempFile>) -> NamedTempFile {
+    download_result.unwrap_or_else(|e| fail("Download", e, GENERAL_EXIT_CODE))
+}
+
+/// Launches the game with `replay` being either the path of a raw replay file or the gpgnet url of a
+/// live replay stream. The init file is picked by the featured mod in `metadata`, which is None for
+/// live replays.
+fn launch_game(
+    game_launch: &GameLaunch,
+    replay: &str,
+    replay_id: u32,
+    metadata: Option<&ReplayMetadata>,
+    game_version: Option<&str>,
+) -> GameExit {
+    let matching_profile = find_matching_profile(game_launch, metadata, game_version);
+    let executable = match matching_profile {
+        Some(profile) => {
+            status!("Using the executable of the profile {}", profile.name);
+            check_file_access(&profile.path, "executable", fs::metadata(&profile.path));
+            profile.path.as_path()
+        }
+        None => game_launch.executable.as_path(),
+    };
+    // a bare file name has an empty directory, which is no working directory for the game. Symlinks are kept,
+    // the init files are next to where the FAF client links the executable, not where it points to.
+    let executable = &std::path::absolute(executable).unwrap_or_else(|_| executable.to_path_buf());
+    let profile = matching_profile.or(game_launch.profile);
+    check_game_version(game_launch, executable, game_version);
+    let init_file = choose_init_file(game_launch, executable, profile, metadata);
+    let featured_mod = choose_featured_mod(game_launch, metadata);
+    let executable_dir = executable.parent().unwrap();
+    if game_launch.preflight {
+        check_game_files(executable, &init_file);
+    }
+    let (working_dir, init_file) = match game_launch.working_dir {
+        // the init file is relative to the working directory for the game
+        Some(working_dir) => (
+            working_dir,
+            executable_dir
+                .join(&init_file)
+                .into_os_string()
+                .into_string()
+                .unwrap_or_else(|init_path| {
+                    fail(
+                        "InvalidArgument",
+                        format!(
+                            "The init file path {} is not valid UTF-8",
+                            Path::new(&init_path).display()
+                        ),
+                        GENERAL_EXIT_CODE,
+                    )
+                }),
+        ),
+        None => (executable_dir, init_file),
+    };
+    let init_file = to_game_path(game_launch, init_file);
+    let game_replay = to_game_path(game_launch, replay.to_string());
+
+    // Command quotes each argument on Windows so the game (the MSVC runtime) splits it the same way again, paths
+    // with spaces stay one argument. For `.bat` and `.cmd` wrappers it quotes them for cmd.exe instead.
+    let mut launch_command = match &game_launch.wrapper {
+        Some(wrapper) => {
+            let mut launch_command = Command::new(&wrapper.program);
+            launch_command.args(&wrapper.args).arg(executable);
+            launch_command
+        }
+        None => Command::new(executable),
+    };
+
+    launch_command.args(["/init", &init_file, "/featuredmod", featured_mod]);
+    if !game_launch.bug_report {
+        launch_command.arg("/nobugreport");
+    }
+    launch_command
+        .args(["/replay", &game_replay, "/replayid", &replay_id.to_string()])
+        .args(&game_launch.game_args)
+        .current_dir(working_dir);
+    // after the game arguments, a display mode given in them is left alone
+    let own_display_mode = game_launch.game_args.iter().any(|arg| {
+        arg.eq_ignore_ascii_case("/windowed") || arg.eq_ignore_ascii_case("/fullscreen")
+    });
+    if game_launch.windowed && !own_display_mode {
+        launch_command.arg("/windowed");
+        if let Some((width, height)) = game_launch.window_size {
+            launch_command.args([width.to_string(), height.to_string()]);
+        }
+    }
+
+    if game_launch.env_clear {
+        launch_command.env_clear();
+        for name in &game_launch.env_pass {
+            if let Some(value) = env::var_os(name) {
+                launch_command.env(name, value);
+            }
+        }
+    }
+    launch_command.envs(game_launch.env.iter().map(|(name, value)| (name, value)));
+
+    // before printing, --dry-run shows the command as it is launched
+    let game_log = if game_launch.no_launch && !game_launch.dry_run {
+        None
+    } else {
+        get_game_log_path(game_launch, replay_id)
+    };
+    if let Some(game_log) = &game_log {
+        let game_log_str = game_log.to_str().unwrap_or_else(|| {
+            fail(
+                "InvalidArgument",
+                format!(
+                    "The game log path {} is not valid UTF-8, --no-auto-log launches without it",
+                    game_log.display()
+                ),
+                GENERAL_EXIT_CODE,
+            )
+        });
+        launch_command.args(["/log", &to_game_path(game_launch, game_log_str.to_string())]);
+    }
+
+    if game_launch.dry_run {
+        print_launch_command(&launch_command, game_launch.env_clear);
+        return ExitStatus::default().into();
+    }
+    if game_launch.no_launch {
+        status!(
+            "The replay is prepared at {}, not launching the game",
+            replay
+        );
+        return ExitStatus::default().into();
+    }
+
+    if game_launch.launch_retries > 0 && game_log.is_none() {
+        eprintln!(
+            "Warning: --launch-retries needs the game log faf-replay-cli passes with /log to recognize \
+             transient failures, not retrying"
+        );
+    }
+    let launch_lock = acquire_launch_lock(game_launch);
+    status!("Launching the replay with replay id {}", replay_id);
+    let log_file = game_launch.log_file.map(create_log_file);
+    if game_launch.detach {
+        let pid = match spawn_detached(&mut launch_command, log_file) {
+            Ok(pid) => pid,
+            Err(e) => {
+                drop(launch_lock);
+                fail_launch(&launch_command, e)
+            }
+        };
+        events::emit(&Event::Launching { pid });
+        status!("We launched the game detached (pid {})", pid);
+        if let Some(session) = record_session(pid, replay_id) {
+            session.keep();
+        }
+        if let Some(launch_lock) = launch_lock {
+            if let Err(e) = launch_lock.hand_over(pid) {
+                eprintln!(
+                    "Warning: Could not hand the launch lock over to the game: {}",
+                    e
+                );
+            }
+        }
+        if let Some(game_log) = &game_log {
+            status!("The game writes its log to {}", game_log.display());
+        }
+        return ExitStatus::default().into();
+    }
+
+    // --timeout stops the programs started by the wrapper or the game as well, they keep its output open
+    let own_group = cfg!(unix) && game_launch.timeout.is_some();
+    #[cfg(unix)]
+    if own_group {
+        use std::os::unix::process::CommandExt;
+
+        launch_command.process_group(0);
+    }
+    let tee = game_launch.tee.map(create_tee);
+    install_interrupt_handler();
+    LAUNCH_ACTIVE.store(true, Ordering::SeqCst);
+    let log_file = log_file.map(Mutex::new);
+    let retry_patterns = get_retry_patterns();
+    let mut launch_lock = launch_lock;
+    // why each retried launch was considered transient
+    let mut retries = Vec::new();
+    let game_exit = loop {
+        let log_length = game_log
+            .as_deref()
+            .and_then(|game_log| fs::metadata(game_log).ok())
+            .map_or(0, |metadata| metadata.len() as usize);
+        let interrupts = INTERRUPTS.load(Ordering::SeqCst);
+        let started = Instant::now();
+        let game_exit = run_game(
+            &mut launch_command,
+            game_launch,
+            replay_id,
+            own_group,
+            log_file.as_ref(),
+            tee.as_ref(),
+            &mut launch_lock,
+        );
+        let transient_failure = match &game_log {
+            Some(game_log) if game_launch.launch_retries > 0 => find_transient_failure(
+                game_exit,
+                started.elapsed(),
+                INTERRUPTS.load(Ordering::SeqCst) != interrupts,
+                game_log,
+                log_length,
+                &retry_patterns,
+            ),
+            _ => None,
+        };
+        match transient_failure {
+            Some(reason) if retries.len() < game_launch.launch_retries as usize => {
+                retries.push(reason);
+                eprintln!(
+                    "Warning: The game exited right away ({}), a known transient startup failure. \
+                     Relaunching in {}s, retry {} of {}",
+                    retries.last().unwrap(),
+                    LAUNCH_RETRY_DELAY.as_secs(),
+                    retries.len(),
+                    game_launch.launch_retries
+                );
+                let relaunch_at = Instant::now() + LAUNCH_RETRY_DELAY;
+                while Instant::now() < relaunch_at && !INTERRUPTED.load(Ordering::SeqCst) {
+                    thread::sleep(EXIT_POLL_INTERVAL);
+                }
+                if INTERRUPTED.load(Ordering::SeqCst) {
+                    eprintln!("Interrupted, not relaunching the game");
+                    break game_exit;
+                }
+            }
+            Some(reason) => {
+                retries.push(reason);
+                eprintln!(
+                    "The game failed to start in {} attempts, each time it exited within {}s with a transient \
+                     startup failure in its log:",
+                    retries.len(),
+                    LAUNCH_RETRY_WINDOW.as_secs()
+                );
+                for (attempt, reason) in retries.iter().enumerate() {
+                    eprintln!("  {}. {}", attempt + 1, reason);
+                }
+                break game_exit;
+            }
+            None => {
+                if !retries.is_empty() && !game_exit.status.success() {
+                    eprintln!(
+                        "The game failed in attempt {}, after {} retries of transient startup failures",
+                        retries.len() + 1,
+                        retries.len()
+                    );
+                }
+                break game_exit;
+            }
+        }
+    };
+    // not buffered, everything is written once the game closed its output
+    drop(log_file);
+    drop(tee);
+    drop(launch_lock);
+
+    if let Some(game_log) = &game_log {
+        report_game_log(game_launch, game_log, game_exit.status);
+    }
+    game_exit
+}
+
+/// One launch of the game, waiting for it to exit while its output is shown. The launch lock is released
+/// before failing.
+fn run_game(
+    launch_command: &mut Command,
+    game_launch: &GameLaunch,
+    replay_id: u32,
+    own_group: bool,
+    log_file: Option<&Mutex<fs::File>>,
+    tee: Option<&Tee>,
+    launch_lock: &mut Option<LaunchLock>,
+) -> GameExit {
+    GAME_RUNNING.store(true, Ordering::SeqCst);
+    let spawned = launch_command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            drop(launch_lock.take());
+            fail_launch(launch_command, e)
+        }
+    };
+    events::emit(&Event::Launching { pid: child.id() });
+    status!(
+        "We launched the game (pid {}). Check for errors!",
+        child.id()
+    );
+    let session = record_session(child.id(), replay_id);
+
+    let game_stdout = child.stdout.take().unwrap();
+    let game_stderr = child.stderr.take().unwrap();
+    // shown while the game runs, waiting also keeps the extracted replay until the game exited
+    let game_exit = thread::scope(|scope| {
+        let quiet = game_launch.quiet;
+        let tee_stream = || tee.map(Tee::stream);
+        let (stdout_tee, stderr_tee) = (tee_stream(), tee_stream());
+        scope.spawn(move || {
+            // stdout is kept to the events
+            let to: Option<Box<dyn Write>> = match (quiet, events::enabled()) {
+                (true, _) => None,
+                (false, true) => Some(Box::new(io::stderr())),
+                (false, false) => Some(Box::new(io::stdout())),
+            };
+            forward_output(game_stdout, to, log_file, stdout_tee)
+        });
+        scope.spawn(move || {
+            let to = Some(io::stderr()).filter(|_| !quiet);
+            forward_output(game_stderr, to, log_file, stderr_tee)
+        });
+        let game_exit = wait_for_game(&mut child, game_launch.timeout, own_group);
+        GAME_RUNNING.store(false, Ordering::SeqCst);
+        game_exit
+    });
+    drop(session);
+    let game_exit = game_exit.unwrap_or_else(|e| {
+        drop(launch_lock.take());
+        fail(
+            "GameLaunch",
+            format!("Could not wait for the game to exit: {}", e),
+            GENERAL_EXIT_CODE,
+        )
+    });
+
+    events::emit(&Event::GameExited {
+        code: game_exit.status.code(),
+    });
+    game_exit
+}
+
+/// The line of the game log telling why the launch failed, if it is worth retrying: the game failed within
+/// `LAUNCH_RETRY_WINDOW` on its own, and what it wrote to the log after `log_length` (it may append) shows one
+/// of the `patterns`
+fn find_transient_failure(
+    game_exit: GameExit,
+    runtime: Duration,
+    interrupted: bool,
+    game_log: &Path,
+    log_length: usize,
+    patterns: &[String],
+) -> Option<String> {
+    if game_exit.status.success()
+        || game_exit.timed_out
+        || interrupted
+        || runtime > LAUNCH_RETRY_WINDOW
+    {
+        return None;
+    }
+    let log = game_log::read(game_log).ok()?;
+    // a shorter log was rewritten from the start
+    let log = log.get(log_length..).unwrap_or(&log);
+    game_log::find_transient_failure(log, patterns).map(|line| format!("\"{}\"", line))
+}
+
+/// The built-in transient failures and `launch.retry_patterns` of the config file
+fn get_retry_patterns() -> Vec<String> {
+    let configured = CONFIG
+        .get()
+        .map_or(&[][..], |config| config.launch.retry_patterns.as_slice());
+
+    game_log::TRANSIENT_FAILURES
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(configured.iter().cloned())
+        .collect()
+}
+
+/// `launch.lock` in the cache directory, exits if another replay is watched already. None with `--force`
+/// or if there is no cache directory to lock in.
+fn acquire_launch_lock(game_launch: &GameLaunch) -> Option<LaunchLock> {
+    if game_launch.force {
+        return None;
+    }
+    let path = paths::cache_dir()?.join("launch.lock");
+
+    match LaunchLock::acquire(&path) {
+        Ok(launch_lock) => Some(launch_lock),
+        Err(LockError::Held(owner)) => fail(
+            "AlreadyRunning",
+            format!(
+                "A replay is already being watched (pid {} started at {}), --force launches anyway",
+                owner.pid, owner.started_at
+            ),
+            ALREADY_RUNNING_EXIT_CODE,
+        ),
+        Err(LockError::Io(e)) => {
+            eprintln!(
+                "Warning: Could not create the launch lock {}, not checking for other launches: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Records the game for `stop` and `status`, None if there is no sessions directory or it can't be written
+fn record_session(pid: u32, replay_id: u32) -> Option<SessionRecord> {
+    let dir = paths::sessions_dir()?;
+    SessionRecord::create(&dir, pid, replay_id)
+        .map_err(|e| {
+            eprintln!(
+                "Warning: Could not record the game in {}, `stop` won't find it: {}",
+                dir.display(),
+                e
+            )
+        })
+        .ok()
+}
+
+/// Waits for the game to exit, with a timeout it is stopped once it ran that long. Ctrl+C (or closing the
+/// terminal) stops it as well and kills it the second time, we only return once it exited, so the extracted
+/// replay is still there until then.
+fn wait_for_game(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    own_group: bool,
+) -> io::Result<GameExit> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    // presses during earlier games of --watch-dir don't count
+    let interrupts_before = INTERRUPTS.load(Ordering::SeqCst);
+    let mut handled_interrupts = 0;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status.into());
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            eprintln!(
+                "The game is still running after {} seconds, stopping it",
+                timeout.unwrap().as_secs()
+            );
+            let status = stop_game(child, own_group)?;
+            return Ok(GameExit {
+                status,
+                timed_out: true,
+            });
+        }
+
+        let interrupts = INTERRUPTS.load(Ordering::SeqCst) - interrupts_before;
+        if interrupts > handled_interrupts {
+            if handled_interrupts == 0 {
+                eprintln!("Interrupted, stopping the game (Ctrl+C again kills it)");
+                terminate_game(child, own_group)?;
+            } else {
+                eprintln!("Interrupted again, killing the game");
+                kill_game(child, own_group)?;
+            }
+            handled_interrupts = interrupts;
+        }
+
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
+/// Terminates the game and kills it if it didn't exit in the grace period
+fn stop_game(child: &mut Child, own_group: bool) -> io::Result<ExitStatus> {
+    terminate_game(child, own_group)?;
+
+    let deadline = Instant::now() + TERMINATE_GRACE_PERIOD;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait()? {
+            // programs of the group ignoring SIGTERM would keep the output open
+            if own_group {
+                kill_game(child, own_group)?;
+            }
+            return Ok(status);
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+
+    kill_game(child, own_group)?;
+    child.wait()
+}
+
+/// SIGTERM on unix, to the process group if the game has its own. Windows has no such request for a program
+/// without console, so the game is terminated right away there.
+fn terminate_game(child: &mut Child, own_group: bool) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        signal_game(child, own_group, libc::SIGTERM);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = own_group;
+        child.kill()
+    }
+}
+
+/// SIGKILL on unix, to the process group if the game has its own, TerminateProcess on Windows
+fn kill_game(child: &mut Child, own_group: bool) -> io::Result<()> {
+    #[cfg(unix)]
+    signal_game(child, own_group, libc::SIGKILL);
+    #[cfg(not(unix))]
+    let _ = own_group;
+
+    child.kill()
+}
+
+#[cfg(unix)]
+fn signal_game(child: &Child, own_group: bool, signal: libc::c_int) {
+    // the pid is ours until we waited for the child, the group is named after it
+    let pid = child.id() as libc::pid_t;
+    let target = if own_group { -pid } else { pid };
+    unsafe { libc::kill(target, signal) };
+}
+
+/// Counts Ctrl+C presses while a game runs for `wait_for_game` and notes them in `INTERRUPTED` for the rest of
+/// the launch, otherwise exits right away like without handler. Installed once, for all games of `--watch-dir`.
+fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            if GAME_RUNNING.load(Ordering::SeqCst) {
+                INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+            } else if LAUNCH_ACTIVE.load(Ordering::SeqCst) {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+            } else {
+                // like shells report SIGINT
+                exit(130)
+            }
+        });
+        if let Err(e) = installed {
+            eprintln!("Could not install the Ctrl+C handler: {}", e);
+        }
+    });
+}
+
+/// A new file in the log directory for `/log`, None with `--no-auto-log`, a `/log` in the game arguments
+/// or if the log directory can't be used
+fn get_game_log_path(game_launch: &GameLaunch, replay_id: u32) -> Option<PathBuf> {
+    let own_log = game_launch
+        .game_args
+        .iter()
+        .any(|arg| arg.eq_ignore_ascii_case("/log"));
+    if !game_launch.auto_log || own_log {
+        return None;
+    }
+
+    let keep_logs = CONFIG
+        .get()
+        .and_then(|config| config.keep_logs)
+        .unwrap_or(game_log::DEFAULT_KEEP_LOGS);
+    let log_dir = paths::log_dir()?;
+    // neither creates the log directory nor removes old logs
+    if game_launch.dry_run {
+        return Some(game_log::log_path(&log_dir, replay_id));
+    }
+    game_log::new_log_path(&log_dir, replay_id, keep_logs)
+        .map_err(|e| {
+            eprintln!(
+                "Warning: Could not prepare the log directory {}, launching without a game log: {}",
+                log_dir.display(),
+                e
+            )
+        })
+        .ok()
+}
+
+/// Points to the log, a failed game also gets the end of it shown. Known problems in it are explained
+/// unless `--no-log-analysis` is given.
+fn report_game_log(game_launch: &GameLaunch, game_log: &Path, status: ExitStatus) {
+    let log = match game_log::read(game_log) {
+        Ok(log) => log,
+        Err(_) => return,
+    };
+    status!("The game log is at {}", game_log.display());
+
+    if !status.success() {
+        eprintln!(
+            "Last lines of the game log:\n{}",
+            game_log::tail(&log, game_log::TAIL_LINES)
+        );
+    }
+    if game_launch.log_analysis {
+        let hints = game_log::diagnose(&log);
+        if !hints.is_empty() {
+            eprintln!("Diagnosis:");
+            for hint in hints {
+                eprintln!("  - {}", hint);
+            }
+        }
+    }
+}
+
+/// Exits if the init file or the game data is missing, the game would just exit without saying why.
+/// Both usually mean that `--executable` is the retail game instead of the one of the FAF client.
+fn check_game_files(executable: &Path, init_file: &str) {
+    let executable_dir = executable.parent().unwrap();
+    let executable_name = executable.file_name().unwrap().to_string_lossy();
+
+    if !executable_dir.join(init_file).is_file() {
+        fail(
+            "FileNotFound",
+            format!(
+                "{} not found next to {} in {}, is it the retail game instead of the FAF bin directory \
+                 ({})? --skip-preflight launches anyway",
+                init_file,
+                executable_name,
+                executable_dir.display(),
+                FAF_BIN_DIR_EXAMPLE
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+
+    // e.g. lua.scd of the game, the FAF client adds its .nx2 files next to them
+    let gamedata_dir = executable_dir
+        .parent()
+        .unwrap_or(executable_dir)
+        .join("gamedata");
+    let has_game_data = fs::read_dir(&gamedata_dir).is_ok_and(|entries| {
+        entries.filter_map(|entry| entry.ok()).any(|entry| {
+            let path = entry.path();
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            extension.is_some_and(|extension| {
+                extension.eq_ignore_ascii_case("scd") || extension.eq_ignore_ascii_case("nx2")
+            })
+        })
+    });
+    if !has_game_data {
+        fail(
+            "FileNotFound",
+            format!(
+                "No game data (*.scd or *.nx2 files) found in {}, is {} the executable of an installed game \
+                 ({})? --skip-preflight launches anyway",
+                gamedata_dir.display(),
+                executable.display(),
+                FAF_BIN_DIR_EXAMPLE
+            ),
+            GENERAL_EXIT_CODE,
+        )
+    }
+}
+
+/// An absolute path as the game can open it, on the `Z:` drive if it runs in wine or Proton
+fn to_game_path(game_launch: &GameLaunch, path: String) -> String {
+    if game_launch.windows_paths && path.starts_with('/') {
+        command_line::to_windows_path(Path::new(&path))
+    } else {
+        path
+    }
+}
+
+fn create_log_file(path: &Path) -> fs::File {
+    fs::File::create(path).unwrap_or_else(|e| {
+        fail(
+            "FileAccess",
+            format!("Could not create the log file {}: {}", path.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    })
+}
+
+fn create_tee(path: &Path) -> Tee {
+    Tee::create(path).unwrap_or_else(|e| {
+        fail(
+            "FileAccess",
+            format!("Could not create the --tee file {}: {}", path.display(), e),
+            GENERAL_EXIT_CODE,
+        )
+    })
+}
+
+/// Exits naming the program that couldn't be started, usually the wrapper or the executable is missing
+fn fail_launch(launch_command: &Command, e: io::Error) -> ! {
+    let program = Path::new(launch_command.get_program());
+    let reason = match e.kind() {
+        // a bare program name like `wine` is looked up in PATH
+        io::ErrorKind::NotFound if program.components().count() == 1 => {
+            format!("not found on PATH ({})", e)
+        }
+        io::ErrorKind::NotFound => format!("not found ({})", e),
+        io::ErrorKind::PermissionDenied => format!("permission denied, is it executable? ({})", e),
+        _ => e.to_string(),
+    };
+
+    fail(
+        "GameLaunch",
+        format!(
+            "Could not launch {}: {}\nThe command was: {}",
+            program.display(),
+            reason,
+            command_line::format_command(launch_command)
+        ),
+        GENERAL_EXIT_CODE,
+    )
+}
+
+/// Starts the game independent of our console, so it keeps running when the terminal is closed.
+/// It isn't waited for since we exit right away (init adopts it on unix). Returns its pid.
+fn spawn_detached(launch_command: &mut Command, log_file: Option<fs::File>) -> io::Result<u32> {
+    // nobody forwards the output anymore, the game writes to the log file itself
+    let (stdout, stderr) = match log_file {
+        Some(log_file) => (Stdio::from(log_file.try_clone()?), Stdio::from(log_file)),
+        None => (Stdio::null(), Stdio::null()),
+    };
+    launch_command
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        // a new session has no controlling terminal that could hang up the game
+        unsafe {
+            launch_command.pre_exec(|| match libc::setsid() {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        launch_command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    launch_command.spawn().map(|child| child.id())
+}
+
+/// What `--dry-run` shows instead of launching the game
+fn print_launch_command(launch_command: &Command, env_clear: bool) {
+    if let Some(dir) = launch_command.get_current_dir() {
+        status!("Working directory: {}", dir.display());
+    }
+    status!("Command: {}", command_line::format_command(launch_command));
+
+    let mut envs = launch_command.get_envs().peekable();
+    if env_clear {
+        status!("Environment: cleared, only the following is set");
+    } else if envs.peek().is_none() {
+        status!("Environment: inherited unchanged");
+    }
+    for (name, value) in envs {
+        match value {
+            Some(value) => status!(
+                "Environment: {}={}",
+                name.to_string_lossy(),
+                command_line::quote_posix(&value.to_string_lossy())
+            ),
+            None => status!("Environment: {} removed", name.to_string_lossy()),
+        }
+    }
+}
+
+/// Copies the output of the game as it arrives, without waiting for complete lines, to `to` unless `--quiet`.
+/// The output of stdout and stderr is combined in the `--log-file` and the `--tee` file.
+fn forward_output(
+    mut output: impl io::Read,
+    mut to: Option<impl Write>,
+    mut log_file: Option<&Mutex<fs::File>>,
+    mut tee: Option<TeeStream>,
+) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+
+    loop {
+        // read on with --quiet, a full pipe would block the game
+        let read = match output.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        // e.g. piped into `head`, reading on so the game doesn't block or get killed by SIGPIPE
+        let forwarded = to
+            .as_mut()
+            .map(|to| to.write_all(&buffer[..read]).and_then(|()| to.flush()));
+        if let Some(Err(e)) = forwarded {
+            // eprintln panics if it is stderr that failed
+            let _ = writeln!(
+                io::stderr(),
+                "Could not forward the game output, not forwarding it anymore: {}",
+                e
+            );
+            to = None;
+        }
+
+        // the game keeps running, so the output is still forwarded without log
+        let logged = log_file.map(|log_file| log_file.lock().unwrap().write_all(&buffer[..read]));
+        if let Some(Err(e)) = logged {
+            eprintln!(
+                "Could not write to the log file, not logging anymore: {}",
+                e
+            );
+            log_file = None;
+        }
+        if let Some(Err(e)) = tee.as_mut().map(|tee| tee.write(&buffer[..read])) {
+            eprintln!(
+                "Could not write to the --tee file, not writing to it anymore: {}",
+                e
+            );
+            tee = None;
+        }
+    }
+
+    if let Some(Err(e)) = tee.as_mut().map(TeeStream::finish) {
+        eprintln!("Could not write to the --tee file: {}", e);
+    }
+    Ok(())
 }
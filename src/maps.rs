@@ -0,0 +1,188 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::download::Downloader;
+
+pub const DEFAULT_CONTENT_BASE_URL: &str = "https://content.faforever.com";
+
+/// Maps shipped with the game, they are never in the maps directory
+const OFFICIAL_MAP_PREFIXES: [&str; 2] = ["scmp_", "x1mp_"];
+
+/// The directory custom maps are installed in, one folder per map like `setons_clutch.v0004`
+pub struct MapsDir {
+    dir: PathBuf,
+}
+
+pub enum MapCheck {
+    Installed,
+    Missing,
+    /// Only other versions of the map are installed, e.g. `setons_clutch.v0003`
+    OtherVersions(Vec<String>),
+}
+
+impl MapsDir {
+    pub fn new(dir: PathBuf) -> MapsDir {
+        MapsDir { dir }
+    }
+
+    /// `Documents/My Games/Gas Powered Games/Supreme Commander Forged Alliance/Maps`
+    /// in the user profile on Windows and in the home directory elsewhere, like the FAF client
+    pub fn default_dir() -> Option<PathBuf> {
+        let documents = env::var_os("USERPROFILE")
+            .map(|profile| Path::new(&profile).join("Documents"))
+            .or_else(|| env::var_os("HOME").map(PathBuf::from))?;
+
+        Some(
+            documents
+                .join("My Games")
+                .join("Gas Powered Games")
+                .join("Supreme Commander Forged Alliance")
+                .join("Maps"),
+        )
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Looks for the map folder, map names in replays don't always match the case on disk
+    pub fn check(&self, map_folder: &str) -> io::Result<MapCheck> {
+        if !self.dir.is_dir() {
+            return Ok(MapCheck::Missing);
+        }
+
+        let (map_name, _) = split_map_version(map_folder);
+        let mut other_versions = Vec::new();
+
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let folder = dir_entry?.file_name().to_string_lossy().into_owned();
+
+            if folder.eq_ignore_ascii_case(map_folder) {
+                return Ok(MapCheck::Installed);
+            }
+            if split_map_version(&folder).0.eq_ignore_ascii_case(map_name) {
+                other_versions.push(folder);
+            }
+        }
+
+        if other_versions.is_empty() {
+            Ok(MapCheck::Missing)
+        } else {
+            other_versions.sort();
+            Ok(MapCheck::OtherVersions(other_versions))
+        }
+    }
+
+    /// Downloads `<content base url>/maps/<map folder>.zip` and unpacks it into the maps directory
+    pub fn install(
+        &self,
+        downloader: &Downloader,
+        content_base_url: &str,
+        map_folder: &str,
+    ) -> io::Result<PathBuf> {
+        let url = format!(
+            "{}/maps/{}.zip",
+            content_base_url.trim_end_matches('/'),
+            map_folder
+        );
+        let zip_file = downloader.download_file(&url, ".zip")?;
+
+        fs::create_dir_all(&self.dir)?;
+        // unpacked next to its destination first, so a broken zip leaves no half map behind
+        let unpack_dir = tempfile::tempdir_in(&self.dir)?;
+        let mut archive = zip::ZipArchive::new(zip_file.reopen()?)
+            .map_err(|e| invalid_map_zip(&url, &e.to_string()))?;
+        archive
+            .extract(unpack_dir.path())
+            .map_err(|e| invalid_map_zip(&url, &e.to_string()))?;
+
+        let map_path = self.dir.join(map_folder);
+        let packed_folder = unpack_dir.path().join(map_folder);
+        if packed_folder.is_dir() {
+            fs::rename(packed_folder, &map_path)?;
+        } else {
+            // zips without the map folder hold its content directly
+            fs::rename(unpack_dir.into_path(), &map_path)?;
+        }
+
+        Ok(map_path)
+    }
+}
+
+/// The folder of the map in a scenario path like `/maps/setons_clutch.v0004/setons_clutch.scmap`
+pub fn get_map_folder(map_path: &str) -> Option<&str> {
+    let mut components = map_path.split(['/', '\\']).filter(|part| !part.is_empty());
+
+    match components.next() {
+        Some(maps) if maps.eq_ignore_ascii_case("maps") => components.next(),
+        _ => None,
+    }
+}
+
+pub fn is_official_map(map_folder: &str) -> bool {
+    let map_folder = map_folder.to_ascii_lowercase();
+
+    OFFICIAL_MAP_PREFIXES
+        .iter()
+        .any(|prefix| map_folder.starts_with(prefix))
+}
+
+/// Splits `setons_clutch.v0004` into `setons_clutch` and `v0004`
+pub fn split_map_version(map_folder: &str) -> (&str, Option<&str>) {
+    match map_folder.rsplit_once('.') {
+        Some((name, version))
+            if version.len() > 1
+                && version.starts_with(['v', 'V'])
+                && version[1..].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (name, Some(version))
+        }
+        _ => (map_folder, None),
+    }
+}
+
+fn invalid_map_zip(url: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid map zip from {}: {}", url, reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_map_folder_in_scenario_path() {
+        assert_eq!(
+            get_map_folder("/maps/setons_clutch.v0004/setons_clutch_scenario.lua"),
+            Some("setons_clutch.v0004")
+        );
+        assert_eq!(
+            get_map_folder("/MAPS/SCMP_009/SCMP_009.scmap"),
+            Some("SCMP_009")
+        );
+        assert_eq!(get_map_folder("/textures/foo.dds"), None);
+        assert!(is_official_map("SCMP_009"));
+        assert!(!is_official_map("setons_clutch.v0004"));
+    }
+
+    #[test]
+    fn reports_other_installed_versions_of_a_map() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("Setons_Clutch.v0003")).unwrap();
+        fs::create_dir(dir.path().join("setons_clutch_remake.v0001")).unwrap();
+        let maps_dir = MapsDir::new(dir.path().to_path_buf());
+
+        match maps_dir.check("setons_clutch.v0004").unwrap() {
+            MapCheck::OtherVersions(versions) => assert_eq!(versions, ["Setons_Clutch.v0003"]),
+            _ => panic!("expected the other version to be found"),
+        }
+        assert!(matches!(
+            maps_dir.check("setons_clutch.V0003").unwrap(),
+            MapCheck::Installed
+        ));
+    }
+}
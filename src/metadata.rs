@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The json metadata line of a legacy `.fafreplay` as written by the FAF replay server.
+///
+/// All fields are optional since the metadata changed over the years.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReplayMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub featured_mod: Option<String>,
+    /// All other fields, kept so re-encoding a replay doesn't lose anything
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
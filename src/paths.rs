@@ -0,0 +1,96 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/// Name of the directories the tool keeps its files in
+const APP_NAME: &str = "faf-replay-cli";
+
+/// `$XDG_CONFIG_HOME/faf-replay-cli`, otherwise the usual place per OS:
+/// `~/.config` on Linux, `~/Library/Application Support` on macOS and `%APPDATA%` on Windows
+pub fn config_dir() -> Option<PathBuf> {
+    resolve(env::var_os("XDG_CONFIG_HOME"), || {
+        project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+    })
+}
+
+/// `$XDG_CACHE_HOME/faf-replay-cli`, otherwise the usual place per OS:
+/// `~/.cache` on Linux, `~/Library/Caches` on macOS and `%LOCALAPPDATA%` on Windows
+pub fn cache_dir() -> Option<PathBuf> {
+    resolve(env::var_os("XDG_CACHE_HOME"), || {
+        project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+    })
+}
+
+pub fn config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// `login.json` next to the config file
+pub fn login_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("login.json"))
+}
+
+pub fn replay_cache_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("replays"))
+}
+
+/// The logs the game writes with `/log`
+pub fn log_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("logs"))
+}
+
+/// The games launched by the tool that are still running, for `stop` and `status`
+pub fn sessions_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("sessions"))
+}
+
+/// `--temp-dir` or `$TMPDIR`, None for the system temp dir
+pub fn temp_dir(temp_dir_arg: Option<&OsStr>) -> Option<PathBuf> {
+    temp_dir_arg.map(PathBuf::from).or_else(|| {
+        env::var_os("TMPDIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from_path(PathBuf::from(APP_NAME))
+}
+
+/// The XDG base directory joined with the app name if set, the spec says to ignore
+/// empty and relative values, the platform's directory otherwise
+fn resolve(
+    xdg_home: Option<OsString>,
+    platform_dir: impl FnOnce() -> Option<PathBuf>,
+) -> Option<PathBuf> {
+    xdg_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(APP_NAME))
+        .or_else(platform_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_absolute_xdg_dirs_over_the_platform_dir() {
+        let xdg_home = env::temp_dir().join("xdg");
+        let platform_dir = || Some(env::temp_dir().join("platform").join(APP_NAME));
+
+        assert_eq!(
+            resolve(Some(xdg_home.clone().into()), platform_dir),
+            Some(xdg_home.join(APP_NAME))
+        );
+        assert_eq!(resolve(Some("".into()), platform_dir), platform_dir());
+        assert_eq!(
+            resolve(Some("relative".into()), platform_dir),
+            platform_dir()
+        );
+        assert_eq!(resolve(None, platform_dir), platform_dir());
+        assert_eq!(resolve(None, || None), None);
+    }
+}
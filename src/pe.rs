@@ -0,0 +1,78 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Start of every PE executable, the DOS header
+const MZ_MAGIC: &[u8] = b"MZ";
+/// Start of a Windows shortcut, the size of its header
+const LNK_MAGIC: &[u8] = &[0x4c, 0x00, 0x00, 0x00];
+/// In the version resource of ForgedAlliance.exe and SupremeCommander.exe
+const PRODUCT_NAME: &str = "Supreme Commander";
+
+/// What the `--executable` turned out to be
+#[derive(Debug, PartialEq)]
+pub enum ExeKind {
+    /// A PE executable naming Supreme Commander in its version resource
+    ForgedAlliance,
+    /// Some other PE executable, e.g. the launcher of the FAF client's Java runtime
+    OtherExecutable,
+    /// A Windows shortcut (`.lnk`)
+    Shortcut,
+    /// No Windows executable at all
+    NotExecutable,
+}
+
+/// Looks at the file behind symlinks
+pub fn inspect(path: &Path) -> io::Result<ExeKind> {
+    let content = fs::read(fs::canonicalize(path)?)?;
+    Ok(inspect_content(&content))
+}
+
+fn inspect_content(content: &[u8]) -> ExeKind {
+    if content.starts_with(LNK_MAGIC) {
+        return ExeKind::Shortcut;
+    }
+    if !content.starts_with(MZ_MAGIC) {
+        return ExeKind::NotExecutable;
+    }
+
+    // version resource strings are UTF-16
+    let product_name: Vec<u8> = PRODUCT_NAME
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    if content
+        .windows(product_name.len())
+        .any(|window| window == product_name.as_slice())
+    {
+        ExeKind::ForgedAlliance
+    } else {
+        ExeKind::OtherExecutable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_the_game_from_other_files() {
+        let mut game = b"MZ\x90\x00 version info ".to_vec();
+        game.extend(
+            "Supreme Commander"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+
+        assert_eq!(inspect_content(&game), ExeKind::ForgedAlliance);
+        assert_eq!(
+            inspect_content(b"MZ\x90\x00 javaw"),
+            ExeKind::OtherExecutable
+        );
+        assert_eq!(
+            inspect_content(b"L\x00\x00\x00\x01\x14\x02"),
+            ExeKind::Shortcut
+        );
+        assert_eq!(inspect_content(b"#!/bin/sh\n"), ExeKind::NotExecutable);
+    }
+}
@@ -0,0 +1,148 @@
+use std::cell::Cell;
+use std::io;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+use crate::events::{self, Event};
+
+/// Time between two `downloading` events, a frontend doesn't need one per read
+const EVENT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Progress of a long running step like a download, so every step reports it the same way
+pub trait Progress {
+    fn set_position(&self, position: u64);
+    fn inc(&self, amount: u64);
+    /// Ends the progress with a line summarizing it
+    fn finish(&self);
+}
+
+/// Bytes processed so far on stderr, as a bar if the total is known and as a spinner otherwise
+pub struct ByteProgress {
+    bar: ProgressBar,
+    /// Starts the summary line, e.g. `Downloaded`
+    done: &'static str,
+    started: Instant,
+}
+
+impl ByteProgress {
+    pub fn new(
+        visible: bool,
+        total: Option<u64>,
+        action: &'static str,
+        done: &'static str,
+    ) -> ByteProgress {
+        let bar = match (visible, total) {
+            (false, _) => ProgressBar::hidden(),
+            (true, Some(total)) => ProgressBar::new(total).with_style(style(
+                "{msg} {wide_bar} {bytes}/{total_bytes} ({percent}%, ETA {eta})",
+            )),
+            (true, None) => ProgressBar::new_spinner().with_style(style("{spinner} {msg} {bytes}")),
+        };
+        bar.set_message(action);
+
+        ByteProgress {
+            bar,
+            done,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Progress for ByteProgress {
+    fn set_position(&self, position: u64) {
+        self.bar.set_position(position);
+    }
+
+    fn inc(&self, amount: u64) {
+        self.bar.inc(amount);
+    }
+
+    fn finish(&self) {
+        if self.bar.is_hidden() {
+            return;
+        }
+
+        self.bar.finish_and_clear();
+        eprintln!(
+            "{} {} in {:.1}s",
+            self.done,
+            HumanBytes(self.bar.position()),
+            self.started.elapsed().as_secs_f32()
+        );
+    }
+}
+
+/// A download as `downloading` events of `--progress-format json`, the first one right away and the last one
+/// with the final size
+pub struct DownloadEvents {
+    total: Option<u64>,
+    position: Cell<u64>,
+    last_event: Cell<Option<Instant>>,
+}
+
+impl DownloadEvents {
+    pub fn new(total: Option<u64>) -> DownloadEvents {
+        DownloadEvents {
+            total,
+            position: Cell::new(0),
+            last_event: Cell::new(None),
+        }
+    }
+
+    fn report(&self, always: bool) {
+        let now = Instant::now();
+        let due = self
+            .last_event
+            .get()
+            .is_none_or(|last_event| now - last_event >= EVENT_INTERVAL);
+        if always || due {
+            self.last_event.set(Some(now));
+            events::emit(&Event::Downloading {
+                bytes: self.position.get(),
+                total: self.total,
+            });
+        }
+    }
+}
+
+impl Progress for DownloadEvents {
+    fn set_position(&self, position: u64) {
+        self.position.set(position);
+        self.report(false);
+    }
+
+    fn inc(&self, amount: u64) {
+        self.position.set(self.position.get() + amount);
+        self.report(false);
+    }
+
+    fn finish(&self) {
+        self.report(true);
+    }
+}
+
+/// Counts everything read through it as progress
+pub struct ProgressRead<'a, R> {
+    inner: R,
+    progress: &'a dyn Progress,
+}
+
+impl<'a, R: Read> ProgressRead<'a, R> {
+    pub fn new(inner: R, progress: &'a dyn Progress) -> ProgressRead<'a, R> {
+        ProgressRead { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressRead<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.progress.inc(read as u64);
+        Ok(read)
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).unwrap()
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Steam app id of Forged Alliance, Steam names its Proton prefix after it
+const FORGED_ALLIANCE_APP_ID: &str = "9420";
+
+/// The Proton installation in `steamapps/common` of the libraries with the highest version.
+/// Numbered releases are preferred over `Proton - Experimental`, which has no version.
+pub fn find_newest(libraries: &[PathBuf]) -> Option<PathBuf> {
+    let mut installations: Vec<(Option<Vec<u32>>, PathBuf)> = libraries
+        .iter()
+        .filter_map(|library| fs::read_dir(library.join("steamapps").join("common")).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let dir_name = entry.file_name().to_str()?.to_string();
+            let proton_dir = entry.path();
+            if !dir_name.starts_with("Proton") || !proton_dir.join("proton").is_file() {
+                return None;
+            }
+            Some((parse_version(&dir_name), proton_dir))
+        })
+        .collect();
+
+    // None sorts first, so the highest numbered release is last
+    installations.sort();
+    installations.pop().map(|(_, proton_dir)| proton_dir)
+}
+
+/// The prefix Steam created for Forged Alliance in `steamapps/compatdata` of the libraries
+pub fn find_compat_data(libraries: &[PathBuf]) -> Option<PathBuf> {
+    libraries
+        .iter()
+        .map(|library| {
+            library
+                .join("steamapps")
+                .join("compatdata")
+                .join(FORGED_ALLIANCE_APP_ID)
+        })
+        .find(|compat_data| compat_data.is_dir())
+}
+
+/// `Proton 8.0` gives `[8, 0]`, `Proton 9.0 (Beta)` `[9, 0]`
+fn parse_version(dir_name: &str) -> Option<Vec<u32>> {
+    let version = dir_name.strip_prefix("Proton ")?.split(' ').next()?;
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_proton_versions_numerically() {
+        assert_eq!(parse_version("Proton 9.0 (Beta)"), Some(vec![9, 0]));
+        assert_eq!(parse_version("Proton - Experimental"), None);
+        assert!(parse_version("Proton 10.0") > parse_version("Proton 9.0"));
+        assert!(parse_version("Proton 7.0") > parse_version("Proton - Experimental"));
+    }
+}
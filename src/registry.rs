@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ};
+use winreg::RegKey;
+
+/// Keys under `HKEY_LOCAL_MACHINE` with the installation directory of the game,
+/// the retail one (32 bit, so in `WOW6432Node` on 64 bit Windows) and Steam's uninstall entry
+const GAME_PATH_KEYS: [(&str, &str); 4] = [
+    (
+        r"SOFTWARE\WOW6432Node\THQ\Gas Powered Games\Supreme Commander - Forged Alliance",
+        "InstallationDirectory",
+    ),
+    (
+        r"SOFTWARE\THQ\Gas Powered Games\Supreme Commander - Forged Alliance",
+        "InstallationDirectory",
+    ),
+    (
+        r"SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\Steam App 9420",
+        "InstallLocation",
+    ),
+    (
+        r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Steam App 9420",
+        "InstallLocation",
+    ),
+];
+
+/// The installation directories recorded in the registry, with the key they are from
+pub fn find_game_paths() -> Vec<(String, PathBuf)> {
+    let local_machine = RegKey::predef(HKEY_LOCAL_MACHINE);
+
+    GAME_PATH_KEYS
+        .iter()
+        .filter_map(|(key, value)| {
+            let game_path: String = local_machine
+                .open_subkey_with_flags(key, KEY_READ)
+                .and_then(|subkey| subkey.get_value(value))
+                .ok()?;
+
+            Some((format!(r"HKLM\{}", key), PathBuf::from(game_path)))
+                .filter(|(_, game_path)| !game_path.as_os_str().is_empty())
+        })
+        .collect()
+}
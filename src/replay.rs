@@ -0,0 +1,873 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::bufread::ZlibDecoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use tempfile::NamedTempFile;
+
+use crate::error::ReplayError;
+use crate::metadata::ReplayMetadata;
+
+/// How much of the raw replay is decoded at most to find the first header line
+const PEEK_LENGTH: u64 = 1024;
+
+/// Raw replays start with the game version, e.g. `Supreme Commander v1.50.3701`
+const RAW_REPLAY_MAGIC: &[u8] = b"Supreme Commander v";
+
+/// Prepended to text files by some Windows tools, serde_json fails on it
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// Replays stored gzip-compressed on disk, e.g. `1234.fafreplay.gz` from backup archives
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Most a replay may decompress to unless `PrepareOptions::max_size` says otherwise, far more than
+/// hours of a full game take, so a crafted stream can't expand to gigabytes
+pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Decompressed bytes between two progress reports, also how exact the offset of
+/// a decompression error is
+const DECOMPRESS_CHUNK_LENGTH: usize = 4 * 1024;
+
+/// Newer FAF tooling compresses the stream behind the Qt size prefix with zstd instead of zlib
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+pub enum ReplayType {
+    Unknown,
+    /// The raw replay format created by the Forged Alliance binary
+    ForgedAlliance,
+    /// The legacy replay format from FAForever
+    /// (A json followed by a linebreak and then including the Qt-zipped base64-ed replay stream)
+    FafLegacy,
+}
+
+impl ReplayType {
+    /// The name of the variant, e.g. `FafLegacy`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplayType::Unknown => "Unknown",
+            ReplayType::ForgedAlliance => "ForgedAlliance",
+            ReplayType::FafLegacy => "FafLegacy",
+        }
+    }
+}
+
+impl fmt::Display for ReplayType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+pub enum ReplayLocation<'a> {
+    AtPath(&'a Path),
+    AtTempFile(NamedTempFile),
+    /// A copy extracted to `PrepareOptions::keep_extracted`, stays on disk
+    Extracted(PathBuf),
+}
+
+impl ReplayLocation<'_> {
+    /// Where the raw replay can be read.
+    ///
+    /// A temp file is deleted as soon as the `ReplayLocation` (or the `PreparedReplay` holding it)
+    /// is dropped, so keep it alive as long as the path is used, e.g. until the game exited.
+    /// Take the `NamedTempFile` out of `AtTempFile` and `keep` or `persist` it to keep the file for good.
+    pub fn path(&self) -> &Path {
+        match self {
+            ReplayLocation::AtPath(path) => path,
+            ReplayLocation::AtTempFile(f) => f.path(),
+            ReplayLocation::Extracted(path) => path,
+        }
+    }
+}
+
+pub struct PreparedReplay<'a> {
+    pub location: ReplayLocation<'a>,
+    /// Only legacy FAF replays carry metadata, raw replays have none
+    pub metadata: Option<ReplayMetadata>,
+}
+
+/// Settings for `prepare_replay_file_with_options`
+#[derive(Default)]
+pub struct PrepareOptions<'a> {
+    /// Where the extracted raw replay is written, the system temp dir if None
+    pub temp_dir: Option<&'a Path>,
+    /// Called with the decompressed and the total size in bytes while a legacy replay
+    /// is decompressed, e.g. to show a progress bar
+    pub progress: Option<&'a mut dyn FnMut(u64, u64)>,
+    /// Extracts the raw replay to this file instead of a temp file. In a directory it is named
+    /// `<uid>.scfareplay` (the replay file name without extensions if there is no uid) and reused
+    /// as long as it isn't older than the replay file.
+    pub keep_extracted: Option<&'a Path>,
+    /// Fails with `ReplayError::TrailingData` instead of warning about data after the compressed stream
+    pub strict: bool,
+    /// Called with problems that don't stop the replay from being watched,
+    /// e.g. trailing data after the compressed stream
+    pub warning: Option<&'a mut dyn FnMut(&str)>,
+    /// Fails with `ReplayError::TooLarge` if the replay decompresses to more bytes,
+    /// `DEFAULT_MAX_SIZE` if None
+    pub max_size: Option<u64>,
+}
+
+/// Where extracted replays are written and how large they may get, taken from `PrepareOptions`
+#[derive(Clone, Copy)]
+struct Extraction<'a> {
+    temp_dir: Option<&'a Path>,
+    keep_extracted: Option<&'a Path>,
+    max_size: u64,
+}
+
+impl Extraction<'_> {
+    /// The kept copy of the replay, None if it is extracted to a temp file
+    fn kept_path(&self, replay_path: &Path, uid: Option<u32>) -> Option<PathBuf> {
+        let keep_extracted = self.keep_extracted?;
+        if !keep_extracted.is_dir() {
+            return Some(keep_extracted.to_path_buf());
+        }
+
+        let name = match uid {
+            Some(uid) => uid.to_string(),
+            None => {
+                let file_name = replay_path.file_name()?.to_string_lossy();
+                file_name.split('.').next().unwrap_or_default().to_string()
+            }
+        };
+        Some(keep_extracted.join(format!("{}.scfareplay", name)))
+    }
+
+    /// A copy extracted by an earlier run, only in a directory where it is named after the replay
+    fn find_reusable(&self, replay_path: &Path, kept_path: &Path) -> Option<PathBuf> {
+        if !self.keep_extracted?.is_dir() {
+            return None;
+        }
+
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+        if modified(kept_path).ok()? < modified(replay_path).ok()? {
+            return None;
+        }
+
+        let mut head = Vec::new();
+        fs::File::open(kept_path)
+            .ok()?
+            .take(RAW_REPLAY_MAGIC.len() as u64)
+            .read_to_end(&mut head)
+            .ok()?;
+        check_raw_replay_magic(&head).ok()?;
+
+        Some(kept_path.to_path_buf())
+    }
+
+    fn write(
+        &self,
+        raw_replay: &[u8],
+        kept_path: Option<PathBuf>,
+    ) -> Result<ReplayLocation<'static>, ReplayError> {
+        let kept_path = match kept_path {
+            Some(kept_path) => kept_path,
+            None => {
+                return Ok(ReplayLocation::AtTempFile(write_temp_replay_file(
+                    raw_replay,
+                    self.temp_dir,
+                )?))
+            }
+        };
+
+        // written next to it first, an interrupted extraction is never reused
+        let dir = match kept_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut kept_file = NamedTempFile::new_in(dir)?;
+        kept_file.write_all(raw_replay)?;
+        kept_file.persist(&kept_path).map_err(|e| e.error)?;
+
+        Ok(ReplayLocation::Extracted(kept_path))
+    }
+}
+
+/// A quick look at a replay without decoding it completely
+#[derive(Debug)]
+pub struct ReplayPeek {
+    /// Size of the raw replay, for legacy replays as declared by the Qt size prefix
+    pub decompressed_size: u64,
+    /// The first line of the raw replay header, e.g. `Supreme Commander v1.50.3701`
+    pub first_header_line: String,
+}
+
+/// The extension is matched case-insensitively, browsers save downloads as e.g. `1234.FAFReplay`
+pub fn get_replay_type(file_name: &str) -> ReplayType {
+    match file_name {
+        _ if strip_extension(file_name, ".scfareplay").is_some() => ReplayType::ForgedAlliance,
+        _ if strip_extension(file_name, ".fafreplay").is_some() => ReplayType::FafLegacy,
+        _ => ReplayType::Unknown,
+    }
+}
+
+/// The file name without `extension`, which is matched case-insensitively.
+/// Only the extension is compared, the rest of the name is kept as it is.
+fn strip_extension<'a>(file_name: &'a str, extension: &str) -> Option<&'a str> {
+    let stem_length = file_name.len().checked_sub(extension.len())?;
+
+    match file_name.as_bytes()[stem_length..].eq_ignore_ascii_case(extension.as_bytes()) {
+        // an ascii extension can't start in the middle of a character
+        true => Some(&file_name[..stem_length]),
+        false => None,
+    }
+}
+
+pub fn get_replay_type_from_content(content: &[u8]) -> ReplayType {
+    match content {
+        _ if content.starts_with(b"Supreme Commander") => ReplayType::ForgedAlliance,
+        _ if content
+            .strip_prefix(UTF8_BOM)
+            .unwrap_or(content)
+            .starts_with(b"{") =>
+        {
+            ReplayType::FafLegacy
+        }
+        _ => ReplayType::Unknown,
+    }
+}
+
+pub fn prepare_replay_file(replay_path: &Path) -> Result<PreparedReplay<'_>, ReplayError> {
+    prepare_replay_file_with_options(replay_path, PrepareOptions::default())
+}
+
+/// Like `prepare_replay_file`, with control over the extracted temp file and its progress.
+pub fn prepare_replay_file_with_options<'a>(
+    replay_path: &'a Path,
+    options: PrepareOptions,
+) -> Result<PreparedReplay<'a>, ReplayError> {
+    let file_name = &replay_path.to_string_lossy();
+    let PrepareOptions {
+        temp_dir,
+        progress: mut report_progress,
+        keep_extracted,
+        strict,
+        warning: mut report_warning,
+        max_size,
+    } = options;
+    let extraction = Extraction {
+        temp_dir,
+        keep_extracted,
+        max_size: max_size.unwrap_or(DEFAULT_MAX_SIZE),
+    };
+    let progress = &mut |decompressed, total| {
+        if let Some(report_progress) = report_progress.as_mut() {
+            report_progress(decompressed, total)
+        }
+    };
+    // usually the replay was concatenated with something else by whatever produced it
+    let trailing_data = &mut |trailing_bytes| {
+        if strict {
+            return Err(ReplayError::TrailingData(trailing_bytes));
+        }
+        if let Some(report_warning) = report_warning.as_mut() {
+            report_warning(&format!(
+                "{} bytes of trailing data after the compressed replay stream are ignored",
+                trailing_bytes
+            ));
+        }
+        Ok(())
+    };
+
+    if let Some(inner_file_name) = strip_extension(file_name, ".gz") {
+        return prepare_gzip_replay(
+            replay_path,
+            inner_file_name,
+            extraction,
+            progress,
+            trailing_data,
+        );
+    }
+
+    match get_replay_type(file_name) {
+        ReplayType::Unknown => Err(ReplayError::UnknownFormat),
+        ReplayType::ForgedAlliance => {
+            let mut head = Vec::new();
+            fs::File::open(replay_path)?
+                .take(RAW_REPLAY_MAGIC.len() as u64)
+                .read_to_end(&mut head)?;
+            if head.starts_with(&GZIP_MAGIC) {
+                return prepare_gzip_replay(
+                    replay_path,
+                    file_name,
+                    extraction,
+                    progress,
+                    trailing_data,
+                );
+            }
+            // the game crashes on anything else instead of reporting it
+            check_raw_replay_magic(&head)?;
+
+            Ok(PreparedReplay {
+                location: ReplayLocation::AtPath(replay_path),
+                metadata: None,
+            })
+        }
+        ReplayType::FafLegacy => {
+            let mut content = fs::read(replay_path)?;
+            if content.starts_with(&GZIP_MAGIC) {
+                content = decompress_gzip(&content, extraction.max_size)?;
+            }
+
+            prepare_legacy_content(replay_path, &content, extraction, progress, trailing_data)
+        }
+    }
+}
+
+/// Reads just enough of a replay file to report its decompressed size and first header line,
+/// without writing a temp file.
+pub fn peek_replay_file(replay_path: &Path) -> Result<ReplayPeek, ReplayError> {
+    let mut head = Vec::new();
+
+    let decompressed_size = match get_replay_type(&replay_path.to_string_lossy()) {
+        ReplayType::Unknown => return Err(ReplayError::UnknownFormat),
+        ReplayType::ForgedAlliance => {
+            let file = fs::File::open(replay_path)?;
+            let size = file.metadata()?.len();
+            file.take(PEEK_LENGTH).read_to_end(&mut head)?;
+            size
+        }
+        ReplayType::FafLegacy => {
+            let content = fs::read(replay_path)?;
+            let (_json_metadata, base64_replay_stream) = split_legacy_replay(&content)?;
+            // the header is at the start, a few kilobytes of the stream are plenty to find it
+            let peeked_stream_length =
+                base64_replay_stream.len().min(8 * PEEK_LENGTH as usize) / 4 * 4;
+            let zipped_qt_data = base64::decode_config(
+                &base64_replay_stream[..peeked_stream_length],
+                base64::STANDARD,
+            )
+            .map_err(ReplayError::Base64)?;
+            let (qt_size_prefix, mut zipped_data) = split_qt_size_prefix(&zipped_qt_data)?;
+
+            // stops decompressing once the peeked bytes are there, what was decoded
+            // before the end of a truncated stream is still worth a look
+            let mut decoder = get_stream_decoder(&mut zipped_data)?.take(PEEK_LENGTH);
+            let mut chunk = [0; 64];
+            loop {
+                match decoder.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => head.extend_from_slice(&chunk[..read]),
+                    Err(_) if !head.is_empty() => break,
+                    Err(e) => return Err(ReplayError::Decompress(e)),
+                }
+            }
+            qt_size_prefix as u64
+        }
+    };
+
+    let first_line_end = head
+        .iter()
+        .position(|byte| *byte == 0 || *byte == b'\r' || *byte == b'\n')
+        .unwrap_or(head.len());
+
+    Ok(ReplayPeek {
+        decompressed_size,
+        first_header_line: String::from_utf8_lossy(&head[..first_line_end]).into_owned(),
+    })
+}
+
+pub fn extract_faf_legacy_replay(
+    file_name: &str,
+) -> Result<(NamedTempFile, ReplayMetadata), ReplayError> {
+    let replay_path = Path::new(file_name);
+    let mut content = fs::read(replay_path)?;
+    if content.starts_with(&GZIP_MAGIC) {
+        content = decompress_gzip(&content, DEFAULT_MAX_SIZE)?;
+    }
+    let extraction = Extraction {
+        temp_dir: None,
+        keep_extracted: None,
+        max_size: DEFAULT_MAX_SIZE,
+    };
+
+    let prepared_replay = prepare_legacy_content(
+        replay_path,
+        &content,
+        extraction,
+        &mut |_, _| {},
+        &mut ignore_trailing_data,
+    )?;
+    match prepared_replay {
+        PreparedReplay {
+            location: ReplayLocation::AtTempFile(temp_replay_file),
+            metadata: Some(metadata),
+        } => Ok((temp_replay_file, metadata)),
+        _ => unreachable!("legacy replays are extracted to a temp file unless kept"),
+    }
+}
+
+/// Reuses a kept copy if possible, the metadata is needed anyway and quickly parsed
+fn prepare_legacy_content<'a>(
+    replay_path: &Path,
+    content: &[u8],
+    extraction: Extraction,
+    progress: &mut dyn FnMut(u64, u64),
+    trailing_data: &mut TrailingDataCheck,
+) -> Result<PreparedReplay<'a>, ReplayError> {
+    let (json_metadata, base64_replay_stream) = split_legacy_replay(content)?;
+    let metadata: ReplayMetadata =
+        serde_json::from_str(&json_metadata).map_err(ReplayError::InvalidMetadata)?;
+
+    let kept_path = extraction.kept_path(replay_path, metadata.uid);
+    if let Some(reused_path) = kept_path
+        .as_deref()
+        .and_then(|kept_path| extraction.find_reusable(replay_path, kept_path))
+    {
+        return Ok(PreparedReplay {
+            location: ReplayLocation::Extracted(reused_path),
+            metadata: Some(metadata),
+        });
+    }
+
+    let raw_replay = decode_legacy_replay_stream(
+        &base64_replay_stream,
+        extraction.max_size,
+        progress,
+        trailing_data,
+    )?;
+    check_raw_replay_magic(&raw_replay)?;
+
+    Ok(PreparedReplay {
+        location: extraction.write(&raw_replay, kept_path)?,
+        metadata: Some(metadata),
+    })
+}
+
+/// Decompresses the outer gzip layer and prepares the content like a file named
+/// `inner_file_name`, falling back to its content if that name has no replay extension.
+fn prepare_gzip_replay<'a>(
+    replay_path: &Path,
+    inner_file_name: &str,
+    extraction: Extraction,
+    progress: &mut dyn FnMut(u64, u64),
+    trailing_data: &mut TrailingDataCheck,
+) -> Result<PreparedReplay<'a>, ReplayError> {
+    let content = decompress_gzip(&fs::read(replay_path)?, extraction.max_size)?;
+
+    let replay_type = match get_replay_type(inner_file_name) {
+        ReplayType::Unknown => get_replay_type_from_content(&content),
+        replay_type => replay_type,
+    };
+
+    match replay_type {
+        ReplayType::Unknown => Err(ReplayError::UnknownFormat),
+        ReplayType::ForgedAlliance => {
+            check_raw_replay_magic(&content)?;
+
+            let kept_path = extraction.kept_path(replay_path, None);
+            Ok(PreparedReplay {
+                location: extraction.write(&content, kept_path)?,
+                metadata: None,
+            })
+        }
+        ReplayType::FafLegacy => {
+            prepare_legacy_content(replay_path, &content, extraction, progress, trailing_data)
+        }
+    }
+}
+
+fn decompress_gzip(gzipped: &[u8], max_size: u64) -> Result<Vec<u8>, ReplayError> {
+    let mut content = Vec::new();
+    // what was decompressed before the failure is left in `content`, a byte more tells it is too large
+    let decompressed = MultiGzDecoder::new(gzipped)
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut content);
+    if let Err(e) = decompressed {
+        return Err(ReplayError::Decompress(error_at_offset(
+            e,
+            content.len(),
+            None,
+        )));
+    }
+    if content.len() as u64 > max_size {
+        return Err(ReplayError::TooLarge(max_size));
+    }
+
+    Ok(content)
+}
+
+/// Decodes the full content of a `.fafreplay` file into the raw `.scfareplay` bytes
+/// without touching the filesystem.
+pub fn decode_legacy_replay(bytes: &[u8]) -> Result<Vec<u8>, ReplayError> {
+    let (_json_metadata, base64_replay_stream) = split_legacy_replay(bytes)?;
+
+    decode_legacy_replay_stream(
+        &base64_replay_stream,
+        DEFAULT_MAX_SIZE,
+        &mut |_, _| {},
+        &mut ignore_trailing_data,
+    )
+}
+
+/// Splits a `.fafreplay` into the json metadata and the base64 replay stream.
+///
+/// Usually these are two lines, newer exports store the whole replay in one json object
+/// with the stream in its `data` field instead.
+fn split_legacy_replay(bytes: &[u8]) -> Result<(String, String), ReplayError> {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    let (json_metadata, base64_replay_stream) = match split_json_replay(bytes) {
+        Some(split_replay) => split_replay,
+        None => {
+            let mut lines = bytes.lines();
+
+            let json_metadata = lines.next().ok_or(ReplayError::MissingMetadata)??;
+
+            let base64_replay_stream = lines.next().ok_or(ReplayError::MissingStream)??;
+
+            (json_metadata, base64_replay_stream)
+        }
+    };
+
+    // would decode to an empty stream and fail less clearly later
+    if base64_replay_stream.trim().is_empty() {
+        return Err(ReplayError::MissingStream);
+    }
+    Ok((json_metadata, base64_replay_stream))
+}
+
+fn split_json_replay(bytes: &[u8]) -> Option<(String, String)> {
+    // the two line format ends with the base64 stream, so this spares parsing its json twice
+    if !bytes.trim_ascii_end().ends_with(b"}") {
+        return None;
+    }
+
+    let mut metadata = match serde_json::from_slice(bytes).ok()? {
+        serde_json::Value::Object(metadata) => metadata,
+        _ => return None,
+    };
+    let base64_replay_stream = match metadata.remove("data")? {
+        serde_json::Value::String(base64_replay_stream) => base64_replay_stream,
+        _ => return None,
+    };
+
+    Some((
+        serde_json::Value::Object(metadata).to_string(),
+        base64_replay_stream,
+    ))
+}
+
+/// Decodes the stream into a temp file, failing with `ReplayError::TooLarge` beyond `DEFAULT_MAX_SIZE`
+pub fn convert_legacy_replay_stream_to_raw(
+    base64_stream: &str,
+) -> Result<NamedTempFile, ReplayError> {
+    convert_legacy_replay_stream_to_raw_limited(base64_stream, None, DEFAULT_MAX_SIZE)
+}
+
+/// Like `convert_legacy_replay_stream_to_raw`, but creates the temp file in `temp_dir`,
+/// e.g. if the system temp dir is too small for the replay.
+pub fn convert_legacy_replay_stream_to_raw_in(
+    base64_stream: &str,
+    temp_dir: &Path,
+) -> Result<NamedTempFile, ReplayError> {
+    convert_legacy_replay_stream_to_raw_limited(base64_stream, Some(temp_dir), DEFAULT_MAX_SIZE)
+}
+
+/// Like `convert_legacy_replay_stream_to_raw`, with the temp file in `temp_dir` if given and failing with
+/// `ReplayError::TooLarge` if the stream decompresses to more than `max_size` bytes
+pub fn convert_legacy_replay_stream_to_raw_limited(
+    base64_stream: &str,
+    temp_dir: Option<&Path>,
+    max_size: u64,
+) -> Result<NamedTempFile, ReplayError> {
+    let zipped_qt_data = decode_base64_stream(base64_stream)?;
+    let raw_replay = decode_qt_data(
+        &zipped_qt_data,
+        max_size,
+        &mut |_, _| {},
+        &mut ignore_trailing_data,
+    )?;
+
+    write_temp_replay_file(&raw_replay, temp_dir)
+}
+
+/// Decompresses the base64-decoded replay stream into the raw `.scfareplay` bytes: strips the Qt size prefix
+/// and decompresses the rest with zlib, or zstd for streams of newer FAF servers. Fails with
+/// `ReplayError::TooLarge` beyond `DEFAULT_MAX_SIZE`.
+pub fn qt_zlib_decode(bytes: &[u8]) -> Result<Vec<u8>, ReplayError> {
+    decode_qt_data(
+        bytes,
+        DEFAULT_MAX_SIZE,
+        &mut |_, _| {},
+        &mut ignore_trailing_data,
+    )
+}
+
+/// Encodes raw `.scfareplay` bytes into the full content of a `.fafreplay` file,
+/// the reverse of `decode_legacy_replay`.
+pub fn encode_legacy_replay(
+    raw_replay: &[u8],
+    metadata: &ReplayMetadata,
+) -> Result<Vec<u8>, ReplayError> {
+    let json_metadata = serde_json::to_string(metadata).map_err(ReplayError::InvalidMetadata)?;
+    let base64_replay_stream = convert_raw_replay_to_legacy_stream(raw_replay)?;
+
+    Ok(format!("{}\n{}", json_metadata, base64_replay_stream).into_bytes())
+}
+
+/// Mirrors `convert_legacy_replay_stream_to_raw`: zlib-compresses the raw replay behind the
+/// size prefix Qt's qCompress would write and base64-encodes the result.
+pub fn convert_raw_replay_to_legacy_stream(raw_replay: &[u8]) -> Result<String, ReplayError> {
+    let qt_size_prefix = (raw_replay.len() as u32).to_be_bytes().to_vec();
+
+    let mut encoder = ZlibEncoder::new(qt_size_prefix, Compression::default());
+    encoder.write_all(raw_replay)?;
+    let zipped_qt_data = encoder.finish()?;
+
+    Ok(base64::encode_config(zipped_qt_data, base64::STANDARD))
+}
+
+/// Gets the number of compressed bytes after the end of the stream if there are any,
+/// fails the decoding if it returns an error
+type TrailingDataCheck<'a> = dyn FnMut(usize) -> Result<(), ReplayError> + 'a;
+
+fn ignore_trailing_data(_trailing_bytes: usize) -> Result<(), ReplayError> {
+    Ok(())
+}
+
+fn decode_legacy_replay_stream(
+    base64_stream: &str,
+    max_size: u64,
+    progress: &mut dyn FnMut(u64, u64),
+    trailing_data: &mut TrailingDataCheck,
+) -> Result<Vec<u8>, ReplayError> {
+    let zipped_qt_data = decode_base64_stream(base64_stream)?;
+
+    decode_qt_data(&zipped_qt_data, max_size, progress, trailing_data)
+}
+
+fn decode_base64_stream(base64_stream: &str) -> Result<Vec<u8>, ReplayError> {
+    base64::decode_config(base64_stream, base64::STANDARD).map_err(ReplayError::Base64)
+}
+
+/// `progress` gets the decompressed size so far and the size declared by the Qt size prefix.
+/// Trailing data is only detected for zlib streams.
+fn decode_qt_data(
+    zipped_qt_data: &[u8],
+    max_size: u64,
+    progress: &mut dyn FnMut(u64, u64),
+    trailing_data: &mut TrailingDataCheck,
+) -> Result<Vec<u8>, ReplayError> {
+    let (qt_size_prefix, mut zipped_data) = split_qt_size_prefix(zipped_qt_data)?;
+
+    let is_zstd = zipped_data.starts_with(&ZSTD_MAGIC);
+    let mut decoder = get_stream_decoder(&mut zipped_data)?;
+    let mut output = Vec::new();
+    let mut chunk = vec![0; DECOMPRESS_CHUNK_LENGTH];
+    loop {
+        match decoder.read(&mut chunk) {
+            Ok(0) => break,
+            // before the memory is taken, a tiny crafted stream can expand to gigabytes
+            Ok(read) if (output.len() + read) as u64 > max_size => {
+                return Err(ReplayError::TooLarge(max_size))
+            }
+            Ok(read) => output.extend_from_slice(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(ReplayError::Decompress(error_at_offset(
+                    e,
+                    output.len(),
+                    Some(qt_size_prefix),
+                )))
+            }
+        }
+        progress(output.len() as u64, qt_size_prefix as u64);
+    }
+    drop(decoder);
+
+    if !is_zstd && !zipped_data.is_empty() {
+        trailing_data(zipped_data.len())?;
+    }
+    Ok(output)
+}
+
+fn check_raw_replay_magic(head: &[u8]) -> Result<(), ReplayError> {
+    match head {
+        _ if head.starts_with(RAW_REPLAY_MAGIC) => Ok(()),
+        [] => Err(ReplayError::InvalidHeader("replay is empty".to_string())),
+        _ => Err(ReplayError::InvalidHeader(
+            "replay doesn't start with Supreme Commander v<version>".to_string(),
+        )),
+    }
+}
+
+/// The first 4 bytes are the uncompressed size prepended by Qt's qCompress
+fn split_qt_size_prefix(zipped_qt_data: &[u8]) -> Result<(u32, &[u8]), ReplayError> {
+    if zipped_qt_data.len() < 4 {
+        return Err(ReplayError::Decompress(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stream is shorter than the Qt size prefix",
+        )));
+    }
+    let (qt_size_prefix, zipped_data) = zipped_qt_data.split_at(4);
+
+    Ok((
+        u32::from_be_bytes(qt_size_prefix.try_into().unwrap()),
+        zipped_data,
+    ))
+}
+
+/// Adds how far decompressing got to the error, e.g. to tell a truncated replay from a corrupt one
+fn error_at_offset(e: io::Error, decompressed: usize, total: Option<u32>) -> io::Error {
+    let message = match total {
+        Some(total) => format!(
+            "{} after {} of {} decompressed bytes",
+            e, decompressed, total
+        ),
+        None => format!("{} after {} decompressed bytes", e, decompressed),
+    };
+
+    io::Error::new(e.kind(), message)
+}
+
+/// The zlib decoder stops at the end of its stream and leaves what follows in `zipped_data`
+fn get_stream_decoder<'a>(zipped_data: &'a mut &[u8]) -> Result<Box<dyn Read + 'a>, ReplayError> {
+    if zipped_data.starts_with(&ZSTD_MAGIC) {
+        let decoder =
+            zstd::stream::read::Decoder::new(zipped_data).map_err(ReplayError::Decompress)?;
+        Ok(Box::new(decoder))
+    } else {
+        Ok(Box::new(ZlibDecoder::new(zipped_data)))
+    }
+}
+
+/// Creates the temp file in `temp_dir`, or the system temp dir if None. It ends in `.scfareplay`,
+/// some game builds don't load replays otherwise.
+fn write_temp_replay_file(
+    raw_replay: &[u8],
+    temp_dir: Option<&Path>,
+) -> Result<NamedTempFile, ReplayError> {
+    let mut builder = tempfile::Builder::new();
+    builder.suffix(".scfareplay");
+    let mut temp_replay_file = match temp_dir {
+        Some(temp_dir) => builder.tempfile_in(temp_dir)?,
+        None => builder.tempfile()?,
+    };
+    temp_replay_file.as_file_mut().write_all(raw_replay)?;
+
+    Ok(temp_replay_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_replay_type_by_extension() {
+        assert!(matches!(
+            get_replay_type("21934412.scfareplay"),
+            ReplayType::ForgedAlliance
+        ));
+        assert!(matches!(
+            get_replay_type("/home/alice/replays/21934412.fafreplay"),
+            ReplayType::FafLegacy
+        ));
+    }
+
+    #[test]
+    fn matches_extensions_case_insensitively() {
+        assert!(matches!(
+            get_replay_type("Alice vs Bob.SCFAReplay"),
+            ReplayType::ForgedAlliance
+        ));
+        assert!(matches!(
+            get_replay_type("21934412.FAFReplay"),
+            ReplayType::FafLegacy
+        ));
+    }
+
+    #[test]
+    fn reports_unrelated_names_as_unknown() {
+        for file_name in [
+            "replay.zip",
+            "scfareplay",
+            "21934412.fafreplay.bak",
+            "",
+            "ü",
+        ] {
+            assert!(
+                matches!(get_replay_type(file_name), ReplayType::Unknown),
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_path_of_replays_with_uppercase_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let replay_path = dir.path().join("Alice vs Bob.ScfaReplay");
+        let fixture: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "tests",
+            "fixtures",
+            "example.scfareplay",
+        ]
+        .iter()
+        .collect();
+        fs::copy(fixture, &replay_path).unwrap();
+
+        let prepared_replay = prepare_replay_file(&replay_path).unwrap();
+
+        assert_eq!(prepared_replay.location.path(), replay_path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prepares_replays_with_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let replay_path = dir.path().join(OsStr::from_bytes(b"bad\xff.fafreplay"));
+        let fixture: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "tests",
+            "fixtures",
+            "example.fafreplay",
+        ]
+        .iter()
+        .collect();
+        fs::copy(fixture, &replay_path).unwrap();
+
+        let prepared_replay = prepare_replay_file(&replay_path).unwrap();
+
+        assert!(prepared_replay.metadata.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peeks_into_replays_with_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let replay_path = dir.path().join(OsStr::from_bytes(b"bad\xff.scfareplay"));
+        let fixture: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "tests",
+            "fixtures",
+            "example.scfareplay",
+        ]
+        .iter()
+        .collect();
+        fs::copy(fixture, &replay_path).unwrap();
+
+        let peek = peek_replay_file(&replay_path).unwrap();
+
+        assert!(peek.first_header_line.starts_with("Supreme Commander v"));
+    }
+
+    #[test]
+    fn displays_the_replay_type_by_its_name() {
+        assert_eq!(ReplayType::ForgedAlliance.to_string(), "ForgedAlliance");
+        assert_eq!(ReplayType::FafLegacy.as_str(), "FafLegacy");
+        assert_eq!(format!("{}", ReplayType::Unknown), "Unknown");
+    }
+}
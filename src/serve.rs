@@ -0,0 +1,191 @@
+use std::io;
+use std::io::Write;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use faf_replay_cli::{ReplayCommand, TICK_MILLIS};
+
+use crate::live;
+
+/// How long a client thread sleeps at most before checking for a shutdown
+const MAX_PACING_SLEEP: Duration = Duration::from_millis(200);
+
+pub struct ServeOptions {
+    pub port: u16,
+    /// Playback speed, 2.0 plays the replay twice as fast
+    pub rate: f64,
+    /// Sends the whole replay at once instead of pacing it by tick
+    pub burst: bool,
+}
+
+/// A raw replay streamed to all connected games on a shared clock, so late joiners
+/// catch up to the same point of the game
+struct ServedReplay {
+    raw_replay: Vec<u8>,
+    header_length: usize,
+    commands: Vec<ReplayCommand>,
+    rate: f64,
+    burst: bool,
+    /// Set when the first game connects
+    playback_start: OnceLock<Instant>,
+    shutdown: AtomicBool,
+    clients: Mutex<Vec<TcpStream>>,
+}
+
+/// Handle to stop a running `serve_replay` from another thread, e.g. on Ctrl+C
+#[derive(Clone)]
+pub struct ServeShutdown {
+    replay: Arc<ServedReplay>,
+    port: u16,
+}
+
+impl ServeShutdown {
+    pub fn shutdown(&self) {
+        self.replay.shutdown.store(true, Ordering::SeqCst);
+        // wakes up the blocking accept
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+pub struct ReplayServer {
+    listener: TcpListener,
+    replay: Arc<ServedReplay>,
+}
+
+impl ReplayServer {
+    pub fn bind(
+        raw_replay: Vec<u8>,
+        header_length: usize,
+        commands: Vec<ReplayCommand>,
+        options: &ServeOptions,
+    ) -> io::Result<ReplayServer> {
+        let listener = TcpListener::bind(("0.0.0.0", options.port))?;
+
+        Ok(ReplayServer {
+            listener,
+            replay: Arc::new(ServedReplay {
+                raw_replay,
+                header_length,
+                commands,
+                rate: options.rate,
+                burst: options.burst,
+                playback_start: OnceLock::new(),
+                shutdown: AtomicBool::new(false),
+                clients: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    pub fn shutdown_handle(&self) -> io::Result<ServeShutdown> {
+        Ok(ServeShutdown {
+            replay: Arc::clone(&self.replay),
+            port: self.port()?,
+        })
+    }
+
+    /// Serves connecting games until shut down
+    pub fn run(self) -> io::Result<()> {
+        for connection in self.listener.incoming() {
+            if self.replay.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let game = match connection {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Could not accept connection: {}", e);
+                    continue;
+                }
+            };
+            let address = game.peer_addr()?;
+            println!("Client {} connected", address);
+
+            if let Ok(game_clone) = game.try_clone() {
+                self.replay.clients.lock().unwrap().push(game_clone);
+            }
+
+            let replay = Arc::clone(&self.replay);
+            thread::spawn(move || match stream_to_game(game, &replay) {
+                Ok(()) => println!("Client {} received the whole replay", address),
+                Err(e) => println!("Client {} disconnected ({})", address, e),
+            });
+        }
+
+        // ends the streams of all connected games
+        for client in self.replay.clients.lock().unwrap().iter() {
+            let _ = client.shutdown(Shutdown::Both);
+        }
+
+        Ok(())
+    }
+}
+
+fn stream_to_game(mut game: TcpStream, replay: &ServedReplay) -> io::Result<()> {
+    live::read_request(&mut game)?;
+
+    let playback_start = *replay.playback_start.get_or_init(Instant::now);
+    let mut sent = 0;
+
+    game.write_all(&replay.raw_replay[..replay.header_length])?;
+    sent += replay.header_length;
+
+    loop {
+        if replay.shutdown.load(Ordering::SeqCst) {
+            return Err(io::Error::other("server shut down"));
+        }
+
+        let current_tick = if replay.burst {
+            u32::MAX
+        } else {
+            (playback_start.elapsed().as_millis() as f64 * replay.rate / TICK_MILLIS as f64) as u32
+        };
+
+        // everything issued up to the current tick
+        let due_commands = replay
+            .commands
+            .partition_point(|command| command.tick <= current_tick);
+        let due_end = match due_commands {
+            _ if due_commands == replay.commands.len() => replay.raw_replay.len(),
+            _ => replay.commands[due_commands].offset,
+        };
+
+        if due_end > sent {
+            game.write_all(&replay.raw_replay[sent..due_end])?;
+            sent = due_end;
+        }
+
+        if sent == replay.raw_replay.len() {
+            game.shutdown(Shutdown::Write)?;
+            return Ok(());
+        }
+
+        let next_tick = replay.commands[due_commands].tick;
+        let next_tick_at =
+            Duration::from_millis((next_tick as f64 * TICK_MILLIS as f64 / replay.rate) as u64);
+        let wait = next_tick_at.saturating_sub(playback_start.elapsed());
+        thread::sleep(wait.min(MAX_PACING_SLEEP));
+    }
+}
+
+/// Parses rates like `1x`, `0.5x` or `2`
+pub fn parse_rate(rate: &str) -> Option<f64> {
+    let rate: f64 = rate.strip_suffix('x').unwrap_or(rate).parse().ok()?;
+
+    if rate > 0.0 && rate.is_finite() {
+        Some(rate)
+    } else {
+        None
+    }
+}
+
+/// The gpgnet url games are launched with to watch the served replay
+pub fn get_game_url(host: &str, port: u16, replay_id: u32) -> String {
+    format!("gpgnet://{}:{}/{}/served.SCFAreplay", host, port, replay_id)
+}
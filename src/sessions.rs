@@ -0,0 +1,274 @@
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::launch_lock::is_running;
+
+const SESSION_EXTENSION: &str = "session";
+
+/// A game we launched, as recorded in the sessions directory
+#[derive(Debug, PartialEq)]
+pub struct Session {
+    pub pid: u32,
+    pub replay_id: u32,
+    pub started_at: SystemTime,
+    /// When the OS says the process started, tells the game apart from a later process reusing its pid.
+    /// Only known on Linux and Windows.
+    process_start: Option<u64>,
+}
+
+/// Keeps the session of a game we wait for recorded, removed when dropped
+pub struct SessionRecord {
+    path: PathBuf,
+}
+
+impl Session {
+    /// Whether the process of the session is still the game we launched
+    pub fn is_running(&self) -> bool {
+        is_running(self.pid)
+            && match (self.process_start, process_start(self.pid)) {
+                (Some(recorded), Some(current)) => recorded == current,
+                // a zombie or a process we can't inspect
+                (Some(_), None) => false,
+                (None, _) => true,
+            }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.started_at)
+            .unwrap_or_default()
+    }
+}
+
+impl SessionRecord {
+    /// Records the game with `pid` in `dir` as `<pid>.session`
+    pub fn create(dir: &Path, pid: u32, replay_id: u32) -> io::Result<SessionRecord> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.{}", pid, SESSION_EXTENSION));
+        let session = Session {
+            pid,
+            replay_id,
+            started_at: SystemTime::now(),
+            process_start: process_start(pid),
+        };
+        fs::write(&path, format_session(&session))?;
+
+        Ok(SessionRecord { path })
+    }
+
+    /// Leaves the session recorded after we exited, for detached games. It is removed by `list` once the
+    /// game exited.
+    pub fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for SessionRecord {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The recorded games still running, oldest first. Sessions of games that exited are removed.
+pub fn list(dir: &Path) -> io::Result<Vec<Session>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut sessions = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SESSION_EXTENSION)
+        {
+            continue;
+        }
+        let session = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| parse_session(&content));
+        match session {
+            Some(session) if session.is_running() => sessions.push(session),
+            // exited, or half written and the writer is gone
+            _ => {
+                if let Err(e) = fs::remove_file(&path) {
+                    if e.kind() != ErrorKind::NotFound {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+    sessions.sort_by_key(|session| session.started_at);
+
+    Ok(sessions)
+}
+
+/// `<pid> <replay id> <started at> <process start>`, the process start is `-` if not known
+fn format_session(session: &Session) -> String {
+    format!(
+        "{} {} {} {}\n",
+        session.pid,
+        session.replay_id,
+        humantime::format_rfc3339_seconds(session.started_at),
+        session
+            .process_start
+            .map_or("-".to_string(), |start| start.to_string())
+    )
+}
+
+fn parse_session(content: &str) -> Option<Session> {
+    let mut parts = content.split_whitespace();
+    let pid = parts.next()?.parse().ok()?;
+    let replay_id = parts.next()?.parse().ok()?;
+    let started_at = humantime::parse_rfc3339(parts.next()?).ok()?;
+    let process_start = match parts.next()? {
+        "-" => None,
+        start => Some(start.parse().ok()?),
+    };
+
+    Some(Session {
+        pid,
+        replay_id,
+        started_at,
+        process_start,
+    })
+}
+
+/// Clock ticks after boot the process started at, from `/proc/<pid>/stat`. None for zombies.
+#[cfg(target_os = "linux")]
+fn process_start(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the name before may contain spaces and parentheses, the state comes after its closing one
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    if fields.next()? == "Z" {
+        return None;
+    }
+    // the 22nd field, the state was the 3rd
+    fields.nth(18)?.parse().ok()
+}
+
+/// The creation time of the process
+#[cfg(windows)]
+fn process_start(pid: u32) -> Option<u64> {
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows_sys::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let empty_time = || FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let (mut created, mut exited, mut kernel, mut user) =
+            (empty_time(), empty_time(), empty_time(), empty_time());
+        let read = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user);
+        CloseHandle(process);
+        if read == 0 {
+            return None;
+        }
+        Some((created.dwHighDateTime as u64) << 32 | created.dwLowDateTime as u64)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_start(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Asks the process to exit, SIGTERM on unix. Windows has no such request for a program without console,
+/// it is terminated right away there.
+pub fn terminate(pid: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    return signal(pid, libc::SIGTERM);
+    #[cfg(windows)]
+    return kill(pid);
+}
+
+/// SIGKILL on unix, TerminateProcess on Windows
+pub fn kill(pid: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    return signal(pid, libc::SIGKILL);
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Threading::{
+            OpenProcess, TerminateProcess, PROCESS_TERMINATE,
+        };
+
+        let process = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let terminated = TerminateProcess(process, 1) != 0;
+        let error = io::Error::last_os_error();
+        CloseHandle(process);
+        if terminated {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    // 0 and negative pids would address process groups
+    if pid == 0 || pid > i32::MAX as u32 {
+        return Err(io::Error::from(ErrorKind::InvalidInput));
+    }
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn lists_running_sessions_and_removes_exited_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        // above the highest pid of all systems
+        fs::write(
+            dir.path().join(format!("{}.session", i32::MAX)),
+            format!("{} 1 2024-01-01T10:00:00Z -\n", i32::MAX),
+        )
+        .unwrap();
+
+        let record = SessionRecord::create(dir.path(), process::id(), 22373098).unwrap();
+        let sessions = list(dir.path()).unwrap();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].pid, process::id());
+        assert_eq!(sessions[0].replay_id, 22373098);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        drop(record);
+        assert!(list(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn tells_a_reused_pid_apart() {
+        let mut session =
+            parse_session(&format!("{} 1 2024-01-01T10:00:00Z -\n", process::id())).unwrap();
+        assert!(session.is_running());
+
+        session.process_start = process_start(process::id()).map(|start| start + 1);
+        assert!(!session.is_running());
+    }
+}
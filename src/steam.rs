@@ -0,0 +1,146 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder of Forged Alliance in `steamapps/common`
+const INSTALL_FOLDER: &str = "Supreme Commander Forged Alliance";
+
+/// Executables in the `bin` folder of the installation, the FAF one first
+const EXECUTABLE_NAMES: [&str; 2] = ["ForgedAlliance.exe", "SupremeCommander.exe"];
+
+/// Where Steam is usually installed per OS, only existing directories
+pub fn default_steam_dirs() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    for program_files in ["ProgramFiles(x86)", "ProgramFiles"] {
+        if let Some(dir) = env::var_os(program_files) {
+            candidates.push(Path::new(&dir).join("Steam"));
+        }
+    }
+    if let Some(home) = env::var_os("HOME") {
+        let home = Path::new(&home);
+        candidates.push(home.join(".steam").join("steam"));
+        candidates.push(home.join(".local").join("share").join("Steam"));
+        // the flatpak of Steam
+        candidates.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+        candidates.push(home.join("Library/Application Support/Steam"));
+    }
+
+    unique_dirs(candidates)
+}
+
+/// The library folders listed in `steamapps/libraryfolders.vdf` of the Steam installations,
+/// the installation itself is always one
+pub fn find_library_folders(steam_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut libraries = Vec::new();
+
+    for steam_dir in steam_dirs {
+        libraries.push(steam_dir.clone());
+
+        let vdf_path = steam_dir.join("steamapps").join("libraryfolders.vdf");
+        if let Ok(vdf) = fs::read_to_string(vdf_path) {
+            libraries.extend(parse_library_paths(&vdf));
+        }
+    }
+
+    unique_dirs(libraries)
+}
+
+/// The executable of every Forged Alliance installation in the libraries
+pub fn find_executables(libraries: &[PathBuf]) -> Vec<PathBuf> {
+    bin_dirs(libraries)
+        .iter()
+        .filter_map(|bin_dir| find_executable_in(bin_dir))
+        .collect()
+}
+
+/// Where Forged Alliance would be installed in the libraries
+pub fn bin_dirs(libraries: &[PathBuf]) -> Vec<PathBuf> {
+    libraries
+        .iter()
+        .map(|library| {
+            library
+                .join("steamapps")
+                .join("common")
+                .join(INSTALL_FOLDER)
+                .join("bin")
+        })
+        .collect()
+}
+
+/// The executable in the `bin` folder of an installation
+pub fn find_executable_in(bin_dir: &Path) -> Option<PathBuf> {
+    EXECUTABLE_NAMES
+        .iter()
+        .map(|name| bin_dir.join(name))
+        .find(|executable| executable.is_file())
+}
+
+/// The `"path"` values of `libraryfolders.vdf`, Valve's key value format:
+/// ```text
+/// "libraryfolders"
+/// {
+///     "0"
+///     {
+///         "path"      "C:\\Program Files (x86)\\Steam"
+/// ```
+fn parse_library_paths(vdf: &str) -> Vec<PathBuf> {
+    vdf.lines()
+        .filter_map(|line| {
+            let mut tokens = line.split('"').skip(1).step_by(2);
+            match (tokens.next(), tokens.next()) {
+                (Some(key), Some(value)) if key.eq_ignore_ascii_case("path") => {
+                    Some(PathBuf::from(value.replace("\\\\", "\\")))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Existing directories without duplicates, `~/.steam/steam` is usually a link to another candidate
+fn unique_dirs(dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut unique: Vec<PathBuf> = Vec::new();
+
+    for dir in dirs {
+        if let Ok(dir) = fs::canonicalize(&dir) {
+            if dir.is_dir() && !unique.contains(&dir) {
+                unique.push(dir);
+            }
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_library_paths_of_libraryfolders_vdf() {
+        let vdf = r#""libraryfolders"
+{
+	"0"
+	{
+		"path"		"C:\\Program Files (x86)\\Steam"
+		"label"		""
+		"apps"
+		{
+			"9420"		"3415839294"
+		}
+	}
+	"1"
+	{
+		"path"		"/mnt/games/SteamLibrary"
+	}
+}"#;
+
+        assert_eq!(
+            parse_library_paths(vdf),
+            vec![
+                PathBuf::from("C:\\Program Files (x86)\\Steam"),
+                PathBuf::from("/mnt/games/SteamLibrary")
+            ]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// `--tee`, the output of the game with the time each line was read, stdout and stderr in one file
+pub struct Tee {
+    file: Mutex<fs::File>,
+}
+
+/// The lines of one output of the game, a line is only written once it is complete
+pub struct TeeStream<'a> {
+    tee: &'a Tee,
+    partial_line: Vec<u8>,
+}
+
+impl Tee {
+    pub fn create(path: &Path) -> io::Result<Tee> {
+        Ok(Tee {
+            file: Mutex::new(fs::File::create(path)?),
+        })
+    }
+
+    pub fn stream(&self) -> TeeStream<'_> {
+        TeeStream {
+            tee: self,
+            partial_line: Vec::new(),
+        }
+    }
+
+    /// Unbuffered and in one write, lines of the two outputs don't mix and are on disk even if we crash
+    fn write_lines(&self, lines: &[u8]) -> io::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        self.file.lock().unwrap().write_all(lines)
+    }
+}
+
+impl TeeStream<'_> {
+    pub fn write(&mut self, output: &[u8]) -> io::Result<()> {
+        let lines = timestamp_lines(&mut self.partial_line, output, SystemTime::now());
+        self.tee.write_lines(&lines)
+    }
+
+    /// Writes the last line if the output didn't end with a line break
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.partial_line.is_empty() {
+            return Ok(());
+        }
+        let lines = timestamp_lines(&mut self.partial_line, b"\n", SystemTime::now());
+        self.tee.write_lines(&lines)
+    }
+}
+
+/// The complete lines of `partial_line` followed by `output`, each prefixed with `now`.
+/// The rest after the last line break is left in `partial_line`.
+fn timestamp_lines(partial_line: &mut Vec<u8>, output: &[u8], now: SystemTime) -> Vec<u8> {
+    let timestamp = humantime::format_rfc3339_millis(now).to_string();
+    let mut lines = Vec::new();
+
+    for byte in output {
+        partial_line.push(*byte);
+        if *byte == b'\n' {
+            lines.extend_from_slice(timestamp.as_bytes());
+            lines.push(b' ');
+            lines.append(partial_line);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn prefixes_complete_lines_with_the_time() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let mut partial_line = Vec::new();
+
+        let lines = timestamp_lines(&mut partial_line, b"info: one\ninfo: tw", now);
+        assert_eq!(lines, b"2023-11-14T22:13:20.123Z info: one\n");
+        assert_eq!(partial_line, b"info: tw");
+
+        let lines = timestamp_lines(&mut partial_line, b"o\n", now);
+        assert_eq!(lines, b"2023-11-14T22:13:20.123Z info: two\n");
+        assert!(partial_line.is_empty());
+    }
+}
@@ -0,0 +1,45 @@
+//! Shared by the integration tests with `mod common;`, each test only uses some of it
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect()
+}
+
+pub fn read_fixture(name: &str) -> Vec<u8> {
+    fs::read(fixture_path(name)).unwrap()
+}
+
+/// A shell script standing in for the game as `bin/ForgedAlliance.exe` in `dir`
+#[cfg(unix)]
+pub fn install_fake_game(dir: &Path, script: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin_dir = dir.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let executable = bin_dir.join("ForgedAlliance.exe");
+    fs::write(&executable, script).unwrap();
+    fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+    executable
+}
+
+/// faf-replay-cli with its home, config and cache in `dir`, offline and without checking the game
+pub fn cli_command(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_faf-replay-cli"));
+    command
+        .args([
+            "--skip-exe-check",
+            "--skip-preflight",
+            "--no-map-download",
+            "--offline",
+        ])
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("XDG_CACHE_HOME", dir.join("cache"));
+    command
+}
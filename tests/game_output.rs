@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+extern crate tempfile;
+
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+
+use common::{cli_command, fixture_path, install_fake_game};
+
+/// Stands in for the game, prints far more than a pipe holds
+const GAME_SCRIPT: &str = r#"#!/bin/sh
+i=0
+while [ $i -lt 20000 ]; do echo "line $i"; i=$((i + 1)); done
+"#;
+
+#[test]
+fn keeps_logging_the_game_output_once_stdout_is_closed() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), GAME_SCRIPT);
+    let log_file = dir.path().join("game.log");
+
+    let mut cli = cli_command(dir.path())
+        .arg(fixture_path("example.fafreplay"))
+        .arg("--executable")
+        .arg(&executable)
+        .arg("--log-file")
+        .arg(&log_file)
+        .arg("--no-auto-log")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // like `| head -2`
+    let mut stdout = BufReader::new(cli.stdout.take().unwrap());
+    let mut line = String::new();
+    while !line.starts_with("line 1") {
+        line.clear();
+        assert!(stdout.read_line(&mut line).unwrap() > 0, "no game output");
+    }
+    drop(stdout);
+    let output = cli.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("not forwarding it anymore"), "{}", stderr);
+    assert_eq!(
+        fs::read_to_string(&log_file).unwrap().lines().count(),
+        20000
+    );
+}
@@ -0,0 +1,119 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate tempfile;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{cli_command, fixture_path, install_fake_game};
+
+/// Stands in for the game: notes when it runs and whether the replay is still there once it is stopped
+const GAME_SCRIPT: &str = r#"#!/bin/sh
+marker_dir=$(dirname "$0")
+while [ $# -gt 0 ]; do [ "$1" = /replay ] && replay=$2; shift; done
+trap '[ -f "$replay" ] && echo present > "$marker_dir/terminated"; kill $sleeper; exit 1' TERM
+touch "$marker_dir/started"
+sleep 30 & sleeper=$!
+wait
+"#;
+
+/// Stands in for a game failing to set up its sound device on every start
+const TRANSIENT_FAILURE_SCRIPT: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do [ "$1" = /log ] && log="$2"; shift; done
+echo "warning: DirectSoundCreate failed: 0x88780078" >> "$log"
+echo started >> "$(dirname "$0")/attempts"
+exit 1
+"#;
+
+fn wait_for(path: &Path) {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !path.exists() {
+        assert!(
+            Instant::now() < deadline,
+            "{} never appeared",
+            path.display()
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn ctrl_c_stops_the_game_before_removing_the_extracted_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), GAME_SCRIPT);
+    let bin_dir = executable.parent().unwrap();
+    let temp_dir = dir.path().join("temp");
+    fs::create_dir_all(&temp_dir).unwrap();
+
+    let cli = cli_command(dir.path())
+        .arg(fixture_path("example.fafreplay"))
+        .arg("--executable")
+        .arg(&executable)
+        .arg("--temp-dir")
+        .arg(&temp_dir)
+        .arg("--no-auto-log")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    wait_for(&bin_dir.join("started"));
+    assert_eq!(
+        unsafe { libc::kill(cli.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    let status = cli.wait_with_output().unwrap().status;
+
+    assert_eq!(status.code(), Some(1), "exits like the stopped game");
+    assert_eq!(
+        fs::read_to_string(bin_dir.join("terminated"))
+            .unwrap()
+            .trim(),
+        "present"
+    );
+    assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+}
+
+#[test]
+fn ctrl_c_before_a_relaunch_removes_the_extracted_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), TRANSIENT_FAILURE_SCRIPT);
+    let attempts = executable.with_file_name("attempts");
+    let temp_dir = dir.path().join("temp");
+    fs::create_dir_all(&temp_dir).unwrap();
+
+    let cli = cli_command(dir.path())
+        .arg(fixture_path("example.fafreplay"))
+        .arg("--executable")
+        .arg(&executable)
+        .arg("--temp-dir")
+        .arg(&temp_dir)
+        .args(["--launch-retries", "2"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    wait_for(&attempts);
+    // the game exited, the relaunch is seconds away
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(
+        unsafe { libc::kill(cli.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    let output = cli.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(130), "{}", stderr);
+    assert!(stderr.contains("not relaunching the game"), "{}", stderr);
+    assert_eq!(fs::read_to_string(&attempts).unwrap().lines().count(), 1);
+    assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+}
@@ -0,0 +1,121 @@
+//! The arguments as the game receives them, for paths with spaces, parentheses and non-ASCII characters.
+//! Runs without the test harness, as the test binary is the game too: started with `ECHO_ARGS_TO` set, it
+//! writes its arguments there, one per line, and exits.
+
+extern crate tempfile;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{cli_command, fixture_path};
+
+const ECHO_ARGS_TO: &str = "ECHO_ARGS_TO";
+
+/// Passed on after `--`, with quotes and a trailing backslash for the Windows quoting
+const GAME_ARG: &str = "say \"hi\" to C:\\My Games\\";
+
+fn main() {
+    if let Some(output) = env::var_os(ECHO_ARGS_TO) {
+        let args: Vec<String> = env::args().skip(1).collect();
+        fs::write(output, args.join("\n")).unwrap();
+        return;
+    }
+
+    passes_paths_with_spaces_to_the_game_intact();
+    passes_paths_with_spaces_through_a_wrapper_intact();
+}
+
+/// A copy of this binary as the game, in a directory like a Windows installation
+fn install_game(dir: &Path) -> PathBuf {
+    let bin_dir = dir
+        .join("Program Files (x86)")
+        .join("Gäme (FA)")
+        .join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let executable = bin_dir.join("ForgedAlliance.exe");
+    fs::copy(env::current_exe().unwrap(), &executable).unwrap();
+    executable
+}
+
+/// Launches the example replay with `options` and checks the arguments the game was started with
+fn launch(dir: &Path, options: &[&std::ffi::OsStr]) {
+    let temp_dir = dir.join("My Documents").join("Replays (Zoë)");
+    fs::create_dir_all(&temp_dir).unwrap();
+    let output = dir.join("args.txt");
+
+    let launch = cli_command(dir)
+        .arg(fixture_path("example.fafreplay"))
+        .args(options)
+        .arg("--temp-dir")
+        .arg(&temp_dir)
+        .args(["--no-auto-log", "--quiet", "--", GAME_ARG])
+        .env(ECHO_ARGS_TO, &output)
+        .output()
+        .unwrap();
+    assert!(
+        launch.status.success(),
+        "{}",
+        String::from_utf8_lossy(&launch.stderr)
+    );
+
+    let args: Vec<String> = fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    let replay = &args[args.iter().position(|arg| arg == "/replay").unwrap() + 1];
+    assert!(
+        Path::new(replay).starts_with(&temp_dir),
+        "{} is not in {}",
+        replay,
+        temp_dir.display()
+    );
+    assert!(args.iter().any(|arg| arg == GAME_ARG), "{:?}", args);
+}
+
+fn passes_paths_with_spaces_to_the_game_intact() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_game(dir.path());
+
+    launch(
+        dir.path(),
+        &["--executable".as_ref(), executable.as_os_str()],
+    );
+}
+
+fn passes_paths_with_spaces_through_a_wrapper_intact() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_game(dir.path());
+    let wrapper_dir = dir.path().join("Wrapper (tools) ü");
+    fs::create_dir_all(&wrapper_dir).unwrap();
+
+    #[cfg(unix)]
+    let wrapper = {
+        use std::os::unix::fs::PermissionsExt;
+
+        let wrapper = wrapper_dir.join("wrap.sh");
+        fs::write(&wrapper, "#!/bin/sh\nexec \"$@\"\n").unwrap();
+        fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o755)).unwrap();
+        wrapper
+    };
+    // std quotes the arguments of batch files for cmd.exe, %* passes them on as they were quoted
+    #[cfg(windows)]
+    let wrapper = {
+        let wrapper = wrapper_dir.join("wrap.bat");
+        fs::write(&wrapper, "@%*\r\n").unwrap();
+        wrapper
+    };
+
+    launch(
+        dir.path(),
+        &[
+            "--executable".as_ref(),
+            executable.as_os_str(),
+            "--wrapper".as_ref(),
+            wrapper.as_os_str(),
+        ],
+    );
+}
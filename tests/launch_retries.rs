@@ -0,0 +1,48 @@
+#![cfg(unix)]
+
+extern crate tempfile;
+
+mod common;
+
+use std::fs;
+
+use common::{cli_command, fixture_path, install_fake_game};
+
+/// Stands in for the game, fails to set up its sound device the first time it is started
+const GAME_SCRIPT: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do [ "$1" = /log ] && log="$2"; shift; done
+attempts_file="$(dirname "$0")/attempts"
+attempt=$(( $(cat "$attempts_file" 2>/dev/null || echo 0) + 1 ))
+echo $attempt > "$attempts_file"
+if [ $attempt -eq 1 ]; then
+  echo "warning: DirectSoundCreate failed: 0x88780078" >> "$log"
+  exit 1
+fi
+echo "info: replay loaded" >> "$log"
+"#;
+
+#[test]
+fn relaunches_the_game_after_a_transient_startup_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), GAME_SCRIPT);
+
+    let output = cli_command(dir.path())
+        .arg(fixture_path("example.fafreplay"))
+        .arg("--executable")
+        .arg(&executable)
+        .args(["--launch-retries", "2"])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("DirectSoundCreate failed: 0x88780078"));
+    assert!(stderr.contains("retry 1 of 2"));
+    assert!(!stderr.contains("retry 2 of 2"));
+    assert_eq!(
+        fs::read_to_string(executable.with_file_name("attempts"))
+            .unwrap()
+            .trim(),
+        "2"
+    );
+}
@@ -0,0 +1,352 @@
+extern crate base64;
+extern crate faf_replay_cli;
+extern crate flate2;
+extern crate tempfile;
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use faf_replay_cli::{
+    convert_legacy_replay_stream_to_raw, convert_raw_replay_to_legacy_stream, decode_legacy_replay,
+    encode_legacy_replay, extract_faf_legacy_replay, peek_replay_file, prepare_replay_file,
+    prepare_replay_file_with_options, qt_zlib_decode, PrepareOptions, ReplayLocation,
+    ReplayMetadata,
+};
+
+use common::{fixture_path, read_fixture};
+
+#[test]
+fn legacy_stream_round_trips_byte_for_byte() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let legacy_stream = convert_raw_replay_to_legacy_stream(&raw_replay).unwrap();
+    let temp_replay_file = convert_legacy_replay_stream_to_raw(&legacy_stream).unwrap();
+
+    assert_eq!(fs::read(temp_replay_file.path()).unwrap(), raw_replay);
+}
+
+#[test]
+fn legacy_stream_starts_with_qt_size_prefix() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let legacy_stream = convert_raw_replay_to_legacy_stream(&raw_replay).unwrap();
+    let zipped_qt_data = base64::decode(legacy_stream).unwrap();
+
+    assert_eq!(zipped_qt_data[..4], (raw_replay.len() as u32).to_be_bytes());
+    // zlib header with default compression
+    assert_eq!(zipped_qt_data[4..6], [0x78, 0x9c]);
+}
+
+#[test]
+fn qt_zlib_decode_decompresses_the_base64_decoded_stream() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let legacy_stream = convert_raw_replay_to_legacy_stream(&raw_replay).unwrap();
+    let zipped_qt_data = base64::decode(legacy_stream).unwrap();
+
+    assert_eq!(qt_zlib_decode(&zipped_qt_data).unwrap(), raw_replay);
+    assert_eq!(
+        qt_zlib_decode(&zipped_qt_data[..3]).unwrap_err().code(),
+        "Decompress"
+    );
+}
+
+#[test]
+fn legacy_replay_round_trips_with_metadata() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let metadata = ReplayMetadata {
+        uid: Some(21934412),
+        ..ReplayMetadata::default()
+    };
+
+    let legacy_replay = encode_legacy_replay(&raw_replay, &metadata).unwrap();
+
+    assert!(legacy_replay.starts_with(b"{\"uid\":21934412}\n"));
+    assert_eq!(decode_legacy_replay(&legacy_replay).unwrap(), raw_replay);
+}
+
+#[test]
+fn decodes_zlib_compressed_legacy_replay() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let legacy_replay = read_fixture("example.fafreplay");
+
+    assert_eq!(decode_legacy_replay(&legacy_replay).unwrap(), raw_replay);
+}
+
+#[test]
+fn decodes_zstd_compressed_legacy_replay() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let legacy_replay = read_fixture("example-zstd.fafreplay");
+
+    assert_eq!(decode_legacy_replay(&legacy_replay).unwrap(), raw_replay);
+}
+
+#[test]
+fn decodes_replay_stored_as_single_json_object() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let (temp_replay_file, metadata) =
+        extract_faf_legacy_replay(fixture_path("example-json.fafreplay").to_str().unwrap())
+            .unwrap();
+
+    assert_eq!(fs::read(temp_replay_file.path()).unwrap(), raw_replay);
+    assert_eq!(metadata.uid, Some(21934412));
+    assert!(!metadata.other.contains_key("data"));
+}
+
+#[test]
+fn decodes_replay_with_a_utf8_bom() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let (temp_replay_file, metadata) =
+        extract_faf_legacy_replay(fixture_path("example-bom.fafreplay").to_str().unwrap()).unwrap();
+
+    assert_eq!(fs::read(temp_replay_file.path()).unwrap(), raw_replay);
+    assert_eq!(metadata.uid, Some(21934412));
+}
+
+#[test]
+fn peeks_at_size_and_first_header_line() {
+    let raw_replay_length = read_fixture("example.scfareplay").len() as u64;
+
+    for name in &[
+        "example.scfareplay",
+        "example.fafreplay",
+        "example-zstd.fafreplay",
+        "example-json.fafreplay",
+    ] {
+        let peek = peek_replay_file(&fixture_path(name)).unwrap();
+
+        assert_eq!(peek.decompressed_size, raw_replay_length, "{}", name);
+        assert_eq!(
+            peek.first_header_line, "Supreme Commander v1.50.3701",
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn missing_stream_has_stable_error_code() {
+    let error = decode_legacy_replay(b"{\"uid\": 21934412}\n").unwrap_err();
+
+    assert_eq!(error.code(), "MissingStream");
+    assert_eq!(error.exit_code(), 203);
+}
+
+#[test]
+fn blank_stream_line_is_a_missing_stream() {
+    let mut legacy_replay = tempfile::Builder::new()
+        .suffix(".fafreplay")
+        .tempfile()
+        .unwrap();
+    legacy_replay
+        .write_all(b"{\"uid\": 21934412}\n  \r\n")
+        .unwrap();
+
+    let error = extract_faf_legacy_replay(legacy_replay.path().to_str().unwrap()).unwrap_err();
+
+    assert_eq!(error.code(), "MissingStream");
+    assert_eq!(
+        decode_legacy_replay(b"{\"uid\": 21934412, \"data\": \"\"}")
+            .unwrap_err()
+            .code(),
+        "MissingStream"
+    );
+}
+
+#[test]
+fn reports_how_far_a_truncated_stream_decompressed() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let legacy_stream = convert_raw_replay_to_legacy_stream(&raw_replay).unwrap();
+    let truncated_stream = &legacy_stream[..legacy_stream.len() / 8 * 4];
+
+    let error = convert_legacy_replay_stream_to_raw(truncated_stream).unwrap_err();
+
+    assert_eq!(error.code(), "Decompress");
+    let message = error.to_string();
+    assert!(
+        message.ends_with(&format!("of {} decompressed bytes)", raw_replay.len())),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn reports_decompression_progress_up_to_the_full_size() {
+    let mut reported = Vec::new();
+
+    let options = PrepareOptions {
+        progress: Some(&mut |done, total| reported.push((done, total))),
+        ..PrepareOptions::default()
+    };
+
+    prepare_replay_file_with_options(&fixture_path("example.fafreplay"), options).unwrap();
+
+    let raw_size = read_fixture("example.scfareplay").len() as u64;
+    assert_eq!(reported.last(), Some(&(raw_size, raw_size)));
+}
+
+#[test]
+fn fails_on_replays_decompressing_beyond_the_max_size() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw_replay).unwrap();
+    let mut gzip_file = tempfile::Builder::new()
+        .suffix(".scfareplay.gz")
+        .tempfile()
+        .unwrap();
+    gzip_file.write_all(&encoder.finish().unwrap()).unwrap();
+
+    for replay_path in [
+        fixture_path("example.fafreplay"),
+        gzip_file.path().to_path_buf(),
+    ] {
+        let at_limit = PrepareOptions {
+            max_size: Some(raw_replay.len() as u64),
+            ..PrepareOptions::default()
+        };
+        assert!(prepare_replay_file_with_options(&replay_path, at_limit).is_ok());
+
+        let below = PrepareOptions {
+            max_size: Some(raw_replay.len() as u64 - 1),
+            ..PrepareOptions::default()
+        };
+        let error = prepare_replay_file_with_options(&replay_path, below).err();
+        assert_eq!(error.map(|e| e.code()), Some("TooLarge"));
+    }
+}
+
+#[test]
+fn prepares_gzip_compressed_replays() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    // double-compressed legacy replays and raw replays gzipped without a .gz extension
+    for (fixture, suffix) in [
+        ("example.fafreplay", ".fafreplay.gz"),
+        ("example.fafreplay", ".FAFReplay.GZ"),
+        ("example.scfareplay", ".scfareplay"),
+    ] {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&read_fixture(fixture)).unwrap();
+        let mut gzip_file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        gzip_file.write_all(&encoder.finish().unwrap()).unwrap();
+
+        let prepared_replay = prepare_replay_file(gzip_file.path()).unwrap();
+
+        match &prepared_replay.location {
+            ReplayLocation::AtTempFile(f) => assert_eq!(fs::read(f.path()).unwrap(), raw_replay),
+            _ => panic!("expected the gzip layer to be removed"),
+        }
+        assert_eq!(
+            prepared_replay.metadata.is_some(),
+            fixture.ends_with(".fafreplay")
+        );
+    }
+}
+
+#[test]
+fn location_path_points_at_the_raw_replay() {
+    let raw_replay_path = fixture_path("example.scfareplay");
+    let legacy_replay_path = fixture_path("example.fafreplay");
+
+    let prepared_raw_replay = prepare_replay_file(&raw_replay_path).unwrap();
+    let prepared_legacy_replay = prepare_replay_file(&legacy_replay_path).unwrap();
+
+    assert_eq!(prepared_raw_replay.location.path(), raw_replay_path);
+    // some game builds only load replays ending in .scfareplay
+    assert_eq!(
+        prepared_legacy_replay.location.path().extension().unwrap(),
+        "scfareplay"
+    );
+    assert_eq!(
+        fs::read(prepared_legacy_replay.location.path()).unwrap(),
+        read_fixture("example.scfareplay")
+    );
+}
+
+#[test]
+fn keeps_extracted_replays_named_by_uid_and_reuses_them() {
+    let legacy_replay_path = fixture_path("example.fafreplay");
+    let uid = prepare_replay_file(&legacy_replay_path)
+        .unwrap()
+        .metadata
+        .unwrap()
+        .uid
+        .unwrap();
+    let keep_dir = tempfile::tempdir().unwrap();
+    let kept_path = keep_dir.path().join(format!("{}.scfareplay", uid));
+    let prepare_kept = || {
+        let options = PrepareOptions {
+            keep_extracted: Some(keep_dir.path()),
+            ..PrepareOptions::default()
+        };
+        prepare_replay_file_with_options(&legacy_replay_path, options).unwrap()
+    };
+
+    match prepare_kept().location {
+        ReplayLocation::Extracted(path) => assert_eq!(path, kept_path),
+        _ => panic!("expected the replay to be extracted to the directory"),
+    }
+    assert_eq!(
+        fs::read(&kept_path).unwrap(),
+        read_fixture("example.scfareplay")
+    );
+
+    // marks the kept copy, a reused one isn't extracted again
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&kept_path)
+        .unwrap()
+        .write_all(b"reused")
+        .unwrap();
+    assert_eq!(prepare_kept().location.path(), kept_path);
+    assert!(fs::read(&kept_path).unwrap().ends_with(b"reused"));
+}
+
+#[test]
+fn warns_about_trailing_data_after_the_stream_or_fails_if_strict() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let mut zipped_qt_data =
+        base64::decode(convert_raw_replay_to_legacy_stream(&raw_replay).unwrap()).unwrap();
+    zipped_qt_data.extend_from_slice(b"concatenated");
+    let mut legacy_replay = tempfile::Builder::new()
+        .suffix(".fafreplay")
+        .tempfile()
+        .unwrap();
+    writeln!(
+        legacy_replay,
+        "{{\"uid\": 1}}\n{}",
+        base64::encode(&zipped_qt_data)
+    )
+    .unwrap();
+
+    let mut warnings = Vec::new();
+    let options = PrepareOptions {
+        warning: Some(&mut |warning| warnings.push(warning.to_string())),
+        ..PrepareOptions::default()
+    };
+    let prepared_replay = prepare_replay_file_with_options(legacy_replay.path(), options).unwrap();
+    assert_eq!(
+        fs::read(prepared_replay.location.path()).unwrap(),
+        raw_replay
+    );
+    assert_eq!(
+        warnings,
+        ["12 bytes of trailing data after the compressed replay stream are ignored"]
+    );
+
+    let strict = PrepareOptions {
+        strict: true,
+        ..PrepareOptions::default()
+    };
+    let error = prepare_replay_file_with_options(legacy_replay.path(), strict).err();
+    assert_eq!(error.map(|e| e.code()), Some("TrailingData"));
+}
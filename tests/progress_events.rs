@@ -0,0 +1,45 @@
+#![cfg(unix)]
+
+extern crate serde_json;
+extern crate tempfile;
+
+mod common;
+
+use serde_json::Value;
+
+use common::{cli_command, fixture_path, install_fake_game};
+
+/// Stands in for the game, its output must not end up between the events
+const GAME_SCRIPT: &str = "#!/bin/sh\necho 'info: loading the replay'\n";
+
+#[test]
+fn reports_the_stages_of_a_successful_launch_as_json_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), GAME_SCRIPT);
+
+    let output = cli_command(dir.path())
+        .arg(fixture_path("example.fafreplay"))
+        .arg("--executable")
+        .arg(&executable)
+        .args(["--progress-format", "json", "--no-auto-log"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let events: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let names: Vec<&str> = events
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["detecting", "extracting", "launching", "game_exited"]
+    );
+    assert!(events[2]["pid"].as_u64().unwrap() > 0);
+    assert_eq!(events[3]["code"], 0);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("info: loading the replay"));
+}
@@ -0,0 +1,76 @@
+extern crate faf_replay_cli;
+
+mod common;
+
+use std::time::Duration;
+
+use faf_replay_cli::{
+    count_replay_commands, parse_replay_commands, parse_replay_header, replay_duration,
+    ReplayError, ADVANCE_COMMAND,
+};
+
+use common::read_fixture;
+
+#[test]
+fn splits_body_into_commands_with_ticks() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let header = parse_replay_header(&raw_replay).unwrap();
+
+    let commands = parse_replay_commands(&raw_replay, header.length).unwrap();
+
+    assert_eq!(commands[0].offset, header.length);
+    assert_eq!(commands[0].tick, 0);
+    let last_command = commands.last().unwrap();
+    assert_eq!(last_command.offset + last_command.length, raw_replay.len());
+    assert!(commands
+        .windows(2)
+        .all(|pair| pair[0].offset + pair[0].length == pair[1].offset));
+    assert!(commands
+        .iter()
+        .any(|command| command.command_type == ADVANCE_COMMAND));
+    assert!(last_command.tick > 0);
+}
+
+#[test]
+fn duration_is_only_known_for_finished_replays() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let header = parse_replay_header(&raw_replay).unwrap();
+    let mut commands = parse_replay_commands(&raw_replay, header.length).unwrap();
+
+    let last_tick = commands.last().unwrap().tick;
+    assert_eq!(
+        replay_duration(&commands),
+        Some(Duration::from_millis(u64::from(last_tick) * 100))
+    );
+
+    commands.pop();
+    assert_eq!(replay_duration(&commands), None);
+}
+
+#[test]
+fn rejects_truncated_command() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let header = parse_replay_header(&raw_replay).unwrap();
+
+    match parse_replay_commands(&raw_replay[..raw_replay.len() - 1], header.length) {
+        Err(ReplayError::InvalidBody(_)) => {}
+        other => panic!("expected an invalid body, got {:?}", other),
+    }
+}
+
+#[test]
+fn counts_commands_and_ticks_up_to_an_invalid_part() {
+    let raw_replay = read_fixture("example.scfareplay");
+    let header = parse_replay_header(&raw_replay).unwrap();
+    let commands = parse_replay_commands(&raw_replay, header.length).unwrap();
+
+    let count = count_replay_commands(&raw_replay, header.length);
+    assert_eq!(count.commands, commands.len());
+    assert_eq!(count.ticks, commands.last().unwrap().tick);
+    assert!(!count.is_approximate());
+
+    let last_command = commands.last().unwrap();
+    let truncated = count_replay_commands(&raw_replay[..raw_replay.len() - 1], header.length);
+    assert_eq!(truncated.commands, commands.len() - 1);
+    assert_eq!(truncated.invalid_at, Some(last_command.offset));
+}
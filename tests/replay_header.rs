@@ -0,0 +1,68 @@
+extern crate faf_replay_cli;
+extern crate tempfile;
+
+mod common;
+
+use std::io::Write;
+
+use faf_replay_cli::{parse_replay_header, prepare_replay_file, LuaValue, ReplayError};
+
+use common::read_fixture;
+
+#[test]
+fn parses_header_of_raw_replay() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    let header = parse_replay_header(&raw_replay).unwrap();
+
+    assert_eq!(header.game_version, "Supreme Commander v1.50.3701");
+    assert_eq!(header.replay_version, "Replay v1.9");
+    assert_eq!(
+        header.map_path,
+        "/maps/setons_clutch.v0004/setons_clutch.scmap"
+    );
+    assert_eq!(header.map_name(), Some("Seton's Clutch"));
+    assert_eq!(
+        header
+            .players
+            .iter()
+            .map(|player| (player.name.as_str(), player.id))
+            .collect::<Vec<_>>(),
+        [("Alice", 0), ("Bob", 1)]
+    );
+    assert_eq!(header.armies.len(), 2);
+    assert_eq!(
+        header.armies[1].data.get("PlayerName"),
+        Some(&LuaValue::String("Bob".to_string()))
+    );
+    assert_eq!(header.armies[1].source, 1);
+    assert_eq!(header.random_seed, 0x1234abcd);
+    assert_eq!(header.length, 412);
+}
+
+#[test]
+fn rejects_truncated_header() {
+    let raw_replay = read_fixture("example.scfareplay");
+
+    match parse_replay_header(&raw_replay[..200]) {
+        Err(ReplayError::InvalidHeader(_)) => {}
+        other => panic!("expected an invalid header, got {:?}", other),
+    }
+}
+
+#[test]
+fn refuses_to_prepare_raw_replay_without_header_magic() {
+    for content in [&b""[..], b"MZ\x90\x00 not a replay"] {
+        let mut replay_file = tempfile::Builder::new()
+            .suffix(".scfareplay")
+            .tempfile()
+            .unwrap();
+        replay_file.write_all(content).unwrap();
+
+        match prepare_replay_file(replay_file.path()) {
+            Err(ReplayError::InvalidHeader(_)) => {}
+            Err(e) => panic!("expected an invalid header, got {:?}", e),
+            Ok(_) => panic!("expected an invalid header for {:?}", content),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(unix)]
+
+extern crate tempfile;
+
+mod common;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{cli_command, fixture_path, install_fake_game};
+
+/// Stands in for the game, notes that it was launched
+const GAME_SCRIPT: &str = r#"#!/bin/sh
+touch "$(dirname "$0")/launched"
+"#;
+
+fn wait_until(what: &str, done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !done() {
+        assert!(Instant::now() < deadline, "{} never happened", what);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn keeps_watching_after_replays_with_non_utf8_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = install_fake_game(dir.path(), GAME_SCRIPT);
+    let watch_dir = dir.path().join("replays");
+    fs::create_dir_all(&watch_dir).unwrap();
+    let output_path = dir.path().join("output.txt");
+    let output = fs::File::create(&output_path).unwrap();
+    let read_output = || fs::read_to_string(&output_path).unwrap_or_default();
+
+    let mut cli = cli_command(dir.path())
+        .arg("--watch-dir")
+        .arg(&watch_dir)
+        .arg("--executable")
+        .arg(&executable)
+        .arg("--no-auto-log")
+        .stdin(Stdio::null())
+        .stdout(output.try_clone().unwrap())
+        .stderr(output)
+        .spawn()
+        .unwrap();
+    wait_until("watching", || read_output().contains("Watching"));
+
+    // raw replays are passed to the game at their path, which it can't get as a string
+    fs::copy(
+        fixture_path("example.scfareplay"),
+        watch_dir.join(OsStr::from_bytes(b"bad\xff.scfareplay")),
+    )
+    .unwrap();
+    wait_until("skipping the raw replay", || {
+        read_output().contains("is not valid UTF-8")
+    });
+    fs::copy(
+        fixture_path("example.fafreplay"),
+        watch_dir.join(OsStr::from_bytes(b"bad\xff.fafreplay")),
+    )
+    .unwrap();
+    wait_until("launching the extracted replay", || {
+        executable.with_file_name("launched").exists()
+    });
+
+    cli.kill().unwrap();
+    cli.wait().unwrap();
+    assert!(!read_output().contains("panicked"), "{}", read_output());
+}